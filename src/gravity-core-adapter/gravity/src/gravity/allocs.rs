@@ -17,6 +17,20 @@ pub fn allocation_by_instruction_index(
             GravityContractInstruction::BFT_ALLOC,
             GravityContractInstruction::LAST_ROUND_ALLOC,
             GravityContractInstruction::PUBKEY_ALLOC * oracles_bft.unwrap(),
+            GravityContractInstruction::BFT_ALLOC,
+        ],
+        // UpdateConsulsWithSignatures
+        2 => vec![
+            GravityContractInstruction::BFT_ALLOC,
+            GravityContractInstruction::LAST_ROUND_ALLOC,
+            GravityContractInstruction::PUBKEY_ALLOC * oracles_bft.unwrap(),
+            GravityContractInstruction::BFT_ALLOC,
+        ],
+        // VerifyConsulsAtRound
+        3 => vec![
+            GravityContractInstruction::BFT_ALLOC,
+            GravityContractInstruction::LAST_ROUND_ALLOC,
+            GravityContractInstruction::PUBKEY_ALLOC * oracles_bft.unwrap(),
         ],
         _ => return Err(GravityError::InvalidInstructionIndex.into()),
     })
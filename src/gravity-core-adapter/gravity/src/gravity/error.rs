@@ -19,6 +19,18 @@ pub enum GravityError {
 
     #[error("Invalid instruction index")]
     InvalidInstructionIndex,
+    /// New Bft > New Consuls Count
+    #[error("New bft exceeds new consuls count")]
+    InvalidNewBFT,
+    /// Consuls Signatures < Bft
+    #[error("Not enough consuls signatures")]
+    NotEnoughConsulsSignatures,
+
+    #[error("Round is not covered by consuls history")]
+    RoundIsNotInHistory,
+
+    #[error("Consuls do not match the round consuls")]
+    ConsulsMismatch,
 }
 
 impl From<GravityError> for ProgramError {
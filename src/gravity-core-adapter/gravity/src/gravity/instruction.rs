@@ -3,8 +3,10 @@
 
 use arrayref::array_ref;
 use solana_program::{
+    instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     pubkey::Pubkey,
+    sysvar,
 };
 
 
@@ -16,6 +18,7 @@ use crate::gravity::allocs::allocation_by_instruction_index;
 use crate::gravity::error::GravityError::InvalidInstruction;
 
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum GravityContractInstruction {
     InitContract {
         new_consuls: Vec<Pubkey>,
@@ -25,6 +28,18 @@ pub enum GravityContractInstruction {
     UpdateConsuls {
         new_consuls: Vec<Pubkey>,
         current_round: u64,
+        new_bft: u8,
+    },
+    // same payload as UpdateConsuls, authorized by Ed25519 consul signatures instead of signer accounts
+    UpdateConsulsWithSignatures {
+        new_consuls: Vec<Pubkey>,
+        current_round: u64,
+        new_bft: u8,
+    },
+    // fails unless `consuls` is the consul set that was valid at `round`
+    VerifyConsulsAtRound {
+        consuls: Vec<Pubkey>,
+        round: u64,
     },
 }
 
@@ -56,24 +71,306 @@ impl GravityContractInstruction {
                     bft,
                 }
             }
-            1 => {
-                let bft = extract_from_range(rest, 0..1, |x: &[u8]| {
+            1 | 2 => {
+                // leading byte is the size of the new consul set, the threshold goes last
+                let consuls_count = extract_from_range(rest, 0..1, |x: &[u8]| {
                     u8::from_le_bytes(*array_ref![x, 0, 1])
                 })?;
-                let allocs = allocation_by_instruction_index((*tag).into(), Some(bft as usize))?;
+                let allocs = allocation_by_instruction_index((*tag).into(), Some(consuls_count as usize))?;
                 let ranges = build_range_from_alloc(&allocs);
 
                 let current_round = extract_from_range(rest, ranges[1].clone(), |x: &[u8]| {
                     u64::from_le_bytes(*array_ref![x, 0, 8])
                 })?;
-                let new_consuls = retrieve_consuls(rest, ranges[2].clone(), bft)?;
+                let new_consuls = retrieve_consuls(rest, ranges[2].clone(), consuls_count)?;
+                let new_bft = extract_from_range(rest, ranges[3].clone(), |x: &[u8]| {
+                    u8::from_le_bytes(*array_ref![x, 0, 1])
+                })?;
 
-                Self::UpdateConsuls {
-                    new_consuls,
-                    current_round,
+                match tag {
+                    1 => Self::UpdateConsuls {
+                        new_consuls,
+                        current_round,
+                        new_bft,
+                    },
+                    _ => Self::UpdateConsulsWithSignatures {
+                        new_consuls,
+                        current_round,
+                        new_bft,
+                    },
                 }
             }
+            3 => {
+                let consuls_count = extract_from_range(rest, 0..1, |x: &[u8]| {
+                    u8::from_le_bytes(*array_ref![x, 0, 1])
+                })?;
+                let allocs = allocation_by_instruction_index((*tag).into(), Some(consuls_count as usize))?;
+                let ranges = build_range_from_alloc(&allocs);
+
+                let round = extract_from_range(rest, ranges[1].clone(), |x: &[u8]| {
+                    u64::from_le_bytes(*array_ref![x, 0, 8])
+                })?;
+                let consuls = retrieve_consuls(rest, ranges[2].clone(), consuls_count)?;
+
+                Self::VerifyConsulsAtRound { consuls, round }
+            }
             _ => return Err(InvalidInstruction.into()),
         })
     }
 }
+
+
+impl GravityContractInstruction {
+    fn pack_consuls(buf: &mut Vec<u8>, consuls: &[Pubkey], round: u64) {
+        buf.push(consuls.len() as u8);
+        buf.extend_from_slice(&round.to_le_bytes());
+        for consul in consuls {
+            buf.extend_from_slice(&consul.to_bytes());
+        }
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        match self {
+            &Self::InitContract {
+                ref new_consuls,
+                current_round,
+                bft,
+            } => {
+                buf.push(0);
+                buf.push(bft);
+                buf.extend_from_slice(&current_round.to_le_bytes());
+                for consul in new_consuls {
+                    buf.extend_from_slice(&consul.to_bytes());
+                }
+            }
+            &Self::UpdateConsuls {
+                ref new_consuls,
+                current_round,
+                new_bft,
+            } => {
+                buf.push(1);
+                Self::pack_consuls(&mut buf, new_consuls, current_round);
+                buf.push(new_bft);
+            }
+            &Self::UpdateConsulsWithSignatures {
+                ref new_consuls,
+                current_round,
+                new_bft,
+            } => {
+                buf.push(2);
+                Self::pack_consuls(&mut buf, new_consuls, current_round);
+                buf.push(new_bft);
+            }
+            &Self::VerifyConsulsAtRound { ref consuls, round } => {
+                buf.push(3);
+                Self::pack_consuls(&mut buf, consuls, round);
+            }
+        }
+        buf
+    }
+}
+
+/// Creates an `InitContract` instruction, `consuls.len()` is used as the initial bft.
+///
+/// Accounts expected:
+///   0. `[signer]` Initializer
+///   1. `[writable]` Gravity data account
+///   2. `[writable]` Multisig account (spl-token Multisig::LEN sized or a native GravityMultisig)
+pub fn init_contract(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    gravity_contract: &Pubkey,
+    multisig: &Pubkey,
+    consuls: &[Pubkey],
+    current_round: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::InitContract {
+        new_consuls: consuls.to_vec(),
+        current_round,
+        bft: consuls.len() as u8,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*gravity_contract, false),
+        AccountMeta::new(*multisig, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `UpdateConsuls` instruction.
+///
+/// Accounts expected:
+///   0. `[signer]` Initializer
+///   1. `[writable]` Gravity data account
+///   2. `[writable]` Multisig account
+///   3. ..3+bft `[signer]` Current consuls
+pub fn update_consuls(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    gravity_contract: &Pubkey,
+    multisig: &Pubkey,
+    consul_signers: &[&Pubkey],
+    new_consuls: &[Pubkey],
+    current_round: u64,
+    new_bft: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::UpdateConsuls {
+        new_consuls: new_consuls.to_vec(),
+        current_round,
+        new_bft,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(3 + consul_signers.len());
+    accounts.push(AccountMeta::new_readonly(*initializer, true));
+    accounts.push(AccountMeta::new(*gravity_contract, false));
+    accounts.push(AccountMeta::new(*multisig, false));
+    for consul_signer in consul_signers.iter() {
+        accounts.push(AccountMeta::new_readonly(**consul_signer, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `UpdateConsulsWithSignatures` instruction. The transaction must also carry
+/// an Ed25519 program instruction with the consuls signatures over
+/// `GravityContract::consuls_update_message`.
+///
+/// Accounts expected:
+///   0. `[signer]` Relayer
+///   1. `[writable]` Gravity data account
+///   2. `[writable]` Multisig account
+///   3. `[]` Instructions sysvar
+pub fn update_consuls_with_signatures(
+    program_id: &Pubkey,
+    relayer: &Pubkey,
+    gravity_contract: &Pubkey,
+    multisig: &Pubkey,
+    new_consuls: &[Pubkey],
+    current_round: u64,
+    new_bft: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::UpdateConsulsWithSignatures {
+        new_consuls: new_consuls.to_vec(),
+        current_round,
+        new_bft,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*relayer, true),
+        AccountMeta::new(*gravity_contract, false),
+        AccountMeta::new(*multisig, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `VerifyConsulsAtRound` instruction.
+///
+/// Accounts expected:
+///   0. `[]` Gravity data account
+pub fn verify_consuls_at_round(
+    program_id: &Pubkey,
+    gravity_contract: &Pubkey,
+    consuls: &[Pubkey],
+    round: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = GravityContractInstruction::VerifyConsulsAtRound {
+        consuls: consuls.to_vec(),
+        round,
+    }
+    .pack();
+
+    let accounts = vec![AccountMeta::new_readonly(*gravity_contract, false)];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn consuls(count: usize) -> Vec<Pubkey> {
+        (0..count).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    #[test]
+    fn test_pack_unpack_round_trip() {
+        let instructions = vec![
+            GravityContractInstruction::InitContract {
+                new_consuls: consuls(3),
+                current_round: 7,
+                bft: 3,
+            },
+            GravityContractInstruction::UpdateConsuls {
+                new_consuls: consuls(5),
+                current_round: 8,
+                new_bft: 4,
+            },
+            GravityContractInstruction::UpdateConsulsWithSignatures {
+                new_consuls: consuls(12),
+                current_round: u64::MAX,
+                new_bft: 9,
+            },
+            GravityContractInstruction::VerifyConsulsAtRound {
+                consuls: consuls(2),
+                round: 3,
+            },
+        ];
+
+        for instruction in instructions {
+            let packed = instruction.pack();
+            assert_eq!(GravityContractInstruction::unpack(&packed).unwrap(), instruction);
+        }
+    }
+
+    #[test]
+    fn test_update_consuls_accounts_order() {
+        let (program_id, initializer, gravity, multisig) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let current_consuls = consuls(2);
+        let signers: Vec<&Pubkey> = current_consuls.iter().collect();
+
+        let instruction = update_consuls(
+            &program_id,
+            &initializer,
+            &gravity,
+            &multisig,
+            &signers,
+            &consuls(3),
+            1,
+            2,
+        )
+        .unwrap();
+
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(keys, vec![initializer, gravity, multisig, current_consuls[0], current_consuls[1]]);
+        assert!(instruction.accounts[3..].iter().all(|meta| meta.is_signer));
+    }
+}
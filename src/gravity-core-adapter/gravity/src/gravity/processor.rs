@@ -14,9 +14,12 @@ use spl_token::{
 };
 
 use crate::gravity::{
-    error::GravityError, instruction::GravityContractInstruction, state::GravityContract,
+    error::GravityError,
+    instruction::GravityContractInstruction,
+    state::{AccountStorage, GravityContract, GravityMultisig},
 };
 
+use gravity_misc::ed25519::verified_signers;
 use gravity_misc::model::PulseID;
 use gravity_misc::validation::{validate_contract_emptiness, validate_contract_non_emptiness};
 
@@ -49,10 +52,37 @@ impl GravityProcessor {
             GravityContractInstruction::UpdateConsuls {
                 current_round,
                 new_consuls,
+                new_bft,
             } => {
                 msg!("Instruction: Update Gravity Consuls");
 
-                Self::process_update_consuls(accounts, current_round, new_consuls, program_id)
+                Self::process_update_consuls(
+                    accounts,
+                    current_round,
+                    new_consuls,
+                    new_bft,
+                    program_id,
+                )
+            }
+            GravityContractInstruction::UpdateConsulsWithSignatures {
+                current_round,
+                new_consuls,
+                new_bft,
+            } => {
+                msg!("Instruction: Update Gravity Consuls With Signatures");
+
+                Self::process_update_consuls_with_signatures(
+                    accounts,
+                    current_round,
+                    new_consuls,
+                    new_bft,
+                    program_id,
+                )
+            }
+            GravityContractInstruction::VerifyConsulsAtRound { consuls, round } => {
+                msg!("Instruction: Verify Gravity Consuls At Round");
+
+                Self::process_verify_consuls_at_round(accounts, round, consuls, program_id)
             }
         }
     }
@@ -60,7 +90,7 @@ impl GravityProcessor {
     fn process_init_gravity_contract(
         accounts: &[AccountInfo],
         new_consuls: Vec<Pubkey>,
-        _current_round: PulseID,
+        current_round: PulseID,
         bft: u8,
         _program_id: &Pubkey,
     ) -> ProgramResult {
@@ -97,10 +127,10 @@ impl GravityProcessor {
 
         gravity_contract_info.multisig_account = *gravity_contract_multisig_account.key;
 
-        GravityContract::pack(
-            gravity_contract_info,
-            &mut gravity_contract_account.try_borrow_mut_data()?[0..GravityContract::LEN],
-        )?;
+        gravity_contract_info.record_consuls(current_round);
+        gravity_contract_info.fit_consuls_history(gravity_contract_account.data_len());
+
+        gravity_contract_info.pack_into_account(&mut gravity_contract_account.try_borrow_mut_data()?)?;
 
         Ok(())
     }
@@ -109,6 +139,7 @@ impl GravityProcessor {
         accounts: &[AccountInfo],
         current_round: u64,
         new_consuls: Vec<Pubkey>,
+        new_bft: u8,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -122,9 +153,8 @@ impl GravityProcessor {
         
         validate_contract_non_emptiness(&gravity_contract_account.try_borrow_data()?[..])?;
 
-        let mut gravity_contract_info = GravityContract::unpack(
-            &gravity_contract_account.try_borrow_data()?[0..GravityContract::LEN],
-        )?;
+        let gravity_contract_info =
+            GravityContract::unpack_from_account(&gravity_contract_account.try_borrow_data()?)?;
         if !gravity_contract_info.is_initialized() {
             return Err(ProgramError::UninitializedAccount);
         }
@@ -132,46 +162,205 @@ impl GravityProcessor {
         msg!("picking multisig account");
         let gravity_contract_multisig_account = next_account_info(account_info_iter)?;
 
+        let consul_signers = accounts
+            .get(3..3 + gravity_contract_info.bft as usize)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
         match MiscProcessor::validate_owner(
             program_id,
-            &gravity_contract_multisig_account.key,
+            &gravity_contract_info.multisig_account,
             &gravity_contract_multisig_account,
-            &accounts[3..3 + gravity_contract_info.bft as usize].to_vec(),
+            consul_signers,
         ) {
             Err(_) => return Err(GravityError::InvalidBFTCount.into()),
             _ => {}
         };
 
+        Self::apply_consuls_update(
+            gravity_contract_account,
+            gravity_contract_multisig_account,
+            gravity_contract_info,
+            current_round,
+            new_consuls,
+            new_bft,
+        )
+    }
+
+    pub fn process_update_consuls_with_signatures(
+        accounts: &[AccountInfo],
+        current_round: u64,
+        new_consuls: Vec<Pubkey>,
+        new_bft: u8,
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        // any relayer may submit the transition, authority comes from the signatures
+        let relayer = next_account_info(account_info_iter)?;
+
+        if !relayer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+
+        validate_contract_non_emptiness(&gravity_contract_account.try_borrow_data()?[..])?;
+
+        let gravity_contract_info =
+            GravityContract::unpack_from_account(&gravity_contract_account.try_borrow_data()?)?;
+        if !gravity_contract_info.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        msg!("picking multisig account");
+        let gravity_contract_multisig_account = next_account_info(account_info_iter)?;
+
+        if *gravity_contract_multisig_account.key != gravity_contract_info.multisig_account {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let instructions_sysvar_account = next_account_info(account_info_iter)?;
+
+        msg!("verifying consuls signatures");
+        let message = GravityContract::consuls_update_message(
+            gravity_contract_account.key,
+            &new_consuls,
+            current_round,
+            new_bft,
+        );
+        let signers = verified_signers(instructions_sysvar_account, &message)?;
+
+        let approvals = gravity_contract_info
+            .consuls
+            .iter()
+            .filter(|consul| signers.contains(consul))
+            .count();
+
+        if approvals < gravity_contract_info.bft as usize {
+            return Err(GravityError::NotEnoughConsulsSignatures.into());
+        }
+
+        Self::apply_consuls_update(
+            gravity_contract_account,
+            gravity_contract_multisig_account,
+            gravity_contract_info,
+            current_round,
+            new_consuls,
+            new_bft,
+        )
+    }
+
+    pub fn process_verify_consuls_at_round(
+        accounts: &[AccountInfo],
+        round: u64,
+        consuls: Vec<Pubkey>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+
+        if gravity_contract_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let gravity_contract_info =
+            GravityContract::unpack_from_account(&gravity_contract_account.try_borrow_data()?)?;
+
+        let snapshot = gravity_contract_info
+            .consuls_at_round(round)
+            .ok_or(GravityError::RoundIsNotInHistory)?;
+
+        if snapshot.consuls_hash != GravityContract::consuls_hash(&consuls) {
+            return Err(GravityError::ConsulsMismatch.into());
+        }
+
+        Ok(())
+    }
+
+    fn apply_consuls_update(
+        gravity_contract_account: &AccountInfo,
+        gravity_contract_multisig_account: &AccountInfo,
+        mut gravity_contract_info: GravityContract,
+        current_round: u64,
+        new_consuls: Vec<Pubkey>,
+        new_bft: u8,
+    ) -> ProgramResult {
         if current_round <= gravity_contract_info.last_round {
             return Err(GravityError::InputRoundMismatch.into());
         }
 
-        gravity_contract_info.last_round = current_round;
-        gravity_contract_info.consuls = new_consuls.clone();
+        if new_bft == 0 || new_bft as usize > new_consuls.len() {
+            return Err(GravityError::InvalidNewBFT.into());
+        }
 
-        GravityContract::pack(
-            gravity_contract_info,
-            &mut gravity_contract_account.try_borrow_mut_data()?[0..GravityContract::LEN],
+        msg!("updating multisig signers");
+        MiscProcessor::process_update_multisig(
+            &gravity_contract_multisig_account,
+            &new_consuls,
+            new_bft,
         )?;
 
+        gravity_contract_info.last_round = current_round;
+        gravity_contract_info.bft = new_bft;
+        gravity_contract_info.consuls = new_consuls;
+
+        gravity_contract_info.record_consuls(current_round);
+        gravity_contract_info.fit_consuls_history(gravity_contract_account.data_len());
+
+        gravity_contract_info.pack_into_account(&mut gravity_contract_account.try_borrow_mut_data()?)?;
+
         Ok(())
     }
 }
 
 pub struct MiscProcessor;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MultisigKind {
+    Spl,
+    Native,
+}
+
 impl MiscProcessor {
+    // Native multisigs are tagged with GravityMultisig::DISCRIMINATOR on init.
+    // Untagged accounts are spl-token multisigs, which only exist as Multisig::LEN sized accounts (legacy),
+    // any other untagged account is an uninitialized native multisig.
+    pub fn multisig_kind(multisig_account: &AccountInfo) -> Result<MultisigKind, ProgramError> {
+        let data = multisig_account.try_borrow_data()?;
+
+        if GravityMultisig::is_tagged(&data) || data.len() != Multisig::get_packed_len() {
+            return Ok(MultisigKind::Native);
+        }
+        Ok(MultisigKind::Spl)
+    }
+
+    fn validate_multisig_bounds(signers_count: usize, minumum_bft: u8) -> ProgramResult {
+        if signers_count == 0 || signers_count > GravityMultisig::MAX_SIGNERS {
+            return Err(TokenError::InvalidNumberOfProvidedSigners.into());
+        }
+        if minumum_bft == 0 || minumum_bft as usize > signers_count {
+            return Err(TokenError::InvalidNumberOfRequiredSigners.into());
+        }
+        Ok(())
+    }
+
     pub fn process_init_multisig(
         multisig_account: &AccountInfo,
         signer_pubkeys: &[Pubkey],
         minumum_bft: u8,
     ) -> ProgramResult {
+        if Self::multisig_kind(multisig_account)? == MultisigKind::Native {
+            return Self::process_init_native_multisig(multisig_account, signer_pubkeys, minumum_bft);
+        }
+
         let mut multisig = Multisig::unpack_unchecked(&multisig_account.try_borrow_data()?)?;
 
         if multisig.is_initialized {
             return Err(TokenError::AlreadyInUse.into());
         }
 
+        Self::validate_multisig_bounds(signer_pubkeys.len(), minumum_bft)?;
+
         multisig.m = minumum_bft;
         multisig.n = signer_pubkeys.len() as u8;
         if !is_valid_signer_index(multisig.n as usize) {
@@ -190,6 +379,104 @@ impl MiscProcessor {
         Ok(())
     }
 
+    fn process_init_native_multisig(
+        multisig_account: &AccountInfo,
+        signer_pubkeys: &[Pubkey],
+        minumum_bft: u8,
+    ) -> ProgramResult {
+        let mut multisig = GravityMultisig::unpack_from_account(&multisig_account.try_borrow_data()?)?;
+
+        if multisig.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        Self::validate_multisig_bounds(signer_pubkeys.len(), minumum_bft)?;
+
+        multisig.discriminator = GravityMultisig::DISCRIMINATOR;
+        multisig.m = minumum_bft;
+        multisig.signers = signer_pubkeys.to_vec();
+        multisig.is_initialized = true;
+
+        multisig.pack_into_account(&mut multisig_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    // rewrites signers and threshold of an already initialized multisig,
+    // so the rotated set becomes the authority for the next round
+    pub fn process_update_multisig(
+        multisig_account: &AccountInfo,
+        signer_pubkeys: &[Pubkey],
+        minumum_bft: u8,
+    ) -> ProgramResult {
+        if Self::multisig_kind(multisig_account)? == MultisigKind::Native {
+            return Self::process_update_native_multisig(multisig_account, signer_pubkeys, minumum_bft);
+        }
+
+        let mut multisig = Multisig::unpack(&multisig_account.try_borrow_data()?)?;
+
+        Self::validate_multisig_bounds(signer_pubkeys.len(), minumum_bft)?;
+
+        multisig.m = minumum_bft;
+        multisig.n = signer_pubkeys.len() as u8;
+        if !is_valid_signer_index(multisig.n as usize) {
+            return Err(TokenError::InvalidNumberOfProvidedSigners.into());
+        }
+        if !is_valid_signer_index(multisig.m as usize) {
+            return Err(TokenError::InvalidNumberOfRequiredSigners.into());
+        }
+
+        multisig.signers = [Pubkey::default(); Self::MAX_SIGNERS];
+        for (i, signer_pubkey) in signer_pubkeys.iter().enumerate() {
+            multisig.signers[i] = *signer_pubkey;
+        }
+
+        Multisig::pack(multisig, &mut multisig_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    fn process_update_native_multisig(
+        multisig_account: &AccountInfo,
+        signer_pubkeys: &[Pubkey],
+        minumum_bft: u8,
+    ) -> ProgramResult {
+        let mut multisig = GravityMultisig::unpack_from_account(&multisig_account.try_borrow_data()?)?;
+
+        if !multisig.is_initialized {
+            return Err(TokenError::UninitializedState.into());
+        }
+
+        Self::validate_multisig_bounds(signer_pubkeys.len(), minumum_bft)?;
+
+        multisig.m = minumum_bft;
+        multisig.signers = signer_pubkeys.to_vec();
+
+        multisig.pack_into_account(&mut multisig_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
+    // returns the signer set and the threshold of either multisig kind
+    pub fn unpack_multisig(multisig_account: &AccountInfo) -> Result<(Vec<Pubkey>, u8), ProgramError> {
+        if Self::multisig_kind(multisig_account)? == MultisigKind::Spl {
+            let multisig = Multisig::unpack(&multisig_account.try_borrow_data()?)?;
+            return Ok((multisig.signers[0..multisig.n as usize].to_vec(), multisig.m));
+        }
+
+        let multisig = GravityMultisig::unpack_from_account(&multisig_account.try_borrow_data()?)?;
+        if !multisig.is_initialized {
+            return Err(TokenError::UninitializedState.into());
+        }
+
+        Ok((multisig.signers, multisig.m))
+    }
+
+    pub fn multisig_signers(multisig_account: &AccountInfo) -> Result<Vec<Pubkey>, ProgramError> {
+        let (signers, _) = Self::unpack_multisig(multisig_account)?;
+        Ok(signers)
+    }
+
     const MAX_SIGNERS: usize = 11;
     pub fn validate_owner(
         program_id: &Pubkey,
@@ -200,14 +487,12 @@ impl MiscProcessor {
         if expected_owner != owner_account_info.key {
             return Err(TokenError::OwnerMismatch.into());
         }
-        if program_id == owner_account_info.owner
-            && owner_account_info.data_len() == Multisig::get_packed_len()
-        {
-            let multisig = Multisig::unpack(&owner_account_info.try_borrow_data()?)?;
+        if program_id == owner_account_info.owner {
+            let (multisig_signers, m) = Self::unpack_multisig(owner_account_info)?;
             let mut num_signers = 0;
-            let mut matched = [false; Self::MAX_SIGNERS];
+            let mut matched = vec![false; multisig_signers.len()];
             for signer in signers.iter() {
-                for (position, key) in multisig.signers[0..multisig.n as usize].iter().enumerate() {
+                for (position, key) in multisig_signers.iter().enumerate() {
                     if key == signer.key && !matched[position] {
                         if !signer.is_signer {
                             return Err(ProgramError::MissingRequiredSignature);
@@ -217,7 +502,7 @@ impl MiscProcessor {
                     }
                 }
             }
-            if num_signers < multisig.m {
+            if num_signers < m {
                 return Err(ProgramError::MissingRequiredSignature);
             }
             return Ok(());
@@ -227,3 +512,326 @@ impl MiscProcessor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshSerialize;
+    use gravity_misc::test_support::{process_with_signers, TestAccount};
+
+    fn init_instruction_data(consuls: &[Pubkey], round: u64) -> Vec<u8> {
+        GravityContractInstruction::InitContract {
+            new_consuls: consuls.to_vec(),
+            current_round: round,
+            bft: consuls.len() as u8,
+        }
+        .pack()
+    }
+
+    fn update_instruction_data(consuls: &[Pubkey], round: u64, new_bft: u8) -> Vec<u8> {
+        GravityContractInstruction::UpdateConsuls {
+            new_consuls: consuls.to_vec(),
+            current_round: round,
+            new_bft,
+        }
+        .pack()
+    }
+
+    fn new_consuls(count: usize) -> Vec<Pubkey> {
+        (0..count).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    #[test]
+    fn test_multisig_follows_consuls_rotation() {
+        let program_id = Pubkey::new_unique();
+        let mut gravity = TestAccount::new(Pubkey::new_unique(), GravityContract::LEN, program_id, false);
+        let mut multisig = TestAccount::new(Pubkey::new_unique(), Multisig::LEN, program_id, false);
+
+        // InitContract sizes the consul set by bft
+        let bft = 2;
+        let genesis_consuls = new_consuls(bft as usize);
+        let init_data = init_instruction_data(&genesis_consuls, 0);
+        process_with_signers(GravityProcessor::process, &program_id, &mut gravity, &mut multisig, &[], &init_data).unwrap();
+
+        let mut current_consuls = genesis_consuls;
+        for round in 1..=3 {
+            let next_consuls = new_consuls(3);
+            let update_data = update_instruction_data(&next_consuls, round, bft);
+
+            process_with_signers(
+                GravityProcessor::process,
+                &program_id,
+                &mut gravity,
+                &mut multisig,
+                &current_consuls[0..bft as usize],
+                &update_data,
+            )
+            .unwrap();
+
+            let multisig_info = Multisig::unpack(&multisig.data).unwrap();
+            assert_eq!(multisig_info.m, bft);
+            assert_eq!(multisig_info.n as usize, next_consuls.len());
+            assert_eq!(multisig_info.signers[0..next_consuls.len()], next_consuls[..]);
+
+            let gravity_info = GravityContract::unpack(&gravity.data).unwrap();
+            assert_eq!(gravity_info.consuls, next_consuls);
+            assert_eq!(gravity_info.last_round, round);
+
+            // the previous consul set must lose its authority
+            let stale_update_data = update_instruction_data(&new_consuls(3), round + 1, bft);
+            assert!(process_with_signers(
+                GravityProcessor::process,
+                &program_id,
+                &mut gravity,
+                &mut multisig,
+                &current_consuls[0..bft as usize],
+                &stale_update_data,
+            )
+            .is_err());
+
+            current_consuls = next_consuls;
+        }
+    }
+
+    #[test]
+    fn test_update_consuls_rejects_foreign_multisig() {
+        let program_id = Pubkey::new_unique();
+        let mut gravity = TestAccount::new(Pubkey::new_unique(), GravityContract::LEN, program_id, false);
+        let mut multisig = TestAccount::new(Pubkey::new_unique(), Multisig::LEN, program_id, false);
+
+        let consuls = new_consuls(2);
+        let init_data = init_instruction_data(&consuls, 0);
+        process_with_signers(GravityProcessor::process, &program_id, &mut gravity, &mut multisig, &[], &init_data).unwrap();
+
+        // a multisig controlled by someone else must not authorize the rotation
+        let attacker_consuls = new_consuls(2);
+        let mut foreign_multisig = TestAccount::new(Pubkey::new_unique(), Multisig::LEN, program_id, false);
+        MiscProcessor::process_init_multisig(&foreign_multisig.info(), &attacker_consuls, 2).unwrap();
+
+        let update_data = update_instruction_data(&attacker_consuls, 1, 2);
+        assert!(process_with_signers(
+            GravityProcessor::process,
+            &program_id,
+            &mut gravity,
+            &mut foreign_multisig,
+            &attacker_consuls,
+            &update_data,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_update_consuls_changes_bft() {
+        let program_id = Pubkey::new_unique();
+        let mut gravity = TestAccount::new(Pubkey::new_unique(), GravityContract::LEN, program_id, false);
+        let mut multisig = TestAccount::new(Pubkey::new_unique(), Multisig::LEN, program_id, false);
+
+        let genesis_consuls = new_consuls(2);
+        let init_data = init_instruction_data(&genesis_consuls, 0);
+        process_with_signers(GravityProcessor::process, &program_id, &mut gravity, &mut multisig, &[], &init_data).unwrap();
+
+        // threshold above the new consul count is rejected
+        let grown_consuls = new_consuls(5);
+        let invalid_data = update_instruction_data(&grown_consuls, 1, 6);
+        assert!(process_with_signers(
+            GravityProcessor::process,
+            &program_id,
+            &mut gravity,
+            &mut multisig,
+            &genesis_consuls,
+            &invalid_data,
+        )
+        .is_err());
+
+        // grow the set and raise the threshold in one transition
+        let update_data = update_instruction_data(&grown_consuls, 1, 4);
+        process_with_signers(GravityProcessor::process, &program_id, &mut gravity, &mut multisig, &genesis_consuls, &update_data).unwrap();
+
+        let gravity_info = GravityContract::unpack(&gravity.data).unwrap();
+        assert_eq!(gravity_info.bft, 4);
+        assert_eq!(gravity_info.consuls, grown_consuls);
+
+        let multisig_info = Multisig::unpack(&multisig.data).unwrap();
+        assert_eq!(multisig_info.m, 4);
+        assert_eq!(multisig_info.n, 5);
+
+        // the next round now requires 4 signatures out of the grown set
+        let shrunk_consuls = new_consuls(1);
+        let update_data = update_instruction_data(&shrunk_consuls, 2, 1);
+        assert!(process_with_signers(
+            GravityProcessor::process,
+            &program_id,
+            &mut gravity,
+            &mut multisig,
+            &grown_consuls[0..3],
+            &update_data,
+        )
+        .is_err());
+        process_with_signers(
+            GravityProcessor::process,
+            &program_id,
+            &mut gravity,
+            &mut multisig,
+            &grown_consuls[0..4],
+            &update_data,
+        )
+        .unwrap();
+
+        let gravity_info = GravityContract::unpack(&gravity.data).unwrap();
+        assert_eq!(gravity_info.bft, 1);
+        assert_eq!(gravity_info.consuls, shrunk_consuls);
+    }
+
+    #[test]
+    fn test_native_multisig_above_spl_signers_cap() {
+        let program_id = Pubkey::new_unique();
+        let consuls_limit = 20;
+        let mut gravity = TestAccount::new(Pubkey::new_unique(), 1024, program_id, false);
+        let mut multisig = TestAccount::new(
+            Pubkey::new_unique(),
+            GravityMultisig::packed_len(consuls_limit),
+            program_id,
+            false,
+        );
+
+        let genesis_consuls = new_consuls(15);
+        let init_data = init_instruction_data(&genesis_consuls, 0);
+        process_with_signers(GravityProcessor::process, &program_id, &mut gravity, &mut multisig, &[], &init_data).unwrap();
+
+        let multisig_info = GravityMultisig::unpack_from_account(&multisig.data).unwrap();
+        assert_eq!(multisig_info.m, 15);
+        assert_eq!(multisig_info.signers, genesis_consuls);
+
+        let next_consuls = new_consuls(consuls_limit);
+        let update_data = update_instruction_data(&next_consuls, 1, 13);
+        process_with_signers(GravityProcessor::process, &program_id, &mut gravity, &mut multisig, &genesis_consuls, &update_data).unwrap();
+
+        let gravity_info = GravityContract::unpack_from_account(&gravity.data).unwrap();
+        assert_eq!(gravity_info.consuls, next_consuls);
+        assert_eq!(gravity_info.bft, 13);
+
+        let multisig_info = GravityMultisig::unpack_from_account(&multisig.data).unwrap();
+        assert_eq!(multisig_info.m, 13);
+        assert_eq!(multisig_info.signers, next_consuls);
+
+        // 12 out of 13 required signatures are not enough
+        let update_data = update_instruction_data(&new_consuls(3), 2, 2);
+        assert!(process_with_signers(
+            GravityProcessor::process,
+            &program_id,
+            &mut gravity,
+            &mut multisig,
+            &next_consuls[0..12],
+            &update_data,
+        )
+        .is_err());
+
+        // a consul set that does not fit into the multisig account is rejected
+        let update_data = update_instruction_data(&new_consuls(consuls_limit + 1), 2, 2);
+        assert!(process_with_signers(
+            GravityProcessor::process,
+            &program_id,
+            &mut gravity,
+            &mut multisig,
+            &next_consuls[0..13],
+            &update_data,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_multisig_kind_follows_tag() {
+        let program_id = Pubkey::new_unique();
+        let consuls = new_consuls(3);
+
+        // a tagged native multisig is not taken for a spl-token one, whatever the account size
+        let mut multisig = TestAccount::new(Pubkey::new_unique(), Multisig::LEN, program_id, false);
+        GravityMultisig {
+            discriminator: GravityMultisig::DISCRIMINATOR,
+            is_initialized: true,
+            m: 2,
+            signers: consuls.clone(),
+        }
+        .pack_into_account(&mut multisig.data)
+        .unwrap();
+
+        assert_eq!(MiscProcessor::multisig_kind(&multisig.info()).unwrap(), MultisigKind::Native);
+        assert_eq!(MiscProcessor::unpack_multisig(&multisig.info()).unwrap(), (consuls, 2));
+
+        let next_consuls = new_consuls(2);
+        MiscProcessor::process_update_multisig(&multisig.info(), &next_consuls, 1).unwrap();
+        assert_eq!(MiscProcessor::unpack_multisig(&multisig.info()).unwrap(), (next_consuls, 1));
+
+        // untagged accounts of another size are uninitialized native multisigs
+        let mut multisig = TestAccount::new(Pubkey::new_unique(), GravityMultisig::packed_len(3), program_id, false);
+        assert!(MiscProcessor::unpack_multisig(&multisig.info()).is_err());
+
+        MiscProcessor::process_init_multisig(&multisig.info(), &new_consuls(3), 2).unwrap();
+        assert!(GravityMultisig::is_tagged(&multisig.data));
+    }
+
+    #[test]
+    fn test_consuls_history_lookup() {
+        let program_id = Pubkey::new_unique();
+        let mut gravity = TestAccount::new(Pubkey::new_unique(), 2048, program_id, false);
+        let mut multisig = TestAccount::new(Pubkey::new_unique(), Multisig::LEN, program_id, false);
+
+        let genesis_consuls = new_consuls(2);
+        let init_data = init_instruction_data(&genesis_consuls, 0);
+        process_with_signers(GravityProcessor::process, &program_id, &mut gravity, &mut multisig, &[], &init_data).unwrap();
+
+        let second_consuls = new_consuls(3);
+        let update_data = update_instruction_data(&second_consuls, 5, 2);
+        process_with_signers(GravityProcessor::process, &program_id, &mut gravity, &mut multisig, &genesis_consuls, &update_data).unwrap();
+
+        let third_consuls = new_consuls(3);
+        let update_data = update_instruction_data(&third_consuls, 9, 3);
+        process_with_signers(GravityProcessor::process, &program_id, &mut gravity, &mut multisig, &second_consuls[0..2], &update_data).unwrap();
+
+        let gravity_info = GravityContract::unpack_from_account(&gravity.data).unwrap();
+        for (round, consuls, bft) in &[
+            (0, &genesis_consuls, 2),
+            (4, &genesis_consuls, 2),
+            (5, &second_consuls, 2),
+            (8, &second_consuls, 2),
+            (9, &third_consuls, 3),
+            (100, &third_consuls, 3),
+        ] {
+            let snapshot = gravity_info.consuls_at_round(*round).unwrap();
+            assert_eq!(snapshot.consuls_hash, GravityContract::consuls_hash(consuls));
+            assert_eq!(snapshot.bft, *bft);
+        }
+
+        let verify = |consuls: &[Pubkey], round: u64, gravity: &mut TestAccount| {
+            let data = GravityContractInstruction::VerifyConsulsAtRound {
+                consuls: consuls.to_vec(),
+                round,
+            }
+            .pack();
+            GravityProcessor::process(&program_id, &[gravity.info()], &data)
+        };
+
+        assert!(verify(&second_consuls, 6, &mut gravity).is_ok());
+        assert!(verify(&third_consuls, 6, &mut gravity).is_err());
+        assert!(verify(&third_consuls, 9, &mut gravity).is_ok());
+    }
+
+    #[test]
+    fn test_consuls_history_is_bounded() {
+        let mut gravity_info = GravityContract::default();
+        gravity_info.consuls = new_consuls(2);
+
+        for round in 0..(GravityContract::MAX_CONSULS_HISTORY as u64 + 4) {
+            gravity_info.record_consuls(round);
+        }
+
+        assert_eq!(gravity_info.consuls_history.len(), GravityContract::MAX_CONSULS_HISTORY);
+        assert!(gravity_info.consuls_at_round(3).is_none());
+        assert!(gravity_info.consuls_at_round(4).is_some());
+
+        // a legacy sized account keeps as much history as fits
+        gravity_info.fit_consuls_history(GravityContract::LEN);
+        assert!(gravity_info.try_to_vec().unwrap().len() <= GravityContract::LEN);
+        assert!(!gravity_info.consuls_history.is_empty());
+    }
+}
@@ -2,6 +2,7 @@ use std::fmt;
 
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use solana_program::{
+    hash::{hash, hashv},
     msg,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
@@ -19,6 +20,19 @@ pub struct GravityContract {
     pub consuls: Vec<Pubkey>,
     pub last_round: u64,
     pub multisig_account: Pubkey,
+
+    // oldest snapshots go first
+    pub consuls_history: Vec<ConsulsSnapshot>,
+}
+
+// consul set that became valid starting from `round`
+#[derive(
+    BorshDeserialize, BorshSchema, BorshSerialize, PartialEq, PartialOrd, Default, Debug, Clone,
+)]
+pub struct ConsulsSnapshot {
+    pub round: u64,
+    pub consuls_hash: [u8; 32],
+    pub bft: u8,
 }
 
 impl fmt::Display for GravityContract {
@@ -34,6 +48,66 @@ impl fmt::Display for GravityContract {
     }
 }
 
+impl GravityContract {
+    pub const MAX_CONSULS_HISTORY: usize = 16;
+
+    pub fn consuls_hash(consuls: &[Pubkey]) -> [u8; 32] {
+        let consuls: Vec<&[u8]> = consuls.iter().map(|consul| consul.as_ref()).collect();
+        hashv(&consuls).to_bytes()
+    }
+
+    pub fn record_consuls(&mut self, round: u64) {
+        self.consuls_history.push(ConsulsSnapshot {
+            round,
+            consuls_hash: Self::consuls_hash(&self.consuls),
+            bft: self.bft,
+        });
+
+        if self.consuls_history.len() > Self::MAX_CONSULS_HISTORY {
+            self.consuls_history.remove(0);
+        }
+    }
+
+    // drops the oldest snapshots until the contract fits into the account,
+    // so legacy accounts of GravityContract::LEN keep working with a shorter history
+    pub fn fit_consuls_history(&mut self, account_len: usize) {
+        while !self.consuls_history.is_empty() {
+            match self.try_to_vec() {
+                Ok(data) if data.len() <= account_len => break,
+                _ => {
+                    self.consuls_history.remove(0);
+                }
+            }
+        }
+    }
+
+    // consul set valid at the given round, None if the round precedes the kept history
+    pub fn consuls_at_round(&self, round: u64) -> Option<&ConsulsSnapshot> {
+        self.consuls_history
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.round <= round)
+    }
+
+    // Payload that the current consuls sign off-chain to authorize a round transition.
+    // Bound to the gravity data account, so it can't be replayed against another contract.
+    pub fn consuls_update_message(
+        gravity_contract: &Pubkey,
+        new_consuls: &[Pubkey],
+        new_round: u64,
+        new_bft: u8,
+    ) -> Vec<u8> {
+        let mut payload = gravity_contract.to_bytes().to_vec();
+        payload.extend_from_slice(&new_round.to_le_bytes());
+        payload.push(new_bft);
+        for consul in new_consuls {
+            payload.extend_from_slice(&consul.to_bytes());
+        }
+
+        hash(&payload).to_bytes().to_vec()
+    }
+}
+
 pub trait PartialStorage {
     const DATA_RANGE: std::ops::Range<usize>;
 
@@ -50,6 +124,36 @@ impl PartialStorage for GravityContract {
     const DATA_RANGE: std::ops::Range<usize> = 0..299;
 }
 
+// borsh storage that spans the whole account data instead of a fixed LEN,
+// so that variable-length records (e.g. large consul sets) fit any account size
+pub trait AccountStorage: BorshSerialize + BorshDeserialize {
+    fn unpack_from_account(src: &[u8]) -> Result<Self, ProgramError> {
+        let mut mut_src: &[u8] = src;
+        Self::deserialize(&mut mut_src).map_err(|err| {
+            msg!("Error: failed to deserialize account data: {}", err);
+            ProgramError::InvalidAccountData
+        })
+    }
+
+    fn pack_into_account(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        let data = self
+            .try_to_vec()
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+
+        if data.len() > dst.len() {
+            msg!("Error: account data is too small: {} < {}", dst.len(), data.len());
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        dst[..data.len()].copy_from_slice(&data);
+        Ok(())
+    }
+}
+
+// GravityContract::LEN is kept as the minimal (legacy) account size,
+// larger accounts hold larger consul sets
+impl AccountStorage for GravityContract {}
+
 impl Sealed for GravityContract {}
 
 impl IsInitialized for GravityContract {
@@ -77,3 +181,34 @@ impl Pack for GravityContract {
         dst[..data.len()].copy_from_slice(&data);
     }
 }
+
+
+// Native consul-set multisig, not capped by spl_token::instruction::MAX_SIGNERS
+#[derive(
+    BorshDeserialize, BorshSchema, BorshSerialize, PartialEq, PartialOrd, Default, Debug, Clone,
+)]
+pub struct GravityMultisig {
+    // GravityMultisig::DISCRIMINATOR once initialized, tells native multisigs apart from spl-token ones
+    pub discriminator: [u8; 8],
+    pub is_initialized: bool,
+    pub m: u8,
+    pub signers: Vec<Pubkey>,
+}
+
+impl GravityMultisig {
+    // signers count is encoded as u8 in instructions
+    pub const MAX_SIGNERS: usize = u8::MAX as usize;
+
+    // the third byte is never 0 or 1, so it can't be read as the is_initialized flag of a spl-token Multisig
+    pub const DISCRIMINATOR: [u8; 8] = *b"gvmultsg";
+
+    pub fn packed_len(signers_count: usize) -> usize {
+        8 + 1 + 1 + 4 + 32 * signers_count
+    }
+
+    pub fn is_tagged(data: &[u8]) -> bool {
+        data.starts_with(&Self::DISCRIMINATOR)
+    }
+}
+
+impl AccountStorage for GravityMultisig {}
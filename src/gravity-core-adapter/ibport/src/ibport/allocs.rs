@@ -15,6 +15,7 @@ pub fn allocation_by_instruction_index(
         ],
         // CreateTransferUnwrapRequest
         1 => vec![
+            1,
             IBPortContractInstruction::DEST_AMOUNT_ALLOC,
             IBPortContractInstruction::FOREIGN_ADDRESS_ALLOC,
             16,
@@ -28,6 +29,20 @@ pub fn allocation_by_instruction_index(
             IBPortContractInstruction::PUBKEY_ALLOC,
             IBPortContractInstruction::PUBKEY_ALLOC,
         ],
+        // RejectRequest, ReclaimRequest
+        5 | 6 => vec![16],
+        // SetRequestTimeout
+        7 => vec![8],
+        // ConfigureFees
+        8 => vec![
+            8,
+            2,
+            8,
+            2,
+            IBPortContractInstruction::PUBKEY_ALLOC,
+        ],
+        // WithdrawFees
+        9 => vec![8],
         _ => return Err(PortError::InvalidInstructionIndex.into()),
     })
 }
@@ -1,29 +1,34 @@
 use solana_program::{
+    clock::Slot,
+    instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     pubkey::Pubkey,
+    sysvar,
 };
-use std::mem::size_of;
 use arrayref::array_ref;
 
 use gravity_misc::validation::{build_range_from_alloc, extract_from_range, retrieve_oracles};
 
 use crate::ibport::allocs::allocation_by_instruction_index;
-use gravity_misc::ports::state::ForeignAddress;
-use gravity_misc::ports::instruction::ATTACH_VALUE_INSTRUCTION_INDEX;
+use gravity_misc::ports::state::{ForeignAddress, PortFee};
+use gravity_misc::ports::instruction::{RequestAmount, ATTACH_VALUE_INSTRUCTION_INDEX};
 
 use solana_gravity_contract::gravity::error::GravityError::InvalidInstruction;
 
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum IBPortContractInstruction {
     InitContract {
         nebula_address: Pubkey,
         token_address: Pubkey,
         token_mint: Pubkey,
         oracles: Vec<Pubkey>,
+        decimals: u8,
+        foreign_decimals: u8,
     },
     CreateTransferUnwrapRequest {
         request_id: [u8; 16],
-        amount: f64,
+        amount: RequestAmount,
         receiver: ForeignAddress,
     },
     AttachValue {
@@ -35,7 +40,24 @@ pub enum IBPortContractInstruction {
     TransferTokenOwnership {
         new_authority: Pubkey,
         new_token: Pubkey,
-    }
+    },
+    RejectRequest {
+        request_id: [u8; 16],
+    },
+    ReclaimRequest {
+        request_id: [u8; 16],
+    },
+    SetRequestTimeout {
+        request_timeout: Slot,
+    },
+    ConfigureFees {
+        outbound_fee: PortFee,
+        inbound_fee: PortFee,
+        fee_vault: Pubkey,
+    },
+    WithdrawFees {
+        amount: u64,
+    },
 }
 
 
@@ -67,22 +89,31 @@ impl IBPortContractInstruction {
                 })?;
                 offset += 1;
                 let oracles = retrieve_oracles(rest, offset..offset + (oracles_bft as usize * 32), oracles_bft)?;
+                offset += oracles_bft as usize * 32;
+                let (decimals, foreign_decimals) = (
+                    extract_from_range(rest, offset..offset + 1, |x: &[u8]| u8::from_le_bytes(*array_ref![x, 0, 1]))?,
+                    extract_from_range(rest, offset + 1..offset + 2, |x: &[u8]| u8::from_le_bytes(*array_ref![x, 0, 1]))?,
+                );
 
                 Self::InitContract {
                     nebula_address,
                     token_address,
                     token_mint,
                     oracles,
+                    decimals,
+                    foreign_decimals,
                 }
             }
             // CreateTransferUnwrapRequest
             1 => {
                 let allocs = allocation_by_instruction_index((*tag).into(), None)?;
                 let ranges = build_range_from_alloc(&allocs);
-                let (amount, receiver, request_id) = (
-                    f64::from_le_bytes(*array_ref![rest[ranges[0].clone()], 0, 8]),
-                    *array_ref![rest[ranges[1].clone()], 0, 32],
-                    *array_ref![rest[ranges[2].clone()], 0, 16],
+
+                // ranges of the current encoding, the legacy f64 amount has no version byte
+                let (amount, rest) = RequestAmount::unpack(rest, ranges[3].end)?;
+                let (receiver, request_id) = (
+                    *array_ref![rest, 0, 32],
+                    *array_ref![rest, 32, 16],
                 );
 
                 Self::CreateTransferUnwrapRequest {
@@ -114,6 +145,58 @@ impl IBPortContractInstruction {
 
                 Self::TransferTokenOwnership { new_authority, new_token }
             }
+            // RejectRequest, ReclaimRequest
+            5 | 6 => {
+                let allocs = allocation_by_instruction_index((*tag).into(), None)?;
+                let ranges = build_range_from_alloc(&allocs);
+
+                let request_id = extract_from_range(rest, ranges[0].clone(), |x: &[u8]| *array_ref![x, 0, 16])?;
+
+                match tag {
+                    5 => Self::RejectRequest { request_id },
+                    _ => Self::ReclaimRequest { request_id },
+                }
+            }
+            // SetRequestTimeout
+            7 => {
+                let allocs = allocation_by_instruction_index((*tag).into(), None)?;
+                let ranges = build_range_from_alloc(&allocs);
+
+                let request_timeout = extract_from_range(rest, ranges[0].clone(), |x: &[u8]| {
+                    Slot::from_le_bytes(*array_ref![x, 0, 8])
+                })?;
+
+                Self::SetRequestTimeout { request_timeout }
+            }
+            // ConfigureFees
+            8 => {
+                let allocs = allocation_by_instruction_index((*tag).into(), None)?;
+                let ranges = build_range_from_alloc(&allocs);
+
+                let fee_from_range = |flat: std::ops::Range<usize>, bps: std::ops::Range<usize>| -> Result<PortFee, ProgramError> {
+                    Ok(PortFee {
+                        flat: extract_from_range(rest, flat, |x: &[u8]| u64::from_le_bytes(*array_ref![x, 0, 8]))?,
+                        bps: extract_from_range(rest, bps, |x: &[u8]| u16::from_le_bytes(*array_ref![x, 0, 2]))?,
+                    })
+                };
+
+                let outbound_fee = fee_from_range(ranges[0].clone(), ranges[1].clone())?;
+                let inbound_fee = fee_from_range(ranges[2].clone(), ranges[3].clone())?;
+                let fee_vault = extract_from_range(rest, ranges[4].clone(), |x: &[u8]| Pubkey::new(x))?;
+
+                Self::ConfigureFees { outbound_fee, inbound_fee, fee_vault }
+            }
+            // WithdrawFees
+            9 => {
+                let allocs = allocation_by_instruction_index((*tag).into(), None)?;
+                let ranges = build_range_from_alloc(&allocs);
+
+                let amount = extract_from_range(rest, ranges[0].clone(), |x: &[u8]| {
+                    u64::from_le_bytes(*array_ref![x, 0, 8])
+                })?;
+
+                Self::WithdrawFees { amount }
+            }
             _ => return Err(InvalidInstruction.into()),
         })
     }
@@ -121,16 +204,554 @@ impl IBPortContractInstruction {
 
 impl IBPortContractInstruction {
     pub fn pack(&self) -> Vec<u8> {
-        let buf = Vec::with_capacity(size_of::<Self>());
+        let mut buf = vec![];
         match self {
+            &Self::InitContract {
+                ref nebula_address,
+                ref token_address,
+                ref token_mint,
+                ref oracles,
+                decimals,
+                foreign_decimals,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(&nebula_address.to_bytes());
+                buf.extend_from_slice(&token_address.to_bytes());
+                buf.extend_from_slice(&token_mint.to_bytes());
+                buf.push(oracles.len() as u8);
+                for oracle in oracles {
+                    buf.extend_from_slice(&oracle.to_bytes());
+                }
+                buf.push(decimals);
+                buf.push(foreign_decimals);
+            }
+            &Self::CreateTransferUnwrapRequest {
+                ref request_id,
+                amount,
+                ref receiver,
+            } => {
+                buf.push(1);
+                amount.pack_into(&mut buf);
+                buf.extend_from_slice(receiver);
+                buf.extend_from_slice(request_id);
+            }
             &Self::AttachValue {
                 ref byte_data,
             } => {
-                let mut buf = byte_data.clone();
-                buf.insert(0, *ATTACH_VALUE_INSTRUCTION_INDEX);
-                buf
+                buf.push(*ATTACH_VALUE_INSTRUCTION_INDEX);
+                buf.extend_from_slice(byte_data);
+            },
+            &Self::ConfirmDestinationChainRequest {
+                ref byte_data,
+            } => {
+                buf.push(3);
+                buf.extend_from_slice(byte_data);
+            }
+            &Self::TransferTokenOwnership {
+                ref new_authority,
+                ref new_token,
+            } => {
+                buf.push(4);
+                buf.extend_from_slice(&new_authority.to_bytes());
+                buf.extend_from_slice(&new_token.to_bytes());
+            }
+            &Self::RejectRequest {
+                ref request_id,
+            } => {
+                buf.push(5);
+                buf.extend_from_slice(request_id);
+            }
+            &Self::ReclaimRequest {
+                ref request_id,
+            } => {
+                buf.push(6);
+                buf.extend_from_slice(request_id);
+            }
+            &Self::SetRequestTimeout {
+                request_timeout,
+            } => {
+                buf.push(7);
+                buf.extend_from_slice(&request_timeout.to_le_bytes());
+            }
+            &Self::ConfigureFees {
+                ref outbound_fee,
+                ref inbound_fee,
+                ref fee_vault,
+            } => {
+                buf.push(8);
+                for fee in &[outbound_fee, inbound_fee] {
+                    buf.extend_from_slice(&fee.flat.to_le_bytes());
+                    buf.extend_from_slice(&fee.bps.to_le_bytes());
+                }
+                buf.extend_from_slice(&fee_vault.to_bytes());
+            }
+            &Self::WithdrawFees {
+                amount,
+            } => {
+                buf.push(9);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+        }
+        buf
+    }
+}
+
+/// Creates an `InitContract` instruction, `decimals` and `foreign_decimals` are the token decimals
+/// on Solana and on the foreign chain.
+///
+/// Accounts expected:
+///   0. `[signer]` Initializer
+///   1. `[writable]` IB port data account
+pub fn init_contract(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    ibport_contract: &Pubkey,
+    nebula_address: &Pubkey,
+    token_address: &Pubkey,
+    token_mint: &Pubkey,
+    oracles: &[Pubkey],
+    decimals: u8,
+    foreign_decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = IBPortContractInstruction::InitContract {
+        nebula_address: *nebula_address,
+        token_address: *token_address,
+        token_mint: *token_mint,
+        oracles: oracles.to_vec(),
+        decimals,
+        foreign_decimals,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*ibport_contract, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `CreateTransferUnwrapRequest` instruction, burning `amount` base units from `token_holder`.
+///
+/// Accounts expected:
+///   0. `[signer]` Initializer
+///   1. `[writable]` IB port data account
+///   2. `[]` Token program
+///   3. `[writable]` Mint
+///   4. `[writable]` Token holder account
+///   5. `[]` IB port PDA (burn authority)
+///   6. `[]` Clock sysvar
+///   7. `[writable]` Fee vault, only when the outbound fee is non-zero
+pub fn create_transfer_unwrap_request(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    ibport_contract: &Pubkey,
+    token_program_id: &Pubkey,
+    mint: &Pubkey,
+    token_holder: &Pubkey,
+    pda_account: &Pubkey,
+    request_id: &[u8; 16],
+    amount: u64,
+    receiver: &ForeignAddress,
+    fee_vault: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let data = IBPortContractInstruction::CreateTransferUnwrapRequest {
+        request_id: *request_id,
+        amount: RequestAmount::BaseUnits(amount),
+        receiver: *receiver,
+    }
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*ibport_contract, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*mint, false),
+        AccountMeta::new(*token_holder, false),
+        AccountMeta::new_readonly(*pda_account, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    if let Some(fee_vault) = fee_vault {
+        accounts.push(AccountMeta::new(*fee_vault, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `AttachValue` instruction, normally issued by Nebula through CPI.
+///
+/// Accounts expected:
+///   0. `[signer]` Oracle
+///   1. `[writable]` IB port data account
+///   2. `[]` Token program
+///   3. `[writable]` Mint
+///   4. `[writable]` Recipient account
+///   5. `[]` IB port PDA (mint authority)
+pub fn attach_value(
+    program_id: &Pubkey,
+    oracle: &Pubkey,
+    ibport_contract: &Pubkey,
+    token_program_id: &Pubkey,
+    mint: &Pubkey,
+    recipient_account: &Pubkey,
+    pda_account: &Pubkey,
+    byte_data: &[u8],
+) -> Result<Instruction, ProgramError> {
+    let data = IBPortContractInstruction::AttachValue {
+        byte_data: byte_data.to_vec(),
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*oracle, true),
+        AccountMeta::new(*ibport_contract, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*mint, false),
+        AccountMeta::new(*recipient_account, false),
+        AccountMeta::new_readonly(*pda_account, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `ConfirmDestinationChainRequest` instruction.
+///
+/// Accounts expected:
+///   0. `[signer]` Oracle
+///   1. `[writable]` IB port data account
+pub fn confirm_destination_chain_request(
+    program_id: &Pubkey,
+    oracle: &Pubkey,
+    ibport_contract: &Pubkey,
+    byte_data: &[u8],
+) -> Result<Instruction, ProgramError> {
+    let data = IBPortContractInstruction::ConfirmDestinationChainRequest {
+        byte_data: byte_data.to_vec(),
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*oracle, true),
+        AccountMeta::new(*ibport_contract, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `TransferTokenOwnership` instruction, a zeroed `new_token` keeps the current token address.
+///
+/// Accounts expected:
+///   0. `[signer]` Oracle
+///   1. `[writable]` IB port data account
+///   2. `[writable]` Mint
+///   3. `[]` IB port PDA (current mint authority)
+///   4. `[]` Token program
+pub fn transfer_token_ownership(
+    program_id: &Pubkey,
+    oracle: &Pubkey,
+    ibport_contract: &Pubkey,
+    mint: &Pubkey,
+    pda_account: &Pubkey,
+    token_program_id: &Pubkey,
+    new_authority: &Pubkey,
+    new_token: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = IBPortContractInstruction::TransferTokenOwnership {
+        new_authority: *new_authority,
+        new_token: *new_token,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*oracle, true),
+        AccountMeta::new(*ibport_contract, false),
+        AccountMeta::new(*mint, false),
+        AccountMeta::new_readonly(*pda_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `RejectRequest` instruction, the rejected request can be reclaimed right away.
+///
+/// Accounts expected:
+///   0. `[signer]` Oracle
+///   1. `[writable]` IB port data account
+pub fn reject_request(
+    program_id: &Pubkey,
+    oracle: &Pubkey,
+    ibport_contract: &Pubkey,
+    request_id: &[u8; 16],
+) -> Result<Instruction, ProgramError> {
+    let data = IBPortContractInstruction::RejectRequest {
+        request_id: *request_id,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*oracle, true),
+        AccountMeta::new(*ibport_contract, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `ReclaimRequest` instruction, re-minting the burned amount.
+/// Any signer may submit it, the amount and the paid outbound fee always go back to the request origin account.
+///
+/// Accounts expected:
+///   0. `[signer]` Payer
+///   1. `[writable]` IB port data account
+///   2. `[]` Token program
+///   3. `[writable]` Mint
+///   4. `[writable]` Request origin token account
+///   5. `[]` IB port PDA (mint authority)
+///   6. `[]` Clock sysvar
+///   7. `[writable]` Fee vault, only when an outbound fee was paid for the request
+pub fn reclaim_request(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    ibport_contract: &Pubkey,
+    token_program_id: &Pubkey,
+    mint: &Pubkey,
+    origin_account: &Pubkey,
+    pda_account: &Pubkey,
+    request_id: &[u8; 16],
+    fee_vault: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let data = IBPortContractInstruction::ReclaimRequest {
+        request_id: *request_id,
+    }
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*payer, true),
+        AccountMeta::new(*ibport_contract, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*mint, false),
+        AccountMeta::new(*origin_account, false),
+        AccountMeta::new_readonly(*pda_account, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    if let Some(fee_vault) = fee_vault {
+        accounts.push(AccountMeta::new(*fee_vault, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `SetRequestTimeout` instruction, 0 disables the reclaim of pending requests.
+///
+/// Accounts expected:
+///   0. `[signer]` Port initializer
+///   1. `[writable]` IB port data account
+pub fn set_request_timeout(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    ibport_contract: &Pubkey,
+    request_timeout: Slot,
+) -> Result<Instruction, ProgramError> {
+    let data = IBPortContractInstruction::SetRequestTimeout { request_timeout }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*ibport_contract, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `ConfigureFees` instruction, the fee vault is a token account of the port mint owned by the port PDA.
+///
+/// Accounts expected:
+///   0. `[signer]` Port initializer
+///   1. `[writable]` IB port data account
+///   2. `[]` Fee vault, unless the fees are disabled with the default pubkey
+pub fn configure_fees(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    ibport_contract: &Pubkey,
+    outbound_fee: &PortFee,
+    inbound_fee: &PortFee,
+    fee_vault: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = IBPortContractInstruction::ConfigureFees {
+        outbound_fee: *outbound_fee,
+        inbound_fee: *inbound_fee,
+        fee_vault: *fee_vault,
+    }
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*ibport_contract, false),
+    ];
+    if *fee_vault != Pubkey::default() {
+        accounts.push(AccountMeta::new_readonly(*fee_vault, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `WithdrawFees` instruction, moving `amount` of the accrued fees out of the fee vault.
+/// The inbound fees kept back on mint are minted into the fee vault first.
+///
+/// Accounts expected:
+///   0. `[signer]` Port initializer
+///   1. `[writable]` IB port data account
+///   2. `[]` Token program
+///   3. `[writable]` Fee vault
+///   4. `[writable]` Destination token account
+///   5. `[]` IB port PDA (fee vault owner and mint authority)
+///   6. `[writable]` Mint
+pub fn withdraw_fees(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    ibport_contract: &Pubkey,
+    token_program_id: &Pubkey,
+    fee_vault: &Pubkey,
+    destination: &Pubkey,
+    pda_account: &Pubkey,
+    mint: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = IBPortContractInstruction::WithdrawFees { amount }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*ibport_contract, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*fee_vault, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(*pda_account, false),
+        AccountMeta::new(*mint, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gravity_misc::ports::{error::PortError, instruction::AMOUNT_ENCODING_VERSION};
+
+    #[test]
+    fn test_unpack_legacy_amount_encoding() {
+        // [tag][f64 amount][receiver][request id], as sent before the amounts became base units
+        let mut legacy = vec![1];
+        legacy.extend_from_slice(&1.5f64.to_le_bytes());
+        legacy.extend_from_slice(&[3; 32]);
+        legacy.extend_from_slice(&[AMOUNT_ENCODING_VERSION; 16]);
+
+        let instruction = IBPortContractInstruction::unpack(&legacy).unwrap();
+        assert_eq!(
+            instruction,
+            IBPortContractInstruction::CreateTransferUnwrapRequest {
+                request_id: [AMOUNT_ENCODING_VERSION; 16],
+                amount: RequestAmount::Ui(1.5),
+                receiver: [3; 32],
+            }
+        );
+        assert_eq!(instruction.pack(), legacy);
+
+        // same length as the current encoding, but without the version byte
+        legacy.push(0);
+        legacy[1] = 0;
+        assert_eq!(
+            IBPortContractInstruction::unpack(&legacy).unwrap_err(),
+            PortError::UnsupportedAmountEncoding.into()
+        );
+    }
+
+    #[test]
+    fn test_pack_unpack_round_trip() {
+        let instructions = vec![
+            IBPortContractInstruction::InitContract {
+                nebula_address: Pubkey::new_unique(),
+                token_address: Pubkey::new_unique(),
+                token_mint: Pubkey::new_unique(),
+                oracles: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+                decimals: 8,
+                foreign_decimals: 18,
+            },
+            IBPortContractInstruction::CreateTransferUnwrapRequest {
+                request_id: [1; 16],
+                amount: RequestAmount::BaseUnits(1_250_000_000),
+                receiver: [2; 32],
+            },
+            IBPortContractInstruction::AttachValue {
+                byte_data: vec![3; IBPortContractInstruction::ATTACHED_DATA_ALLOC],
             },
-            _ => buf
+            IBPortContractInstruction::ConfirmDestinationChainRequest {
+                byte_data: vec![4; IBPortContractInstruction::ATTACHED_DATA_ALLOC],
+            },
+            IBPortContractInstruction::TransferTokenOwnership {
+                new_authority: Pubkey::new_unique(),
+                new_token: Pubkey::new_unique(),
+            },
+            IBPortContractInstruction::RejectRequest {
+                request_id: [5; 16],
+            },
+            IBPortContractInstruction::ReclaimRequest {
+                request_id: [6; 16],
+            },
+            IBPortContractInstruction::SetRequestTimeout {
+                request_timeout: 432_000,
+            },
+            IBPortContractInstruction::ConfigureFees {
+                outbound_fee: PortFee { flat: 100, bps: 30 },
+                inbound_fee: PortFee { flat: 0, bps: 10 },
+                fee_vault: Pubkey::new_unique(),
+            },
+            IBPortContractInstruction::WithdrawFees {
+                amount: 1_000,
+            },
+        ];
+
+        for instruction in instructions {
+            let packed = instruction.pack();
+            assert_eq!(IBPortContractInstruction::unpack(&packed).unwrap(), instruction);
         }
     }
 }
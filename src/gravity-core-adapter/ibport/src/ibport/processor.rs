@@ -1,27 +1,31 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::{Clock, Slot},
     entrypoint::ProgramResult,
     msg,
     program::invoke_signed,
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
+    sysvar::Sysvar,
 };
 
 use spl_token::{
-    instruction::{burn, mint_to, set_authority, AuthorityType},
+    instruction::{burn, mint_to, set_authority, transfer, AuthorityType},
+    state::{Account, Mint},
 };
 
 use gravity_misc::validation::validate_contract_emptiness;
 
 
 
-use gravity_misc::ports::state::ForeignAddress;
+use gravity_misc::ports::state::{ForeignAddress, PortFee, PortFees};
 
 use crate::ibport::instruction::IBPortContractInstruction;
 use crate::ibport::state::IBPortContract;
 
 use gravity_misc::ports::error::PortError;
+use gravity_misc::ports::instruction::RequestAmount;
 use gravity_misc::ports::state::PortOperationIdentifier;
 use gravity_misc::validation::{PDAResolver, validate_pubkey_match, TokenMintConstrained};
 
@@ -39,6 +43,8 @@ impl IBPortProcessor {
         token_mint: &Pubkey,
         nebula_address: &Pubkey,
         oracles: &Vec<Pubkey>,
+        decimals: u8,
+        foreign_decimals: u8,
         _program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -59,6 +65,9 @@ impl IBPortProcessor {
         ibport_contract_info.nebula_address = *nebula_address;
         ibport_contract_info.token_mint = *token_mint;
         ibport_contract_info.oracles = oracles.clone();
+        ibport_contract_info.decimals = decimals;
+        ibport_contract_info.foreign_decimals = foreign_decimals;
+        ibport_contract_info.decimals_configured = true;
         ibport_contract_info.initializer_pubkey = *initializer.key;
 
         msg!("instantiated ib port contract");
@@ -76,7 +85,7 @@ impl IBPortProcessor {
     fn process_create_transfer_unwrap_request(
         accounts: &[AccountInfo],
         request_id: &[u8; 16],
-        ui_amount: f64,
+        amount: RequestAmount,
         receiver: &ForeignAddress,
         _program_id: &Pubkey,
     ) -> ProgramResult {
@@ -92,8 +101,6 @@ impl IBPortProcessor {
         let mut ibport_contract_info =
             IBPortContract::unpack(&ibport_contract_account.data.borrow()[0..IBPortContract::LEN])?;
 
-        let amount = spl_token::ui_amount_to_amount(ui_amount, 8);
-
         // Get the accounts to mint
         let token_program_id = next_account_info(account_info_iter)?;
 
@@ -104,12 +111,50 @@ impl IBPortProcessor {
         let mint = next_account_info(account_info_iter)?;
         let token_holder = next_account_info(account_info_iter)?;
         let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
 
         ibport_contract_info.validate_token_mint(mint.key)?;
         // if *mint.key != susy_wrapped_gton_mint() {
         //     return Err(PortError::InvalidTokenMint.into());
         // }
 
+        let token_mint_info = Mint::unpack(&mint.data.borrow()[0..Mint::LEN])?;
+        ibport_contract_info.validate_mint_decimals(&token_mint_info)?;
+
+        let amount = amount.to_base_units(token_mint_info.decimals)?;
+        let (amount, fee) = ibport_contract_info.fees.collect_outbound(amount)?;
+
+        // the burned amount has to be representable on the foreign chain
+        if ibport_contract_info.port_decimals().to_foreign(amount).is_none() {
+            return Err(PortError::InvalidAmount.into());
+        }
+
+        if fee > 0 {
+            let fee_vault = next_account_info(account_info_iter)?;
+            ibport_contract_info.fees.validate_vault(fee_vault.key)?;
+
+            msg!("collecting outbound fee");
+            let transfer_ix = transfer(
+                &token_program_id.key,
+                &token_holder.key,
+                &fee_vault.key,
+                &pda_account.key,
+                &[],
+                fee,
+            )?;
+
+            invoke_signed(
+                &transfer_ix,
+                &[
+                    token_holder.clone(),
+                    fee_vault.clone(),
+                    pda_account.clone(),
+                    token_program_id.clone(),
+                ],
+                &[&[PDAResolver::Gravity.bump_seeds()]],
+            )?;
+        }
+
         let burn_ix = burn(
             &token_program_id.key,
             &token_holder.key,
@@ -131,7 +176,7 @@ impl IBPortProcessor {
         )?;
 
         msg!("saving request info");
-        ibport_contract_info.create_transfer_unwrap_request(request_id, amount, token_holder.key, receiver)?;
+        ibport_contract_info.create_transfer_unwrap_request(request_id, amount, fee, token_holder.key, receiver, clock.slot)?;
 
         IBPortContract::pack(
             ibport_contract_info,
@@ -188,6 +233,9 @@ impl IBPortProcessor {
 
         ibport_contract_info.validate_token_mint(mint.key)?;
 
+        let token_mint_info = Mint::unpack(&mint.data.borrow()[0..Mint::LEN])?;
+        ibport_contract_info.validate_mint_decimals(&token_mint_info)?;
+
         msg!("Creating mint instruction");
 
         let mut amount: u64 = 0;
@@ -196,7 +244,9 @@ impl IBPortProcessor {
 
         if operation == PortOperationIdentifier::MINT.to_string() {
             msg!("unpacked ibport_contract_account");
-    
+
+            let amount = ibport_contract_info.fees.collect_inbound(amount)?;
+
             let mint_ix = mint_to(
                 &token_program_id.key,
                 &mint.key,
@@ -333,6 +383,295 @@ impl IBPortProcessor {
         Ok(())
     }
 
+    fn process_reject_request(
+        accounts: &[AccountInfo],
+        request_id: &[u8; 16],
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let ibport_contract_account = next_account_info(account_info_iter)?;
+
+        let mut ibport_contract_info =
+            IBPortContract::unpack(&ibport_contract_account.data.borrow()[0..IBPortContract::LEN])?;
+
+        msg!("validating initializer");
+        Self::validate_data_provider(
+            &ibport_contract_info.oracles,
+            initializer.key,
+        )?;
+
+        ibport_contract_info.port_requests().reject(request_id)?;
+
+        IBPortContract::pack(
+            ibport_contract_info,
+            &mut ibport_contract_account.try_borrow_mut_data()?[0..IBPortContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    fn process_reclaim_request(
+        accounts: &[AccountInfo],
+        request_id: &[u8; 16],
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let ibport_contract_account = next_account_info(account_info_iter)?;
+
+        let mut ibport_contract_info =
+            IBPortContract::unpack(&ibport_contract_account.data.borrow()[0..IBPortContract::LEN])?;
+
+        let token_program_id = next_account_info(account_info_iter)?;
+
+        if *token_program_id.key != ibport_contract_info.token_address {
+            return Err(PortError::InvalidInputToken.into());
+        }
+
+        let mint = next_account_info(account_info_iter)?;
+        let origin_account = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        ibport_contract_info.validate_token_mint(mint.key)?;
+
+        let (amount, fee) = ibport_contract_info.port_requests().reclaim(request_id, origin_account.key, clock.slot)?;
+
+        msg!("re-minting reclaimed request");
+        let mint_ix = mint_to(
+            &token_program_id.key,
+            &mint.key,
+            &origin_account.key,
+            &pda_account.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(
+            &mint_ix,
+            &[
+                mint.clone(),
+                origin_account.clone(),
+                pda_account.clone(),
+                token_program_id.clone(),
+            ],
+            &[&[PDAResolver::Gravity.bump_seeds()]]
+        )?;
+
+        if fee > 0 {
+            let fee_vault = next_account_info(account_info_iter)?;
+            ibport_contract_info.fees.validate_vault(fee_vault.key)?;
+
+            msg!("refunding outbound fee");
+            let transfer_ix = transfer(
+                &token_program_id.key,
+                &fee_vault.key,
+                &origin_account.key,
+                &pda_account.key,
+                &[],
+                fee,
+            )?;
+
+            invoke_signed(
+                &transfer_ix,
+                &[
+                    fee_vault.clone(),
+                    origin_account.clone(),
+                    pda_account.clone(),
+                    token_program_id.clone(),
+                ],
+                &[&[PDAResolver::Gravity.bump_seeds()]]
+            )?;
+        }
+
+        IBPortContract::pack(
+            ibport_contract_info,
+            &mut ibport_contract_account.try_borrow_mut_data()?[0..IBPortContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    fn process_set_request_timeout(
+        accounts: &[AccountInfo],
+        request_timeout: Slot,
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let ibport_contract_account = next_account_info(account_info_iter)?;
+
+        let mut ibport_contract_info =
+            IBPortContract::unpack(&ibport_contract_account.data.borrow()[0..IBPortContract::LEN])?;
+
+        if ibport_contract_info.initializer_pubkey != *initializer.key {
+            return Err(PortError::AccessDenied.into());
+        }
+
+        ibport_contract_info.request_timeout = request_timeout;
+
+        IBPortContract::pack(
+            ibport_contract_info,
+            &mut ibport_contract_account.try_borrow_mut_data()?[0..IBPortContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    fn process_configure_fees(
+        accounts: &[AccountInfo],
+        outbound_fee: PortFee,
+        inbound_fee: PortFee,
+        fee_vault: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let ibport_contract_account = next_account_info(account_info_iter)?;
+
+        let mut ibport_contract_info =
+            IBPortContract::unpack(&ibport_contract_account.data.borrow()[0..IBPortContract::LEN])?;
+
+        if ibport_contract_info.initializer_pubkey != *initializer.key {
+            return Err(PortError::AccessDenied.into());
+        }
+
+        if *fee_vault != Pubkey::default() {
+            let fee_vault_account = next_account_info(account_info_iter)?;
+
+            if fee_vault_account.key != fee_vault || *fee_vault_account.owner != ibport_contract_info.token_address {
+                return Err(PortError::InvalidFeeVault.into());
+            }
+
+            let fee_vault_info = Account::unpack(&fee_vault_account.data.borrow())?;
+            PortFees::validate_vault_account(
+                &fee_vault_info,
+                &ibport_contract_info.token_mint,
+                &PDAResolver::Gravity.address(program_id)?,
+            )?;
+        }
+
+        ibport_contract_info.fees.configure(outbound_fee, inbound_fee, fee_vault)?;
+
+        IBPortContract::pack(
+            ibport_contract_info,
+            &mut ibport_contract_account.try_borrow_mut_data()?[0..IBPortContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    fn process_withdraw_fees(
+        accounts: &[AccountInfo],
+        amount: u64,
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let ibport_contract_account = next_account_info(account_info_iter)?;
+
+        let mut ibport_contract_info =
+            IBPortContract::unpack(&ibport_contract_account.data.borrow()[0..IBPortContract::LEN])?;
+
+        if ibport_contract_info.initializer_pubkey != *initializer.key {
+            return Err(PortError::AccessDenied.into());
+        }
+
+        let token_program_id = next_account_info(account_info_iter)?;
+
+        if *token_program_id.key != ibport_contract_info.token_address {
+            return Err(PortError::InvalidInputToken.into());
+        }
+
+        let fee_vault = next_account_info(account_info_iter)?;
+        let destination = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let mint = next_account_info(account_info_iter)?;
+
+        ibport_contract_info.validate_token_mint(mint.key)?;
+        ibport_contract_info.fees.validate_vault(fee_vault.key)?;
+
+        let unsettled = ibport_contract_info.fees.settle()?;
+        ibport_contract_info.fees.withdraw(amount)?;
+
+        if unsettled > 0 {
+            msg!("minting kept back inbound fees");
+            let mint_ix = mint_to(
+                &token_program_id.key,
+                &mint.key,
+                &fee_vault.key,
+                &pda_account.key,
+                &[],
+                unsettled,
+            )?;
+
+            invoke_signed(
+                &mint_ix,
+                &[
+                    mint.clone(),
+                    fee_vault.clone(),
+                    pda_account.clone(),
+                    token_program_id.clone(),
+                ],
+                &[&[PDAResolver::Gravity.bump_seeds()]]
+            )?;
+        }
+
+        msg!("withdrawing fees");
+        let transfer_ix = transfer(
+            &token_program_id.key,
+            &fee_vault.key,
+            &destination.key,
+            &pda_account.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(
+            &transfer_ix,
+            &[
+                fee_vault.clone(),
+                destination.clone(),
+                pda_account.clone(),
+                token_program_id.clone(),
+            ],
+            &[&[PDAResolver::Gravity.bump_seeds()]]
+        )?;
+
+        IBPortContract::pack(
+            ibport_contract_info,
+            &mut ibport_contract_account.try_borrow_mut_data()?[0..IBPortContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
     pub fn process(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -346,6 +685,8 @@ impl IBPortProcessor {
                 token_mint,
                 nebula_address,
                 oracles,
+                decimals,
+                foreign_decimals,
             } => {
                 msg!("Instruction: Init IB Port Contract");
                 
@@ -355,6 +696,8 @@ impl IBPortProcessor {
                     &token_mint,
                     &nebula_address,
                     &oracles,
+                    decimals,
+                    foreign_decimals,
                     program_id,
                 )
             }
@@ -407,7 +750,264 @@ impl IBPortProcessor {
                     program_id,
                 )
             }
+            IBPortContractInstruction::RejectRequest {
+                request_id
+            } => {
+                msg!("Instruction: RejectRequest");
+
+                Self::process_reject_request(
+                    accounts,
+                    &request_id,
+                    program_id,
+                )
+            }
+            IBPortContractInstruction::ReclaimRequest {
+                request_id
+            } => {
+                msg!("Instruction: ReclaimRequest");
+
+                Self::process_reclaim_request(
+                    accounts,
+                    &request_id,
+                    program_id,
+                )
+            }
+            IBPortContractInstruction::SetRequestTimeout {
+                request_timeout
+            } => {
+                msg!("Instruction: SetRequestTimeout");
+
+                Self::process_set_request_timeout(
+                    accounts,
+                    request_timeout,
+                    program_id,
+                )
+            }
+            IBPortContractInstruction::ConfigureFees {
+                outbound_fee,
+                inbound_fee,
+                fee_vault,
+            } => {
+                msg!("Instruction: ConfigureFees");
+
+                Self::process_configure_fees(
+                    accounts,
+                    outbound_fee,
+                    inbound_fee,
+                    &fee_vault,
+                    program_id,
+                )
+            }
+            IBPortContractInstruction::WithdrawFees {
+                amount
+            } => {
+                msg!("Instruction: WithdrawFees");
+
+                Self::process_withdraw_fees(
+                    accounts,
+                    amount,
+                    program_id,
+                )
+            }
             // _ => Err(GravityError::InvalidInstruction.into()),
         }
     }    
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ibport::instruction::{
+        attach_value, configure_fees, confirm_destination_chain_request, create_transfer_unwrap_request, init_contract,
+        reclaim_request, reject_request, withdraw_fees,
+    };
+    use crate::ibport::state::PortOperation;
+    use gravity_misc::model::AbstractRecordHandler;
+    use gravity_misc::ports::state::{PortAmount, PortDecimals};
+    use gravity_misc::test_support::{take_token_cpis, TestPort, TokenCpi};
+
+    const DECIMALS: PortDecimals = PortDecimals { local: 8, foreign: 18 };
+
+    fn new_port() -> TestPort {
+        let mut port = TestPort::new(IBPortProcessor::process, IBPortContract::LEN, DECIMALS.local);
+
+        let ix = init_contract(
+            &port.program_id,
+            &port.admin,
+            &port.port,
+            &Pubkey::new_unique(),
+            &spl_token::id(),
+            &port.mint,
+            &[port.oracle],
+            DECIMALS.local,
+            DECIMALS.foreign,
+        )
+        .unwrap();
+        port.process(&ix).unwrap();
+        port
+    }
+
+    trait IBTestPort {
+        fn burn(&mut self, token_holder: &Pubkey, request_id: &[u8; 16], fee_vault: Option<&Pubkey>) -> ProgramResult;
+        fn configure_fees(&mut self, outbound_fee: PortFee, inbound_fee: PortFee, fee_vault: &Pubkey) -> ProgramResult;
+        fn contract(&self) -> IBPortContract;
+    }
+
+    impl IBTestPort for TestPort {
+        fn burn(&mut self, token_holder: &Pubkey, request_id: &[u8; 16], fee_vault: Option<&Pubkey>) -> ProgramResult {
+            let ix = create_transfer_unwrap_request(
+                &self.program_id,
+                &Pubkey::new_unique(),
+                &self.port,
+                &spl_token::id(),
+                &self.mint,
+                token_holder,
+                &self.pda,
+                request_id,
+                1_000,
+                &[1; 32],
+                fee_vault,
+            )
+            .unwrap();
+            self.process(&ix)
+        }
+
+        fn configure_fees(&mut self, outbound_fee: PortFee, inbound_fee: PortFee, fee_vault: &Pubkey) -> ProgramResult {
+            let ix = configure_fees(&self.program_id, &self.admin, &self.port, &outbound_fee, &inbound_fee, fee_vault).unwrap();
+            self.process(&ix)
+        }
+
+        fn contract(&self) -> IBPortContract {
+            self.unpack_contract()
+        }
+    }
+
+    #[test]
+    fn test_configure_fee_vault() {
+        let mut port = new_port();
+        let fee = PortFee { flat: 10, bps: 0 };
+        let (mint, pda) = (port.mint, port.pda);
+
+        let foreign_mint_vault = port.add_token_account(&Pubkey::new_unique(), &pda);
+        let foreign_owner_vault = port.add_token_account(&mint, &Pubkey::new_unique());
+        let fee_vault = port.add_token_account(&mint, &pda);
+
+        for vault in [foreign_mint_vault, foreign_owner_vault] {
+            assert_eq!(port.configure_fees(fee, fee, &vault).unwrap_err(), PortError::InvalidFeeVault.into());
+        }
+
+        port.configure_fees(fee, fee, &fee_vault).unwrap();
+        assert_eq!(port.contract().fees.vault, fee_vault);
+    }
+
+    #[test]
+    fn test_fee_collection_and_withdrawal() {
+        let mut port = new_port();
+        let (mint, pda) = (port.mint, port.pda);
+        let fee_vault = port.add_token_account(&mint, &pda);
+        port.configure_fees(PortFee { flat: 10, bps: 100 }, PortFee { flat: 0, bps: 50 }, &fee_vault).unwrap();
+
+        // 10 flat + 1% of 1000 are paid into the vault, the rest is burned
+        let token_holder = Pubkey::new_unique();
+        take_token_cpis();
+        port.burn(&token_holder, &[1; 16], Some(&fee_vault)).unwrap();
+        assert_eq!(
+            take_token_cpis(),
+            vec![
+                TokenCpi::Transfer { from: token_holder, to: fee_vault, amount: 20 },
+                TokenCpi::Burn { from: token_holder, amount: 980 },
+            ]
+        );
+        assert_eq!(port.contract().requests.get(&[1; 16]).unwrap().amount, 980);
+        assert_eq!((port.contract().fees.accrued, port.contract().fees.pending), (0, 20));
+
+        // the outbound fee is withdrawable once the request is confirmed
+        let confirm = PortOperation {
+            action: PortOperationIdentifier::CONFIRM.as_bytes()[0],
+            swap_id: &[1; 16],
+            amount: PortAmount::BaseUnits(DECIMALS.to_foreign(980).unwrap()),
+            receiver: &[1; 32],
+        }
+        .pack()
+        .unwrap();
+        let ix = confirm_destination_chain_request(&port.program_id, &port.oracle, &port.port, &confirm).unwrap();
+        port.process(&ix).unwrap();
+        assert_eq!((port.contract().fees.accrued, port.contract().fees.pending), (20, 0));
+
+        // 0.5% of the minted amount is kept back, the nebula accounts have no fee vault
+        let recipient = Pubkey::new_unique();
+        let mint_data = PortOperation {
+            action: PortOperationIdentifier::MINT.as_bytes()[0],
+            swap_id: &[2; 16],
+            amount: PortAmount::BaseUnits(DECIMALS.to_foreign(1_000).unwrap()),
+            receiver: &recipient.to_bytes(),
+        }
+        .pack()
+        .unwrap();
+        let ix = attach_value(&port.program_id, &port.oracle, &port.port, &spl_token::id(), &mint, &recipient, &pda, &mint_data)
+            .unwrap();
+        port.process(&ix).unwrap();
+        assert_eq!(take_token_cpis(), vec![TokenCpi::MintTo { to: recipient, amount: 995 }]);
+        assert_eq!((port.contract().fees.accrued, port.contract().fees.unsettled), (20, 5));
+
+        let destination = Pubkey::new_unique();
+        let withdraw = |amount: u64| {
+            withdraw_fees(&port.program_id, &port.admin, &port.port, &spl_token::id(), &fee_vault, &destination, &pda, &mint, amount)
+                .unwrap()
+        };
+        let (over_withdraw_ix, withdraw_ix, drained_withdraw_ix) = (withdraw(26), withdraw(25), withdraw(1));
+
+        assert_eq!(
+            port.process(&over_withdraw_ix).unwrap_err(),
+            PortError::FeeWithdrawalExceedsAccrued.into()
+        );
+
+        port.process(&withdraw_ix).unwrap();
+        assert_eq!(
+            take_token_cpis(),
+            vec![
+                TokenCpi::MintTo { to: fee_vault, amount: 5 },
+                TokenCpi::Transfer { from: fee_vault, to: destination, amount: 25 },
+            ]
+        );
+        assert_eq!((port.contract().fees.accrued, port.contract().fees.unsettled), (0, 0));
+
+        assert_eq!(
+            port.process(&drained_withdraw_ix).unwrap_err(),
+            PortError::FeeWithdrawalExceedsAccrued.into()
+        );
+    }
+
+    #[test]
+    fn test_reclaim_refunds_outbound_fee() {
+        let mut port = new_port();
+        let (mint, pda) = (port.mint, port.pda);
+        let fee_vault = port.add_token_account(&mint, &pda);
+        port.configure_fees(PortFee { flat: 10, bps: 100 }, PortFee::default(), &fee_vault).unwrap();
+
+        let token_holder = Pubkey::new_unique();
+        port.burn(&token_holder, &[1; 16], Some(&fee_vault)).unwrap();
+        let ix = reject_request(&port.program_id, &port.oracle, &port.port, &[1; 16]).unwrap();
+        port.process(&ix).unwrap();
+
+        let reclaim = |fee_vault: Option<&Pubkey>| {
+            reclaim_request(&port.program_id, &token_holder, &port.port, &spl_token::id(), &mint, &token_holder, &pda, &[1; 16], fee_vault)
+                .unwrap()
+        };
+        let (no_fee_vault_ix, foreign_vault_ix, reclaim_ix) = (reclaim(None), reclaim(Some(&Pubkey::new_unique())), reclaim(Some(&fee_vault)));
+
+        assert_eq!(port.process(&no_fee_vault_ix).unwrap_err(), ProgramError::NotEnoughAccountKeys);
+        assert_eq!(port.process(&foreign_vault_ix).unwrap_err(), PortError::InvalidFeeVault.into());
+
+        take_token_cpis();
+        port.process(&reclaim_ix).unwrap();
+        assert_eq!(
+            take_token_cpis(),
+            vec![
+                TokenCpi::MintTo { to: token_holder, amount: 980 },
+                TokenCpi::Transfer { from: fee_vault, to: token_holder, amount: 20 },
+            ]
+        );
+        assert_eq!((port.contract().fees.accrued, port.contract().fees.pending), (0, 0));
+    }
+}
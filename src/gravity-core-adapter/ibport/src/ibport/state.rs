@@ -1,11 +1,12 @@
 use solana_program::{
-    msg,
+    clock::Slot,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
 
 use solana_gravity_contract::gravity::state::PartialStorage;
+use spl_token::state::Mint;
 
 use gravity_misc::model::{AbstractRecordHandler, RecordHandler};
 use gravity_misc::validation::TokenMintConstrained;
@@ -17,10 +18,13 @@ use gravity_misc::ports::state::{
     RequestCountConstrained,
     RequestStatus,
     ForeignAddress,
-    PortOperationIdentifier
+    PortDecimals,
+    PortFees,
+    PortLayout,
+    PortOperationIdentifier,
+    PortRequests,
 };
 
-use arrayref::array_ref;
 use borsh::{BorshDeserialize, BorshSerialize};
 
 // use crate::ibport::token::susy_wrapped_gton_mint;
@@ -43,6 +47,25 @@ pub struct IBPortContract {
     pub is_state_initialized: bool,
 
     pub requests_queue: RequestsQueue<[u8; 16]>,
+
+    // fields below are missing in accounts written before the layout was tagged
+    pub layout: PortLayout,
+
+    // token decimals on Solana and on the foreign chain, set on init;
+    // ports initialized before they were stored have `decimals_configured` unset
+    pub decimals: u8,
+    pub foreign_decimals: u8,
+    pub decimals_configured: bool,
+
+    // pending requests can be reclaimed `request_timeout` slots after their creation, 0 disables it
+    pub request_timeout: Slot,
+    pub requests_creation_slot: RecordHandler<[u8; 16], Slot>,
+
+    // fees taken on request creation (outbound) and on mint/unlock (inbound)
+    pub fees: PortFees,
+
+    // outbound fees paid by the pending requests, refunded when they are reclaimed
+    pub requests_fee: RecordHandler<[u8; 16], u64>,
 }
 
 /* Warning: backward compatibility is constrainted to production IB port data account */
@@ -63,6 +86,24 @@ pub struct UpdatedIBPortContract {
     pub requests_queue: RequestsQueue<[u8; 16]>,
 }
 
+// legacy accounts keep the defaults of the fields added since, e.g. `decimals_configured` is unset
+impl From<UpdatedIBPortContract> for IBPortContract {
+    fn from(legacy: UpdatedIBPortContract) -> Self {
+        IBPortContract {
+            nebula_address: legacy.nebula_address,
+            token_address: legacy.token_address,
+            token_mint: legacy.token_mint,
+            initializer_pubkey: legacy.initializer_pubkey,
+            oracles: legacy.oracles,
+            swap_status: legacy.swap_status,
+            requests: legacy.requests,
+            is_state_initialized: legacy.is_state_initialized,
+            requests_queue: legacy.requests_queue,
+            ..IBPortContract::default()
+        }
+    }
+}
+
 impl TokenMintConstrained<PortError> for IBPortContract {
 
     fn bound_token_mint(&self) -> (Pubkey, PortError) {
@@ -100,19 +141,11 @@ impl Pack for IBPortContract {
     const LEN: usize = 20000;
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let mut mut_src: &[u8] = src;
-        Self::deserialize(&mut mut_src).map_err(|err| {
-            msg!(
-                "Error: failed to deserialize IBPortContract instruction: {}",
-                err
-            );
-            ProgramError::InvalidInstructionData
-        })
+        PortLayout::unpack(src, |legacy: UpdatedIBPortContract| legacy.into())
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let data = self.try_to_vec().unwrap();
-        dst[..data.len()].copy_from_slice(&data);
+        PortLayout::pack_into_slice(self, dst)
     }
 }
 
@@ -120,6 +153,26 @@ impl Pack for IBPortContract {
 pub type PortOperation<'a> = GenericPortOperation<'a, ForeignAddress>;
 
 impl IBPortContract {
+    // decimals used before they were stored in the contract
+    pub const LEGACY_DECIMALS: u8 = 8;
+
+    pub fn port_decimals(&self) -> PortDecimals {
+        if !self.decimals_configured {
+            return PortDecimals::unscaled(Self::LEGACY_DECIMALS);
+        }
+
+        PortDecimals {
+            local: self.decimals,
+            foreign: self.foreign_decimals,
+        }
+    }
+
+    pub fn validate_mint_decimals(&self, token_mint_info: &Mint) -> Result<(), PortError> {
+        if token_mint_info.decimals != self.port_decimals().local {
+            return Err(PortError::InvalidTokenMint);
+        }
+        Ok(())
+    }
 
     fn unprocessed_burn_requests(&self) -> usize {
         self.requests.len()
@@ -133,38 +186,17 @@ impl IBPortContract {
     }
 
     pub fn unpack_byte_array(byte_data: &Vec<u8>) -> Result<PortOperation, ProgramError> {
-        if byte_data.len() < 57 {
-            return Err(PortError::ByteArrayUnpackFailed.into());
-        }
-
-        let mut pos = 0;
-        let action = byte_data[pos];
-        pos += 1;
-
-        let swap_id = array_ref![byte_data, pos, 16];
-        pos += 16;
-        
-        let raw_amount = array_ref![byte_data, pos, 8];
-        pos += 8;
-
-        let receiver = array_ref![byte_data, pos, 32];
-
-        return Ok(PortOperation {
-            action,
-            swap_id,
-            amount: raw_amount,
-            receiver
-        });
+        Ok(PortOperation::unpack(byte_data)?)
     }
 
     pub fn attach_data<'a>(&mut self, byte_data: &'a Vec<u8>, input_pubkey: &'a Pubkey, input_amount: &'a mut u64) -> Result<String, ProgramError> {
-        let action = &[byte_data[0]];
+        let port_operation = Self::unpack_byte_array(byte_data)?;
+        let action = &[port_operation.action];
 
-        let command_char = std::str::from_utf8(action).unwrap();
+        let command_char = std::str::from_utf8(action).map_err(|_| PortError::InvalidDataOnAttach)?;
 
         match command_char {
             PortOperationIdentifier::MINT => {
-                let port_operation = Self::unpack_byte_array(byte_data)?;
                 let swap_status = self.swap_status.get(port_operation.swap_id);
 
                 if swap_status.is_some() {
@@ -175,7 +207,7 @@ impl IBPortContract {
                     return Err(PortError::ErrorOnReceiverUnpack.into());
                 }
                 
-                *input_amount = port_operation.amount_to_u64(8);
+                *input_amount = port_operation.amount_to_u64(&self.port_decimals())?;
 
                 self.swap_status.insert(*port_operation.swap_id, RequestStatus::Success);
             },
@@ -200,59 +232,84 @@ impl IBPortContract {
     }
 
 
-    pub fn drop_processed_request(&mut self, byte_array: &Vec<u8>) -> Result<(), ProgramError>  {
+    pub fn drop_processed_request(&mut self, byte_array: &Vec<u8>) -> Result<(), ProgramError> {
         let port_operation = Self::unpack_byte_array(byte_array)?;
-        let request_id = port_operation.swap_id;
-
-        let request_drop_res = self.requests.drop(request_id);
+        let decimals = self.port_decimals();
 
-        // cannot drop non existing
-        if request_drop_res.is_none() {
-            return Err(PortError::RequestIDForConfirmationIsInvalid.into());
-        }
-
-        let request_drop_res = request_drop_res.unwrap();
+        self.port_requests().confirm(&port_operation, &decimals)?;
 
-        if request_drop_res.destination_address != *port_operation.receiver {
-            return Err(PortError::RequestReceiverMismatch.into());
-        }
+        Ok(())
+    }
 
-        let swap_status = self.swap_status.get(request_id).unwrap();
+    pub fn create_transfer_unwrap_request(&mut self, record_id: &[u8; 16], amount: u64, fee: u64, sender_data_account: &Pubkey, receiver: &ForeignAddress, creation_slot: Slot) -> Result<(), PortError>  {
+        self.validate_requests_count()?;
 
-        // we can't obviously delete unprocessed requests
-        if *swap_status == RequestStatus::New {
-            return Err(PortError::RequestStatusMismatch.into());
-        }
-        
-        let port_amount = port_operation.amount_to_u64(8);
+        self.port_requests().create(record_id, amount, fee, sender_data_account, receiver, creation_slot)
+    }
 
-        if request_drop_res.amount != port_amount {
-            return Err(PortError::RequestAmountMismatch.into());
+    pub fn port_requests(&mut self) -> PortRequests<'_> {
+        PortRequests {
+            requests: &mut self.requests,
+            swap_status: &mut self.swap_status,
+            requests_creation_slot: &mut self.requests_creation_slot,
+            requests_queue: &mut self.requests_queue,
+            requests_fee: &mut self.requests_fee,
+            fees: &mut self.fees,
+            request_timeout: self.request_timeout,
         }
-
-        self.swap_status.drop(request_id).unwrap();
-
-        let rq_queue_index = self.requests_queue.iter().position(|r| *r == *request_id).unwrap();
-        self.requests_queue.remove(rq_queue_index);
-
-        Ok(())
     }
+}
 
-    pub fn create_transfer_unwrap_request(&mut self, record_id: &[u8; 16], amount: u64, sender_data_account: &Pubkey, receiver: &ForeignAddress) -> Result<(), PortError>  {
-        self.validate_requests_count()?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if self.requests.contains_key(record_id) {
-            return Err(PortError::RequestIDIsAlreadyBeingProcessed.into());
-        }
+    #[test]
+    fn test_port_decimals() {
+        let mut ibport = IBPortContract::default();
+        assert_eq!(ibport.port_decimals(), PortDecimals::unscaled(IBPortContract::LEGACY_DECIMALS));
 
-        self.requests.insert(*record_id, UnwrapRequest {
-            destination_address: *receiver,
-            origin_address: *sender_data_account,
-            amount
-        });
-        self.swap_status.insert(*record_id, RequestStatus::New);
-        self.requests_queue.push(*record_id);
+        ibport.decimals_configured = true;
+        assert_eq!(ibport.port_decimals(), PortDecimals { local: 0, foreign: 0 });
+        ibport.validate_mint_decimals(&Mint { decimals: 0, ..Mint::default() }).unwrap();
+        assert!(ibport.validate_mint_decimals(&Mint { decimals: 8, ..Mint::default() }).is_err());
+    }
 
-        Ok(())
+    #[test]
+    fn test_unpack_legacy_layout() {
+        let mut legacy = UpdatedIBPortContract {
+            nebula_address: Pubkey::new_unique(),
+            token_mint: Pubkey::new_unique(),
+            oracles: vec![Pubkey::new_unique()],
+            is_state_initialized: true,
+            requests_queue: vec![[1; 16]],
+            ..UpdatedIBPortContract::default()
+        };
+        legacy.swap_status.insert([1; 16], RequestStatus::New);
+
+        // the account used to hold a longer queue, its leftovers follow the legacy fields
+        let legacy_data = legacy.try_to_vec().unwrap();
+        let mut data = vec![0xff; IBPortContract::LEN];
+        data[..legacy_data.len()].copy_from_slice(&legacy_data);
+
+        let ibport = IBPortContract::unpack(&data).unwrap();
+        assert_eq!(ibport.nebula_address, legacy.nebula_address);
+        assert_eq!(ibport.oracles, legacy.oracles);
+        assert_eq!(ibport.swap_status, legacy.swap_status);
+        assert_eq!(ibport.requests_queue, legacy.requests_queue);
+        assert!(!ibport.decimals_configured);
+        assert_eq!(ibport.request_timeout, 0);
+        assert_eq!(ibport.fees, PortFees::default());
+        assert_eq!(ibport.layout, PortLayout::CURRENT);
+
+        // the upgraded contract is tagged and nothing is left of the previous state
+        IBPortContract::pack(ibport.clone(), &mut data).unwrap();
+        let packed_len = ibport.try_to_vec().unwrap().len();
+        assert!(data[packed_len..].iter().all(|byte| *byte == 0));
+        assert_eq!(IBPortContract::unpack(&data).unwrap(), ibport);
+
+        // the version byte follows the tag
+        data[legacy_data.len() + PortLayout::TAG.len()] = PortLayout::CURRENT.version + 1;
+        assert_eq!(IBPortContract::unpack(&data).unwrap_err(), ProgramError::InvalidAccountData);
     }
-}
\ No newline at end of file
+}
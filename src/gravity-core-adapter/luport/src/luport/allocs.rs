@@ -15,6 +15,7 @@ pub fn allocation_by_instruction_index(
         ],
         // CreateTransferUnwrapRequest
         1 => vec![
+            1,
             LUPortContractInstruction::DEST_AMOUNT_ALLOC,
             LUPortContractInstruction::FOREIGN_ADDRESS_ALLOC,
             16,
@@ -29,6 +30,22 @@ pub fn allocation_by_instruction_index(
             LUPortContractInstruction::PUBKEY_ALLOC,
             LUPortContractInstruction::PUBKEY_ALLOC,
         ],
+        // RejectRequest, ReclaimRequest
+        5 | 6 => vec![16],
+        // SetRequestTimeout
+        7 => vec![8],
+        // ConfigureFees
+        8 => vec![
+            8,
+            2,
+            8,
+            2,
+            LUPortContractInstruction::PUBKEY_ALLOC,
+        ],
+        // WithdrawFees
+        9 => vec![8],
+        // SetLockVault
+        10 => vec![LUPortContractInstruction::PUBKEY_ALLOC],
         _ => return Err(PortError::InvalidInstructionIndex.into()),
     })
 }
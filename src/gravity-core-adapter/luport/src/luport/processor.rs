@@ -1,23 +1,26 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::{Clock, Slot},
     entrypoint::ProgramResult,
     msg,
     program::invoke_signed,
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
+    sysvar::Sysvar,
 };
 
 use spl_token::{
     instruction::transfer,
-    state::Mint,
+    state::{Account, Mint},
 };
 
 
 use crate::luport::instruction::LUPortContractInstruction;
 use crate::luport::state::LUPortContract;
 use gravity_misc::ports::error::PortError;
-use gravity_misc::ports::state::{PortOperationIdentifier, ForeignAddress};
+use gravity_misc::ports::instruction::RequestAmount;
+use gravity_misc::ports::state::{is_port_vault, PortOperationIdentifier, ForeignAddress, PortFee, PortFees};
 use gravity_misc::validation::{PDAResolver, TokenMintConstrained, validate_pubkey_match, validate_contract_emptiness};
 
 
@@ -30,6 +33,9 @@ impl LUPortProcessor {
         token_mint: &Pubkey,
         nebula_address: &Pubkey,
         oracles: &Vec<Pubkey>,
+        decimals: u8,
+        foreign_decimals: u8,
+        lock_vault: &Pubkey,
         _program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -50,6 +56,10 @@ impl LUPortProcessor {
         luport_contract_info.token_mint = *token_mint;
         luport_contract_info.nebula_address = *nebula_address;
         luport_contract_info.oracles = oracles.clone();
+        luport_contract_info.decimals = decimals;
+        luport_contract_info.foreign_decimals = foreign_decimals;
+        luport_contract_info.decimals_configured = true;
+        luport_contract_info.lock_vault = *lock_vault;
         luport_contract_info.initializer_pubkey = *initializer.key;
 
         msg!("instantiated ib port contract");
@@ -67,7 +77,7 @@ impl LUPortProcessor {
     fn process_create_transfer_unwrap_request(
         accounts: &[AccountInfo],
         request_id: &[u8; 16],
-        ui_amount: f64,
+        amount: RequestAmount,
         foreign_receiver: &ForeignAddress,
         _program_id: &Pubkey,
     ) -> ProgramResult {
@@ -95,12 +105,46 @@ impl LUPortProcessor {
 
         let token_mint_info = Mint::unpack(&mint.data.borrow()[0..Mint::LEN])?;
 
-        let amount = spl_token::ui_amount_to_amount(ui_amount, token_mint_info.decimals);
+        let amount = amount.to_base_units(token_mint_info.decimals)?;
+        let (amount, fee) = luport_contract_info.fees.collect_outbound(amount)?;
+
+        // the locked amount has to be representable on the foreign chain
+        if luport_contract_info.port_decimals(&token_mint_info)?.to_foreign(amount).is_none() {
+            return Err(PortError::InvalidAmount.into());
+        }
 
         let token_holder = next_account_info(account_info_iter)?;
         let token_receiver = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
 
         luport_contract_info.validate_token_mint(mint.key)?;
+        luport_contract_info.validate_lock_vault(token_receiver.key)?;
+
+        if fee > 0 {
+            let fee_vault = next_account_info(account_info_iter)?;
+            luport_contract_info.fees.validate_vault(fee_vault.key)?;
+
+            msg!("collecting outbound fee");
+            let transfer_ix = transfer(
+                &token_program_id.key,
+                &token_holder.key,
+                &fee_vault.key,
+                &initializer.key,
+                &[],
+                fee
+            )?;
+
+            invoke_signed(
+                &transfer_ix,
+                &[
+                    token_holder.clone(),
+                    fee_vault.clone(),
+                    initializer.clone(),
+                    token_program_id.clone(),
+                ],
+                &[&[PDAResolver::Gravity.bump_seeds()]],
+            )?;
+        }
 
         // lock tockens
         let transfer_ix = transfer(
@@ -124,7 +168,7 @@ impl LUPortProcessor {
         )?;
 
         msg!("saving request info");
-        luport_contract_info.create_transfer_wrap_request(request_id, amount, token_holder.key, foreign_receiver)?;
+        luport_contract_info.create_transfer_wrap_request(request_id, amount, fee, token_holder.key, foreign_receiver, clock.slot)?;
 
         LUPortContract::pack(
             luport_contract_info,
@@ -182,6 +226,7 @@ impl LUPortProcessor {
         let token_holder = next_account_info(account_info_iter)?;
 
         luport_contract_info.validate_token_mint(mint.key)?;
+        luport_contract_info.validate_lock_vault(token_holder.key)?;
 
         msg!("Creating unlock IX");
 
@@ -191,6 +236,8 @@ impl LUPortProcessor {
         let operation = luport_contract_info.attach_data(byte_data, recipient_account.key, &mut amount, &token_mint_info)?;
 
         if operation == PortOperationIdentifier::UNLOCK {
+            let amount = luport_contract_info.fees.collect_inbound(amount)?;
+
             let transfer_ix = transfer(
                 &token_program_id.key,
                 &token_holder.key,
@@ -220,6 +267,390 @@ impl LUPortProcessor {
         Ok(())
     }
 
+    fn process_confirm_destination_chain_request(
+        accounts: &[AccountInfo],
+        byte_data: &Vec<u8>,
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let luport_contract_account = next_account_info(account_info_iter)?;
+
+        let mut luport_contract_info =
+            LUPortContract::unpack(&luport_contract_account.data.borrow()[0..LUPortContract::LEN])?;
+
+        msg!("validating initializer");
+        Self::validate_data_provider(
+            &luport_contract_info.oracles,
+            initializer.key,
+        )?;
+
+        let mint = next_account_info(account_info_iter)?;
+        luport_contract_info.validate_token_mint(mint.key)?;
+
+        let token_mint_info = Mint::unpack(&mint.data.borrow()[0..Mint::LEN])?;
+        let decimals = luport_contract_info.port_decimals(&token_mint_info)?;
+
+        msg!("dropping processed request");
+        luport_contract_info.drop_processed_request(byte_data, &decimals)?;
+
+        LUPortContract::pack(
+            luport_contract_info,
+            &mut luport_contract_account.try_borrow_mut_data()?[0..LUPortContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    fn process_reject_request(
+        accounts: &[AccountInfo],
+        request_id: &[u8; 16],
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let luport_contract_account = next_account_info(account_info_iter)?;
+
+        let mut luport_contract_info =
+            LUPortContract::unpack(&luport_contract_account.data.borrow()[0..LUPortContract::LEN])?;
+
+        msg!("validating initializer");
+        Self::validate_data_provider(
+            &luport_contract_info.oracles,
+            initializer.key,
+        )?;
+
+        luport_contract_info.port_requests().reject(request_id)?;
+
+        LUPortContract::pack(
+            luport_contract_info,
+            &mut luport_contract_account.try_borrow_mut_data()?[0..LUPortContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    fn process_reclaim_request(
+        accounts: &[AccountInfo],
+        request_id: &[u8; 16],
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let luport_contract_account = next_account_info(account_info_iter)?;
+
+        let mut luport_contract_info =
+            LUPortContract::unpack(&luport_contract_account.data.borrow()[0..LUPortContract::LEN])?;
+
+        let token_program_id = next_account_info(account_info_iter)?;
+
+        if *token_program_id.key != luport_contract_info.token_address {
+            return Err(PortError::InvalidInputToken.into());
+        }
+
+        let mint = next_account_info(account_info_iter)?;
+        let origin_account = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let lock_account = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        luport_contract_info.validate_token_mint(mint.key)?;
+        luport_contract_info.validate_lock_vault(lock_account.key)?;
+
+        let (amount, fee) = luport_contract_info.port_requests().reclaim(request_id, origin_account.key, clock.slot)?;
+
+        msg!("unlocking reclaimed request");
+        let transfer_ix = transfer(
+            &token_program_id.key,
+            &lock_account.key,
+            &origin_account.key,
+            &pda_account.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(
+            &transfer_ix,
+            &[
+                lock_account.clone(),
+                origin_account.clone(),
+                pda_account.clone(),
+                token_program_id.clone(),
+            ],
+            &[&[PDAResolver::Gravity.bump_seeds()]]
+        )?;
+
+        if fee > 0 {
+            let fee_vault = next_account_info(account_info_iter)?;
+            luport_contract_info.fees.validate_vault(fee_vault.key)?;
+
+            msg!("refunding outbound fee");
+            let transfer_ix = transfer(
+                &token_program_id.key,
+                &fee_vault.key,
+                &origin_account.key,
+                &pda_account.key,
+                &[],
+                fee,
+            )?;
+
+            invoke_signed(
+                &transfer_ix,
+                &[
+                    fee_vault.clone(),
+                    origin_account.clone(),
+                    pda_account.clone(),
+                    token_program_id.clone(),
+                ],
+                &[&[PDAResolver::Gravity.bump_seeds()]]
+            )?;
+        }
+
+        LUPortContract::pack(
+            luport_contract_info,
+            &mut luport_contract_account.try_borrow_mut_data()?[0..LUPortContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    fn process_set_request_timeout(
+        accounts: &[AccountInfo],
+        request_timeout: Slot,
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let luport_contract_account = next_account_info(account_info_iter)?;
+
+        let mut luport_contract_info =
+            LUPortContract::unpack(&luport_contract_account.data.borrow()[0..LUPortContract::LEN])?;
+
+        if luport_contract_info.initializer_pubkey != *initializer.key {
+            return Err(PortError::AccessDenied.into());
+        }
+
+        luport_contract_info.request_timeout = request_timeout;
+
+        LUPortContract::pack(
+            luport_contract_info,
+            &mut luport_contract_account.try_borrow_mut_data()?[0..LUPortContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    fn process_configure_fees(
+        accounts: &[AccountInfo],
+        outbound_fee: PortFee,
+        inbound_fee: PortFee,
+        fee_vault: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let luport_contract_account = next_account_info(account_info_iter)?;
+
+        let mut luport_contract_info =
+            LUPortContract::unpack(&luport_contract_account.data.borrow()[0..LUPortContract::LEN])?;
+
+        if luport_contract_info.initializer_pubkey != *initializer.key {
+            return Err(PortError::AccessDenied.into());
+        }
+
+        if *fee_vault != Pubkey::default() {
+            let fee_vault_account = next_account_info(account_info_iter)?;
+
+            // the fees are accounted apart from the locked tokens
+            if fee_vault_account.key != fee_vault
+                || *fee_vault == luport_contract_info.lock_vault
+                || *fee_vault_account.owner != luport_contract_info.token_address {
+                return Err(PortError::InvalidFeeVault.into());
+            }
+
+            let fee_vault_info = Account::unpack(&fee_vault_account.data.borrow())?;
+            PortFees::validate_vault_account(
+                &fee_vault_info,
+                &luport_contract_info.token_mint,
+                &PDAResolver::Gravity.address(program_id)?,
+            )?;
+        }
+
+        luport_contract_info.fees.configure(outbound_fee, inbound_fee, fee_vault)?;
+
+        LUPortContract::pack(
+            luport_contract_info,
+            &mut luport_contract_account.try_borrow_mut_data()?[0..LUPortContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    fn process_withdraw_fees(
+        accounts: &[AccountInfo],
+        amount: u64,
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let luport_contract_account = next_account_info(account_info_iter)?;
+
+        let mut luport_contract_info =
+            LUPortContract::unpack(&luport_contract_account.data.borrow()[0..LUPortContract::LEN])?;
+
+        if luport_contract_info.initializer_pubkey != *initializer.key {
+            return Err(PortError::AccessDenied.into());
+        }
+
+        let token_program_id = next_account_info(account_info_iter)?;
+
+        if *token_program_id.key != luport_contract_info.token_address {
+            return Err(PortError::InvalidInputToken.into());
+        }
+
+        let fee_vault = next_account_info(account_info_iter)?;
+        let destination = next_account_info(account_info_iter)?;
+        let pda_account = next_account_info(account_info_iter)?;
+        let lock_account = next_account_info(account_info_iter)?;
+
+        luport_contract_info.validate_lock_vault(lock_account.key)?;
+        luport_contract_info.fees.validate_vault(fee_vault.key)?;
+
+        let unsettled = luport_contract_info.fees.settle()?;
+        luport_contract_info.fees.withdraw(amount)?;
+
+        if unsettled > 0 {
+            msg!("moving kept back inbound fees");
+            let transfer_ix = transfer(
+                &token_program_id.key,
+                &lock_account.key,
+                &fee_vault.key,
+                &pda_account.key,
+                &[],
+                unsettled,
+            )?;
+
+            invoke_signed(
+                &transfer_ix,
+                &[
+                    lock_account.clone(),
+                    fee_vault.clone(),
+                    pda_account.clone(),
+                    token_program_id.clone(),
+                ],
+                &[&[PDAResolver::Gravity.bump_seeds()]]
+            )?;
+        }
+
+        msg!("withdrawing fees");
+        let transfer_ix = transfer(
+            &token_program_id.key,
+            &fee_vault.key,
+            &destination.key,
+            &pda_account.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(
+            &transfer_ix,
+            &[
+                fee_vault.clone(),
+                destination.clone(),
+                pda_account.clone(),
+                token_program_id.clone(),
+            ],
+            &[&[PDAResolver::Gravity.bump_seeds()]]
+        )?;
+
+        LUPortContract::pack(
+            luport_contract_info,
+            &mut luport_contract_account.try_borrow_mut_data()?[0..LUPortContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    fn process_set_lock_vault(
+        accounts: &[AccountInfo],
+        lock_vault: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let luport_contract_account = next_account_info(account_info_iter)?;
+
+        let mut luport_contract_info =
+            LUPortContract::unpack(&luport_contract_account.data.borrow()[0..LUPortContract::LEN])?;
+
+        if luport_contract_info.initializer_pubkey != *initializer.key {
+            return Err(PortError::AccessDenied.into());
+        }
+
+        let lock_vault_account = next_account_info(account_info_iter)?;
+
+        // only ports initialized without a lock vault can bind one, the locked tokens never move to another vault
+        if luport_contract_info.lock_vault != Pubkey::default()
+            || lock_vault_account.key != lock_vault
+            || *lock_vault == luport_contract_info.fees.vault
+            || *lock_vault_account.owner != luport_contract_info.token_address {
+            return Err(PortError::InvalidLockVault.into());
+        }
+
+        let lock_vault_info = Account::unpack(&lock_vault_account.data.borrow())?;
+        if !is_port_vault(
+            &lock_vault_info,
+            &luport_contract_info.token_mint,
+            &PDAResolver::Gravity.address(program_id)?,
+        ) {
+            return Err(PortError::InvalidLockVault.into());
+        }
+
+        luport_contract_info.lock_vault = *lock_vault;
+
+        LUPortContract::pack(
+            luport_contract_info,
+            &mut luport_contract_account.try_borrow_mut_data()?[0..LUPortContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
     pub fn process(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -233,6 +664,9 @@ impl LUPortProcessor {
                 token_mint,
                 nebula_address,
                 oracles,
+                decimals,
+                foreign_decimals,
+                lock_vault,
             } => {
                 msg!("Instruction: Init IB Port Contract");
 
@@ -242,6 +676,9 @@ impl LUPortProcessor {
                     &token_mint,
                     &nebula_address,
                     &oracles,
+                    decimals,
+                    foreign_decimals,
+                    &lock_vault,
                     program_id,
                 )
             }
@@ -271,6 +708,425 @@ impl LUPortProcessor {
                     program_id,
                 )
             }
+            LUPortContractInstruction::ConfirmDestinationChainRequest {
+                byte_data
+            } => {
+                msg!("Instruction: ConfirmDestinationChainRequest");
+
+                Self::process_confirm_destination_chain_request(
+                    accounts,
+                    &byte_data,
+                    program_id,
+                )
+            }
+            LUPortContractInstruction::RejectRequest {
+                request_id
+            } => {
+                msg!("Instruction: RejectRequest");
+
+                Self::process_reject_request(
+                    accounts,
+                    &request_id,
+                    program_id,
+                )
+            }
+            LUPortContractInstruction::ReclaimRequest {
+                request_id
+            } => {
+                msg!("Instruction: ReclaimRequest");
+
+                Self::process_reclaim_request(
+                    accounts,
+                    &request_id,
+                    program_id,
+                )
+            }
+            LUPortContractInstruction::SetRequestTimeout {
+                request_timeout
+            } => {
+                msg!("Instruction: SetRequestTimeout");
+
+                Self::process_set_request_timeout(
+                    accounts,
+                    request_timeout,
+                    program_id,
+                )
+            }
+            LUPortContractInstruction::ConfigureFees {
+                outbound_fee,
+                inbound_fee,
+                fee_vault,
+            } => {
+                msg!("Instruction: ConfigureFees");
+
+                Self::process_configure_fees(
+                    accounts,
+                    outbound_fee,
+                    inbound_fee,
+                    &fee_vault,
+                    program_id,
+                )
+            }
+            LUPortContractInstruction::WithdrawFees {
+                amount
+            } => {
+                msg!("Instruction: WithdrawFees");
+
+                Self::process_withdraw_fees(
+                    accounts,
+                    amount,
+                    program_id,
+                )
+            }
+            LUPortContractInstruction::SetLockVault {
+                lock_vault
+            } => {
+                msg!("Instruction: SetLockVault");
+
+                Self::process_set_lock_vault(
+                    accounts,
+                    &lock_vault,
+                    program_id,
+                )
+            }
         }
     }    
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::luport::instruction::{
+        attach_value, configure_fees, confirm_destination_chain_request, create_transfer_unwrap_request, init_contract,
+        reclaim_request, reject_request, set_lock_vault, withdraw_fees,
+    };
+    use crate::luport::state::PortOperation;
+    use gravity_misc::model::AbstractRecordHandler;
+    use gravity_misc::ports::state::{PortAmount, PortDecimals};
+    use gravity_misc::test_support::{take_token_cpis, TestPort, TokenCpi};
+
+    const DECIMALS: PortDecimals = PortDecimals { local: 9, foreign: 18 };
+
+    fn new_port() -> TestPort {
+        let mut port = TestPort::new(LUPortProcessor::process, LUPortContract::LEN, DECIMALS.local);
+        let (mint, pda) = (port.mint, port.pda);
+        let lock_vault = port.add_token_account(&mint, &pda);
+
+        let ix = init_contract(
+            &port.program_id,
+            &port.admin,
+            &port.port,
+            &Pubkey::new_unique(),
+            &spl_token::id(),
+            &mint,
+            &[port.oracle],
+            DECIMALS.local,
+            DECIMALS.foreign,
+            &lock_vault,
+        )
+        .unwrap();
+        port.process(&ix).unwrap();
+        port
+    }
+
+    trait LUTestPort {
+        fn lock_vault(&self) -> Pubkey;
+        fn lock(&mut self, token_holder: &Pubkey, lock_vault: &Pubkey, request_id: &[u8; 16], fee_vault: Option<&Pubkey>) -> ProgramResult;
+        fn configure_fees(&mut self, outbound_fee: PortFee, inbound_fee: PortFee, fee_vault: &Pubkey) -> ProgramResult;
+        fn contract(&self) -> LUPortContract;
+    }
+
+    impl LUTestPort for TestPort {
+        fn lock_vault(&self) -> Pubkey {
+            self.contract().lock_vault
+        }
+
+        fn lock(&mut self, token_holder: &Pubkey, lock_vault: &Pubkey, request_id: &[u8; 16], fee_vault: Option<&Pubkey>) -> ProgramResult {
+            let ix = create_transfer_unwrap_request(
+                &self.program_id,
+                &Pubkey::new_unique(),
+                &self.port,
+                &spl_token::id(),
+                &self.mint,
+                token_holder,
+                lock_vault,
+                request_id,
+                1_000,
+                &[1; 32],
+                fee_vault,
+            )
+            .unwrap();
+            self.process(&ix)
+        }
+
+        fn configure_fees(&mut self, outbound_fee: PortFee, inbound_fee: PortFee, fee_vault: &Pubkey) -> ProgramResult {
+            let ix = configure_fees(&self.program_id, &self.admin, &self.port, &outbound_fee, &inbound_fee, fee_vault).unwrap();
+            self.process(&ix)
+        }
+
+        fn contract(&self) -> LUPortContract {
+            self.unpack_contract()
+        }
+    }
+
+    #[test]
+    fn test_lock_into_foreign_vault() {
+        let mut port = new_port();
+        let token_holder = Pubkey::new_unique();
+
+        assert_eq!(
+            port.lock(&token_holder, &Pubkey::new_unique(), &[1; 16], None).unwrap_err(),
+            PortError::InvalidLockVault.into()
+        );
+
+        let lock_vault = port.lock_vault();
+        port.lock(&token_holder, &lock_vault, &[1; 16], None).unwrap();
+        assert_eq!(port.contract().requests.len(), 1);
+    }
+
+    #[test]
+    fn test_reclaim_from_foreign_vault() {
+        let mut port = new_port();
+        let token_holder = Pubkey::new_unique();
+        let lock_vault = port.lock_vault();
+        port.lock(&token_holder, &lock_vault, &[1; 16], None).unwrap();
+
+        let ix = reject_request(&port.program_id, &port.oracle, &port.port, &[1; 16]).unwrap();
+        port.process(&ix).unwrap();
+
+        let reclaim = |lock_vault: &Pubkey| {
+            reclaim_request(
+                &port.program_id,
+                &token_holder,
+                &port.port,
+                &spl_token::id(),
+                &port.mint,
+                &token_holder,
+                &port.pda,
+                lock_vault,
+                &[1; 16],
+                None,
+            )
+            .unwrap()
+        };
+
+        let foreign_vault_ix = reclaim(&Pubkey::new_unique());
+        let lock_vault_ix = reclaim(&lock_vault);
+
+        assert_eq!(
+            port.process(&foreign_vault_ix).unwrap_err(),
+            PortError::InvalidLockVault.into()
+        );
+
+        port.process(&lock_vault_ix).unwrap();
+        assert_eq!(port.contract().requests.len(), 0);
+    }
+
+    #[test]
+    fn test_reclaim_refunds_outbound_fee() {
+        let mut port = new_port();
+        let (mint, pda, lock_vault) = (port.mint, port.pda, port.lock_vault());
+        let fee_vault = port.add_token_account(&mint, &pda);
+        let fee = PortFee { flat: 10, bps: 100 };
+        port.configure_fees(fee, PortFee::default(), &fee_vault).unwrap();
+
+        let token_holder = Pubkey::new_unique();
+        port.lock(&token_holder, &lock_vault, &[1; 16], Some(&fee_vault)).unwrap();
+        let ix = reject_request(&port.program_id, &port.oracle, &port.port, &[1; 16]).unwrap();
+        port.process(&ix).unwrap();
+
+        // the vault holding the pending fees can't be replaced
+        let other_vault = port.add_token_account(&mint, &pda);
+        assert_eq!(port.configure_fees(fee, PortFee::default(), &other_vault).unwrap_err(), PortError::InvalidFeeVault.into());
+
+        let reclaim = |fee_vault: Option<&Pubkey>| {
+            reclaim_request(
+                &port.program_id,
+                &token_holder,
+                &port.port,
+                &spl_token::id(),
+                &mint,
+                &token_holder,
+                &pda,
+                &lock_vault,
+                &[1; 16],
+                fee_vault,
+            )
+            .unwrap()
+        };
+        let (no_fee_vault_ix, other_vault_ix, reclaim_ix) = (reclaim(None), reclaim(Some(&other_vault)), reclaim(Some(&fee_vault)));
+
+        assert_eq!(port.process(&no_fee_vault_ix).unwrap_err(), ProgramError::NotEnoughAccountKeys);
+        assert_eq!(port.process(&other_vault_ix).unwrap_err(), PortError::InvalidFeeVault.into());
+
+        take_token_cpis();
+        port.process(&reclaim_ix).unwrap();
+        assert_eq!(
+            take_token_cpis(),
+            vec![
+                TokenCpi::Transfer { from: lock_vault, to: token_holder, amount: 980 },
+                TokenCpi::Transfer { from: fee_vault, to: token_holder, amount: 20 },
+            ]
+        );
+        assert_eq!((port.contract().fees.accrued, port.contract().fees.pending), (0, 0));
+        assert!(port.contract().requests_fee.get(&[1; 16]).is_none());
+
+        port.configure_fees(fee, PortFee::default(), &other_vault).unwrap();
+    }
+
+    #[test]
+    fn test_set_lock_vault_of_legacy_port() {
+        let mut port = new_port();
+        let (mint, pda, lock_vault) = (port.mint, port.pda, port.lock_vault());
+
+        // ports initialized before the lock vault was bound to the contract
+        let mut contract = port.contract();
+        contract.lock_vault = Pubkey::default();
+        LUPortContract::pack(contract, &mut port.accounts[0].data[0..LUPortContract::LEN]).unwrap();
+
+        let token_holder = Pubkey::new_unique();
+        assert_eq!(
+            port.lock(&token_holder, &lock_vault, &[1; 16], None).unwrap_err(),
+            PortError::InvalidLockVault.into()
+        );
+
+        let foreign_mint_vault = port.add_token_account(&Pubkey::new_unique(), &pda);
+        let foreign_owner_vault = port.add_token_account(&mint, &Pubkey::new_unique());
+        let set = |initializer: &Pubkey, vault: &Pubkey| {
+            set_lock_vault(&port.program_id, initializer, &port.port, vault).unwrap()
+        };
+        let (foreign_initializer_ix, foreign_mint_ix, foreign_owner_ix, set_ix) = (
+            set(&Pubkey::new_unique(), &lock_vault),
+            set(&port.admin, &foreign_mint_vault),
+            set(&port.admin, &foreign_owner_vault),
+            set(&port.admin, &lock_vault),
+        );
+
+        assert_eq!(port.process(&foreign_initializer_ix).unwrap_err(), PortError::AccessDenied.into());
+        for ix in [foreign_mint_ix, foreign_owner_ix] {
+            assert_eq!(port.process(&ix).unwrap_err(), PortError::InvalidLockVault.into());
+        }
+
+        port.process(&set_ix).unwrap();
+        assert_eq!(port.contract().lock_vault, lock_vault);
+
+        // the bound lock vault can't be replaced
+        assert_eq!(port.process(&set_ix).unwrap_err(), PortError::InvalidLockVault.into());
+
+        port.lock(&token_holder, &lock_vault, &[1; 16], None).unwrap();
+        assert_eq!(port.contract().requests.len(), 1);
+    }
+
+    #[test]
+    fn test_configure_fee_vault() {
+        let mut port = new_port();
+        let fee = PortFee { flat: 10, bps: 0 };
+        let (mint, pda, lock_vault) = (port.mint, port.pda, port.lock_vault());
+
+        let foreign_mint_vault = port.add_token_account(&Pubkey::new_unique(), &pda);
+        let foreign_owner_vault = port.add_token_account(&mint, &Pubkey::new_unique());
+        let fee_vault = port.add_token_account(&mint, &pda);
+
+        for vault in [foreign_mint_vault, foreign_owner_vault] {
+            assert_eq!(port.configure_fees(fee, fee, &vault).unwrap_err(), PortError::InvalidFeeVault.into());
+        }
+
+        // the lock account can't hold the fees
+        assert_eq!(port.configure_fees(fee, fee, &lock_vault).unwrap_err(), PortError::InvalidFeeVault.into());
+
+        port.configure_fees(fee, fee, &fee_vault).unwrap();
+        assert_eq!(port.contract().fees.vault, fee_vault);
+    }
+
+    #[test]
+    fn test_fee_collection_and_withdrawal() {
+        let mut port = new_port();
+        let (mint, pda, lock_vault) = (port.mint, port.pda, port.lock_vault());
+        let fee_vault = port.add_token_account(&mint, &pda);
+        port.configure_fees(PortFee { flat: 10, bps: 100 }, PortFee { flat: 0, bps: 50 }, &fee_vault).unwrap();
+
+        // 10 flat + 1% of 1000 are paid into the vault, the rest is locked
+        let token_holder = Pubkey::new_unique();
+        take_token_cpis();
+        port.lock(&token_holder, &lock_vault, &[1; 16], Some(&fee_vault)).unwrap();
+        assert_eq!(
+            take_token_cpis(),
+            vec![
+                TokenCpi::Transfer { from: token_holder, to: fee_vault, amount: 20 },
+                TokenCpi::Transfer { from: token_holder, to: lock_vault, amount: 980 },
+            ]
+        );
+        assert_eq!(port.contract().requests.get(&[1; 16]).unwrap().amount, 980);
+        assert_eq!((port.contract().fees.accrued, port.contract().fees.pending), (0, 20));
+
+        // the outbound fee is withdrawable once the request is confirmed
+        let confirm = PortOperation {
+            action: PortOperationIdentifier::CONFIRM.as_bytes()[0],
+            swap_id: &[1; 16],
+            amount: PortAmount::BaseUnits(DECIMALS.to_foreign(980).unwrap()),
+            receiver: &[1; 32],
+        }
+        .pack()
+        .unwrap();
+        let ix = confirm_destination_chain_request(&port.program_id, &port.oracle, &port.port, &mint, &confirm).unwrap();
+        port.process(&ix).unwrap();
+        assert_eq!((port.contract().fees.accrued, port.contract().fees.pending), (20, 0));
+
+        // 0.5% of the unlocked amount is kept back in the lock account
+        let recipient = Pubkey::new_unique();
+        let unlock = PortOperation {
+            action: PortOperationIdentifier::UNLOCK.as_bytes()[0],
+            swap_id: &[2; 16],
+            amount: PortAmount::BaseUnits(DECIMALS.to_foreign(1_000).unwrap()),
+            receiver: &recipient.to_bytes(),
+        }
+        .pack()
+        .unwrap();
+        let ix = attach_value(
+            &port.program_id,
+            &port.oracle,
+            &port.port,
+            &spl_token::id(),
+            &mint,
+            &recipient,
+            &pda,
+            &Pubkey::new_unique(),
+            &lock_vault,
+            &unlock,
+        )
+        .unwrap();
+        port.process(&ix).unwrap();
+        assert_eq!(
+            take_token_cpis(),
+            vec![TokenCpi::Transfer { from: lock_vault, to: recipient, amount: 995 }]
+        );
+        assert_eq!((port.contract().fees.accrued, port.contract().fees.unsettled), (20, 5));
+
+        let destination = Pubkey::new_unique();
+        let withdraw = |amount: u64| {
+            withdraw_fees(&port.program_id, &port.admin, &port.port, &spl_token::id(), &fee_vault, &destination, &pda, &lock_vault, amount)
+                .unwrap()
+        };
+        let (over_withdraw_ix, withdraw_ix, drained_withdraw_ix) = (withdraw(26), withdraw(25), withdraw(1));
+
+        assert_eq!(
+            port.process(&over_withdraw_ix).unwrap_err(),
+            PortError::FeeWithdrawalExceedsAccrued.into()
+        );
+
+        port.process(&withdraw_ix).unwrap();
+        assert_eq!(
+            take_token_cpis(),
+            vec![
+                TokenCpi::Transfer { from: lock_vault, to: fee_vault, amount: 5 },
+                TokenCpi::Transfer { from: fee_vault, to: destination, amount: 25 },
+            ]
+        );
+        assert_eq!((port.contract().fees.accrued, port.contract().fees.unsettled), (0, 0));
+
+        assert_eq!(
+            port.process(&drained_withdraw_ix).unwrap_err(),
+            PortError::FeeWithdrawalExceedsAccrued.into()
+        );
+    }
+}
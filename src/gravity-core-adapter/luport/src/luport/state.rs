@@ -1,5 +1,6 @@
 use solana_program::{
     msg,
+    clock::Slot,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
@@ -17,10 +18,13 @@ use gravity_misc::ports::state::{
     RequestCountConstrained,
     RequestStatus,
     ForeignAddress,
-    PortOperationIdentifier
+    PortDecimals,
+    PortFees,
+    PortLayout,
+    PortOperationIdentifier,
+    PortRequests,
 };
 
-use arrayref::array_ref;
 use borsh::{BorshDeserialize, BorshSerialize};
 
 use gravity_misc::ports::error::PortError;
@@ -44,6 +48,64 @@ pub struct LUPortContract {
     pub is_state_initialized: bool,
 
     pub requests_queue: RequestsQueue<[u8; 16]>,
+
+    // fields below are missing in accounts written before the layout was tagged
+    pub layout: PortLayout,
+
+    // token decimals on Solana and on the foreign chain, set on init;
+    // ports initialized before they were stored have `decimals_configured` unset
+    pub decimals: u8,
+    pub foreign_decimals: u8,
+    pub decimals_configured: bool,
+
+    // pending requests can be reclaimed `request_timeout` slots after their creation, 0 disables it
+    pub request_timeout: Slot,
+    pub requests_creation_slot: RecordHandler<[u8; 16], Slot>,
+
+    // fees taken on request creation (outbound) and on mint/unlock (inbound)
+    pub fees: PortFees,
+
+    // PDA owned token account holding the locked tokens, set on init
+    pub lock_vault: Pubkey,
+
+    // outbound fees paid by the pending requests, refunded when they are reclaimed
+    pub requests_fee: RecordHandler<[u8; 16], u64>,
+}
+
+// layout of the accounts written before the layout was tagged
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Default, Debug, Clone)]
+pub struct LegacyLUPortContract {
+    pub nebula_address: Pubkey,
+    pub token_address: Pubkey,
+    pub token_mint: Pubkey,
+    pub initializer_pubkey: Pubkey,
+    pub oracles: Vec<Pubkey>,
+
+    pub swap_status: RecordHandler<[u8; 16], RequestStatus>,
+    pub requests: RecordHandler<[u8; 16], WrapRequest>,
+
+    pub is_state_initialized: bool,
+
+    pub requests_queue: RequestsQueue<[u8; 16]>,
+}
+
+// legacy accounts keep the defaults of the fields added since, e.g. `decimals_configured` is unset
+impl From<LegacyLUPortContract> for LUPortContract {
+    fn from(legacy: LegacyLUPortContract) -> Self {
+        LUPortContract {
+            nebula_address: legacy.nebula_address,
+            token_address: legacy.token_address,
+            token_mint: legacy.token_mint,
+            initializer_pubkey: legacy.initializer_pubkey,
+            oracles: legacy.oracles,
+            swap_status: legacy.swap_status,
+            requests: legacy.requests,
+            is_state_initialized: legacy.is_state_initialized,
+            requests_queue: legacy.requests_queue,
+            ..LUPortContract::default()
+        }
+    }
 }
 
 impl RequestCountConstrained for LUPortContract {
@@ -83,19 +145,11 @@ impl Pack for LUPortContract {
     const LEN: usize = 20000;
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let mut mut_src: &[u8] = src;
-        Self::deserialize(&mut mut_src).map_err(|err| {
-            msg!(
-                "Error: failed to deserialize LUPortContract instruction: {}",
-                err
-            );
-            ProgramError::InvalidInstructionData
-        })
+        PortLayout::unpack(src, |legacy: LegacyLUPortContract| legacy.into())
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let data = self.try_to_vec().unwrap();
-        dst[..data.len()].copy_from_slice(&data);
+        PortLayout::pack_into_slice(self, dst)
     }
 }
 
@@ -103,6 +157,28 @@ impl Pack for LUPortContract {
 pub type PortOperation<'a> = GenericPortOperation<'a, ForeignAddress>;
 
 impl LUPortContract {
+    // ports initialized before the decimals were stored use the mint decimals on both chains
+    pub fn port_decimals(&self, token_mint_info: &Mint) -> Result<PortDecimals, PortError> {
+        let decimals = match self.decimals_configured {
+            true => PortDecimals {
+                local: self.decimals,
+                foreign: self.foreign_decimals,
+            },
+            false => PortDecimals::unscaled(token_mint_info.decimals),
+        };
+
+        if token_mint_info.decimals != decimals.local {
+            return Err(PortError::InvalidTokenMint);
+        }
+        Ok(decimals)
+    }
+
+    pub fn validate_lock_vault(&self, lock_vault: &Pubkey) -> Result<(), PortError> {
+        if self.lock_vault == Pubkey::default() || *lock_vault != self.lock_vault {
+            return Err(PortError::InvalidLockVault);
+        }
+        Ok(())
+    }
 
     fn unprocessed_burn_requests(&self) -> usize {
         self.requests.len()
@@ -116,38 +192,17 @@ impl LUPortContract {
     }
 
     pub fn unpack_byte_array(byte_data: &Vec<u8>) -> Result<PortOperation, ProgramError> {
-        if byte_data.len() < 57 {
-            return Err(PortError::ByteArrayUnpackFailed.into());
-        }
-
-        let mut pos = 0;
-        let action = byte_data[pos];
-        pos += 1;
-
-        let swap_id = array_ref![byte_data, pos, 16];
-        pos += 16;
-        
-        let raw_amount = array_ref![byte_data, pos, 8];
-        pos += 8;
-
-        let receiver = array_ref![byte_data, pos, 32];
-
-        return Ok(PortOperation {
-            action,
-            swap_id,
-            amount: raw_amount,
-            receiver
-        });
+        Ok(PortOperation::unpack(byte_data)?)
     }
 
     pub fn attach_data<'a>(&mut self, byte_data: &'a Vec<u8>, input_pubkey: &'a Pubkey, input_amount: &'a mut u64, token_mint_info: &Mint) -> Result<String, ProgramError> {
-        let action = &[byte_data[0]];
+        let port_operation = Self::unpack_byte_array(byte_data)?;
+        let action = &[port_operation.action];
 
-        let command_char = std::str::from_utf8(action).unwrap();
+        let command_char = std::str::from_utf8(action).map_err(|_| PortError::InvalidDataOnAttach)?;
 
         match command_char {
             PortOperationIdentifier::UNLOCK => {
-                let port_operation = Self::unpack_byte_array(byte_data)?;
                 let swap_status = self.swap_status.get(port_operation.swap_id);
 
                 if swap_status.is_some() {
@@ -161,31 +216,200 @@ impl LUPortContract {
                     return Err(PortError::ErrorOnReceiverUnpack.into());
                 }
 
-                *input_amount = port_operation.amount_to_u64(token_mint_info.decimals);
+                *input_amount = port_operation.amount_to_u64(&self.port_decimals(token_mint_info)?)?;
 
                 self.swap_status.insert(*port_operation.swap_id, RequestStatus::Success);
             },
+            PortOperationIdentifier::CONFIRM => {
+                let decimals = self.port_decimals(token_mint_info)?;
+                self.drop_processed_request(byte_data, &decimals)?;
+            },
             _ => return Err(PortError::InvalidDataOnAttach.into())
         }
         
         Ok(String::from(command_char))
     }
 
-    pub fn create_transfer_wrap_request(&mut self, record_id: &[u8; 16], amount: u64, sender_data_account: &Pubkey, receiver: &ForeignAddress) -> Result<(), PortError>  {
+    // clears a lock request once the destination chain has processed it
+    pub fn drop_processed_request(&mut self, byte_array: &Vec<u8>, decimals: &PortDecimals) -> Result<(), ProgramError> {
+        let port_operation = Self::unpack_byte_array(byte_array)?;
+
+        self.port_requests().confirm(&port_operation, decimals)?;
+
+        Ok(())
+    }
+
+    pub fn create_transfer_wrap_request(&mut self, record_id: &[u8; 16], amount: u64, fee: u64, sender_data_account: &Pubkey, receiver: &ForeignAddress, creation_slot: Slot) -> Result<(), PortError>  {
         self.validate_requests_count()?;
 
-        if self.requests.contains_key(record_id) {
-            return Err(PortError::RequestIDIsAlreadyBeingProcessed.into());
+        self.port_requests().create(record_id, amount, fee, sender_data_account, receiver, creation_slot)
+    }
+
+    pub fn port_requests(&mut self) -> PortRequests<'_> {
+        PortRequests {
+            requests: &mut self.requests,
+            swap_status: &mut self.swap_status,
+            requests_creation_slot: &mut self.requests_creation_slot,
+            requests_queue: &mut self.requests_queue,
+            requests_fee: &mut self.requests_fee,
+            fees: &mut self.fees,
+            request_timeout: self.request_timeout,
         }
+    }
+}
 
-        self.requests.insert(*record_id, WrapRequest {
-            destination_address: *receiver,
-            origin_address: *sender_data_account,
-            amount
-        });
-        self.swap_status.insert(*record_id, RequestStatus::New);
-        self.requests_queue.push(*record_id);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gravity_misc::model::u256_from_u64;
+    use gravity_misc::ports::state::PortAmount;
+
+    fn confirm_data(request_id: &[u8; 16], amount: u64, receiver: &ForeignAddress) -> Vec<u8> {
+        PortOperation {
+            action: PortOperationIdentifier::CONFIRM.as_bytes()[0],
+            swap_id: request_id,
+            amount: PortAmount::BaseUnits(u256_from_u64(amount)),
+            receiver,
+        }
+        .pack()
+        .unwrap()
+    }
 
-        Ok(())
+    #[test]
+    fn test_port_decimals() {
+        let integral_mint = Mint { decimals: 0, ..Mint::default() };
+        let mut luport = LUPortContract::default();
+
+        // not configured: the mint decimals are used on both chains
+        assert_eq!(luport.port_decimals(&integral_mint).unwrap(), PortDecimals::unscaled(0));
+
+        luport.decimals_configured = true;
+        luport.foreign_decimals = 6;
+        assert_eq!(
+            luport.port_decimals(&integral_mint).unwrap(),
+            PortDecimals { local: 0, foreign: 6 }
+        );
+
+        // a configured port without decimals on both chains is not taken for a legacy one
+        luport.foreign_decimals = 0;
+        assert_eq!(luport.port_decimals(&integral_mint).unwrap(), PortDecimals { local: 0, foreign: 0 });
+        assert!(matches!(
+            luport.port_decimals(&Mint { decimals: 9, ..Mint::default() }),
+            Err(PortError::InvalidTokenMint)
+        ));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_drop_processed_request() {
+        let decimals = PortDecimals { local: 9, foreign: 9 };
+        let mut luport = LUPortContract::default();
+        let (request_id, receiver) = ([1; 16], [2; 32]);
+
+        luport
+            .create_transfer_wrap_request(&request_id, 500, 0, &Pubkey::new_unique(), &receiver, 10)
+            .unwrap();
+
+        assert_eq!(
+            luport.drop_processed_request(&confirm_data(&request_id, 500, &[3; 32]), &decimals).unwrap_err(),
+            PortError::RequestReceiverMismatch.into()
+        );
+        assert_eq!(
+            luport.drop_processed_request(&confirm_data(&request_id, 499, &receiver), &decimals).unwrap_err(),
+            PortError::RequestAmountMismatch.into()
+        );
+        assert_eq!(
+            luport.drop_processed_request(&confirm_data(&[9; 16], 500, &receiver), &decimals).unwrap_err(),
+            PortError::RequestIDForConfirmationIsInvalid.into()
+        );
+
+        luport
+            .drop_processed_request(&confirm_data(&request_id, 500, &receiver), &decimals)
+            .unwrap();
+        assert_eq!(luport.requests.len(), 0);
+        assert!(luport.swap_status.get(&request_id).is_none());
+        assert!(luport.requests_queue.is_empty());
+
+        assert_eq!(
+            luport.drop_processed_request(&confirm_data(&request_id, 500, &receiver), &decimals).unwrap_err(),
+            PortError::RequestIDForConfirmationIsInvalid.into()
+        );
+    }
+
+    #[test]
+    fn test_reclaim_request() {
+        let mut luport = LUPortContract::default();
+        let (request_id, origin) = ([1; 16], Pubkey::new_unique());
+
+        luport
+            .create_transfer_wrap_request(&request_id, 500, 0, &origin, &[2; 32], 100)
+            .unwrap();
+
+        // the timeout is disabled by default
+        assert!(matches!(luport.port_requests().reclaim(&request_id, &origin, 1_000_000), Err(PortError::RequestTimeoutNotReached)));
+
+        luport.request_timeout = 50;
+        assert!(matches!(luport.port_requests().reclaim(&request_id, &origin, 149), Err(PortError::RequestTimeoutNotReached)));
+
+        // the refund goes back to the request origin only
+        assert!(matches!(
+            luport.port_requests().reclaim(&request_id, &Pubkey::new_unique(), 150),
+            Err(PortError::RequestReceiverMismatch)
+        ));
+        assert_eq!(luport.port_requests().reclaim(&request_id, &origin, 150).unwrap(), (500, 0));
+        assert_eq!(luport.requests.len(), 0);
+        assert!(luport.requests_creation_slot.get(&request_id).is_none());
+
+        // rejected requests are reclaimed right away and can't be confirmed anymore
+        luport
+            .create_transfer_wrap_request(&request_id, 500, 0, &origin, &[2; 32], 200)
+            .unwrap();
+        luport.port_requests().reject(&request_id).unwrap();
+        assert!(matches!(luport.port_requests().reject(&request_id), Err(PortError::InvalidRequestStatusTransition)));
+        assert_eq!(
+            luport
+                .drop_processed_request(&confirm_data(&request_id, 500, &[2; 32]), &PortDecimals { local: 9, foreign: 9 })
+                .unwrap_err(),
+            PortError::InvalidRequestStatusTransition.into()
+        );
+        assert!(luport.port_requests().reclaim(&request_id, &origin, 200).is_ok());
+        assert!(matches!(luport.port_requests().reject(&request_id), Err(PortError::UnknownRequest)));
+    }
+
+    #[test]
+    fn test_unpack_legacy_layout() {
+        let mut legacy = LegacyLUPortContract {
+            nebula_address: Pubkey::new_unique(),
+            token_mint: Pubkey::new_unique(),
+            oracles: vec![Pubkey::new_unique()],
+            is_state_initialized: true,
+            requests_queue: vec![[1; 16]],
+            ..LegacyLUPortContract::default()
+        };
+        legacy.swap_status.insert([1; 16], RequestStatus::New);
+
+        // the account used to hold a longer queue, its leftovers follow the legacy fields
+        let legacy_data = legacy.try_to_vec().unwrap();
+        let mut data = vec![0xff; LUPortContract::LEN];
+        data[..legacy_data.len()].copy_from_slice(&legacy_data);
+
+        let luport = LUPortContract::unpack(&data).unwrap();
+        assert_eq!(luport.nebula_address, legacy.nebula_address);
+        assert_eq!(luport.oracles, legacy.oracles);
+        assert_eq!(luport.swap_status, legacy.swap_status);
+        assert_eq!(luport.requests_queue, legacy.requests_queue);
+        assert!(!luport.decimals_configured);
+        assert_eq!(luport.request_timeout, 0);
+        assert_eq!(luport.fees, PortFees::default());
+        assert_eq!(luport.layout, PortLayout::CURRENT);
+
+        // the upgraded contract is tagged and nothing is left of the previous state
+        LUPortContract::pack(luport.clone(), &mut data).unwrap();
+        let packed_len = luport.try_to_vec().unwrap().len();
+        assert!(data[packed_len..].iter().all(|byte| *byte == 0));
+        assert_eq!(LUPortContract::unpack(&data).unwrap(), luport);
+
+        // the version byte follows the tag
+        data[legacy_data.len() + PortLayout::TAG.len()] = PortLayout::CURRENT.version + 1;
+        assert_eq!(LUPortContract::unpack(&data).unwrap_err(), ProgramError::InvalidAccountData);
+    }
+}
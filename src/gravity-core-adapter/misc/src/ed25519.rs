@@ -0,0 +1,163 @@
+use arrayref::array_ref;
+use std::str::FromStr;
+
+use solana_program::{
+    account_info::AccountInfo,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::instructions::load_instruction_at,
+};
+
+use crate::model::ValidationError;
+use crate::validation::extract_from_range;
+
+// requires a runtime with the Ed25519 native program enabled (solana 1.8 and later)
+pub fn ed25519_program_id() -> Pubkey {
+    Pubkey::from_str("Ed25519SigVerify111111111111111111111111111").unwrap()
+}
+
+pub const SIGNATURE_OFFSETS_START: usize = 2;
+pub const SIGNATURE_OFFSETS_SERIALIZED_SIZE: usize = 14;
+pub const PUBKEY_SERIALIZED_SIZE: usize = 32;
+
+// offsets pointing to u16::MAX refer to the Ed25519 instruction itself
+pub const CURRENT_INSTRUCTION_INDEX: u16 = u16::MAX;
+
+// Returns the public keys whose signatures over `message` are carried inside
+// the Ed25519 program instruction at `instruction_index`.
+// The runtime verifies the signatures before the transaction is executed,
+// so only the referenced public keys and messages have to be matched here.
+pub fn ed25519_instruction_signers(
+    instruction_index: u16,
+    data: &[u8],
+    message: &[u8],
+) -> Result<Vec<Pubkey>, ProgramError> {
+    let num_signatures = *data.get(0).ok_or(ValidationError::InvalidEd25519Instruction)? as usize;
+
+    let mut signers = vec![];
+
+    for i in 0..num_signatures {
+        let start = SIGNATURE_OFFSETS_START + i * SIGNATURE_OFFSETS_SERIALIZED_SIZE;
+        let offsets = data
+            .get(start..start + SIGNATURE_OFFSETS_SERIALIZED_SIZE)
+            .ok_or(ValidationError::InvalidEd25519Instruction)?;
+
+        let read_u16 = |pos: usize| u16::from_le_bytes(*array_ref![offsets, pos, 2]);
+
+        let public_key_offset = read_u16(4) as usize;
+        let public_key_instruction_index = read_u16(6);
+        let message_data_offset = read_u16(8) as usize;
+        let message_data_size = read_u16(10) as usize;
+        let message_instruction_index = read_u16(12);
+
+        // data living in other instructions can't be matched against the expected message
+        let is_inline = |index: u16| index == CURRENT_INSTRUCTION_INDEX || index == instruction_index;
+        if !is_inline(public_key_instruction_index) || !is_inline(message_instruction_index) {
+            continue;
+        }
+
+        let public_key = data
+            .get(public_key_offset..public_key_offset + PUBKEY_SERIALIZED_SIZE)
+            .ok_or(ValidationError::InvalidEd25519Instruction)?;
+        let signed_message = data
+            .get(message_data_offset..message_data_offset + message_data_size)
+            .ok_or(ValidationError::InvalidEd25519Instruction)?;
+
+        if signed_message == message {
+            signers.push(Pubkey::new(public_key));
+        }
+    }
+
+    Ok(signers)
+}
+
+// Collects signers of `message` from every Ed25519 program instruction of the current transaction
+pub fn verified_signers(
+    instructions_sysvar: &AccountInfo,
+    message: &[u8],
+) -> Result<Vec<Pubkey>, ProgramError> {
+    if !solana_program::sysvar::instructions::check_id(instructions_sysvar.key) {
+        return Err(ValidationError::InvalidInstructionsSysvar.into());
+    }
+
+    let data = instructions_sysvar.try_borrow_data()?;
+    let instructions_count = extract_from_range(&data, 0..2, |x: &[u8]| {
+        u16::from_le_bytes(*array_ref![x, 0, 2])
+    })?;
+
+    let ed25519_program_id = ed25519_program_id();
+    let mut signers = vec![];
+
+    for index in 0..instructions_count {
+        let instruction = load_instruction_at(index as usize, &data)
+            .map_err(|_| ValidationError::InvalidInstructionsSysvar)?;
+
+        if instruction.program_id != ed25519_program_id {
+            continue;
+        }
+
+        signers.extend(ed25519_instruction_signers(index, &instruction.data, message)?);
+    }
+
+    Ok(signers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // mirrors the layout produced by the Ed25519 program instruction builder
+    fn build_ed25519_instruction_data(entries: &[(Pubkey, Vec<u8>)]) -> Vec<u8> {
+        let header_len = SIGNATURE_OFFSETS_START + entries.len() * SIGNATURE_OFFSETS_SERIALIZED_SIZE;
+        let mut data = vec![entries.len() as u8, 0];
+        let mut payload = vec![];
+
+        for (public_key, message) in entries {
+            let public_key_offset = header_len + payload.len();
+            payload.extend_from_slice(&public_key.to_bytes());
+            let signature_offset = header_len + payload.len();
+            payload.extend_from_slice(&[0; 64]);
+            let message_data_offset = header_len + payload.len();
+            payload.extend_from_slice(message);
+
+            for value in &[
+                signature_offset as u16,
+                CURRENT_INSTRUCTION_INDEX,
+                public_key_offset as u16,
+                CURRENT_INSTRUCTION_INDEX,
+                message_data_offset as u16,
+                message.len() as u16,
+                CURRENT_INSTRUCTION_INDEX,
+            ] {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        data.extend(payload);
+        data
+    }
+
+    #[test]
+    fn test_ed25519_instruction_signers() {
+        let message = b"round transition".to_vec();
+        let (first, second, third) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        let data = build_ed25519_instruction_data(&[
+            (first, message.clone()),
+            (second, b"another payload".to_vec()),
+            (third, message.clone()),
+        ]);
+
+        let signers = ed25519_instruction_signers(0, &data, &message).unwrap();
+        assert_eq!(signers, vec![first, third]);
+    }
+
+    #[test]
+    fn test_ed25519_instruction_signers_truncated() {
+        let message = b"round transition".to_vec();
+        let data = build_ed25519_instruction_data(&[(Pubkey::new_unique(), message.clone())]);
+
+        assert!(ed25519_instruction_signers(0, &data[..data.len() - 1], &message).is_err());
+        assert!(ed25519_instruction_signers(0, &[], &message).is_err());
+    }
+}
@@ -4,4 +4,7 @@
 pub mod misc;
 pub mod model;
 pub mod validation;
-pub mod ports;
\ No newline at end of file
+pub mod ports;
+pub mod ed25519;
+#[cfg(feature = "test-support")]
+pub mod test_support;
@@ -7,6 +7,7 @@ use solana_program::{
     program_error::ProgramError,
 };
 
+use arrayref::array_ref;
 use borsh::{BorshDeserialize, BorshSerialize};
 
 use uuid::v1::{Context, Timestamp};
@@ -16,7 +17,13 @@ use uuid::Uuid;
 #[derive(Error, Debug, Copy, Clone)]
 pub enum ValidationError {
     #[error("Error during extraction")]
-    ExtractionError
+    ExtractionError,
+
+    #[error("Invalid instructions sysvar account")]
+    InvalidInstructionsSysvar,
+
+    #[error("Invalid Ed25519 instruction data")]
+    InvalidEd25519Instruction,
 }
 
 impl From<ValidationError> for ProgramError {
@@ -27,6 +34,7 @@ impl From<ValidationError> for ProgramError {
 
 pub type SubscriptionID = [u8; 16];
 pub type PulseID = u64;
+pub type FeedID = u32;
 
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
 pub enum DataType {
@@ -43,12 +51,74 @@ impl Default for DataType {
 
 
 impl DataType {
-    pub fn cast_from(i: u8) -> DataType {
+    pub const INT64_LEN: usize = 8;
+    pub const LENGTH_PREFIX_LEN: usize = 4;
+
+    pub fn cast_from(i: u8) -> Option<DataType> {
         match i {
-            0 => DataType::Int64,
-            1 => DataType::String,
-            2 => DataType::Bytes,
-            _ => panic!("invalid data type"),
+            0 => Some(DataType::Int64),
+            1 => Some(DataType::String),
+            2 => Some(DataType::Bytes),
+            _ => None,
+        }
+    }
+
+    // Int64 is 8 bytes little-endian, String and Bytes are prefixed with a u32 LE length
+    pub fn encoded_len(&self, input: &[u8]) -> Option<usize> {
+        match self {
+            DataType::Int64 => Some(Self::INT64_LEN),
+            DataType::String | DataType::Bytes => {
+                let prefix = input.get(0..Self::LENGTH_PREFIX_LEN)?;
+                let len = u32::from_le_bytes(*array_ref![prefix, 0, 4]) as usize;
+                Self::LENGTH_PREFIX_LEN.checked_add(len)
+            }
+        }
+    }
+
+    // returns the raw value, without the length prefix
+    pub fn decode_value(&self, input: &[u8]) -> Option<Vec<u8>> {
+        let encoded_len = self.encoded_len(input)?;
+        if input.len() != encoded_len {
+            return None;
+        }
+
+        let value = match self {
+            DataType::Int64 => input.to_vec(),
+            DataType::String | DataType::Bytes => input[Self::LENGTH_PREFIX_LEN..].to_vec(),
+        };
+
+        if self.is_valid_value(&value) {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    pub fn encode_value(&self, value: &[u8]) -> Vec<u8> {
+        match self {
+            DataType::Int64 => value.to_vec(),
+            DataType::String | DataType::Bytes => {
+                let mut buf = Vec::with_capacity(Self::LENGTH_PREFIX_LEN + value.len());
+                buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                buf.extend_from_slice(value);
+                buf
+            }
+        }
+    }
+
+    pub fn is_valid_value(&self, value: &[u8]) -> bool {
+        match self {
+            DataType::Int64 => value.len() == Self::INT64_LEN,
+            DataType::String => std::str::from_utf8(value).is_ok(),
+            DataType::Bytes => true,
+        }
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            DataType::Int64 => 0,
+            DataType::String => 1,
+            DataType::Bytes => 2,
         }
     }
 }
@@ -100,6 +170,20 @@ impl<K: Default + Clone, V: Default + Clone> RecordHandler<K, V> {
     pub fn is_full(&self) -> bool {
         self.cap() == self.len()
     }
+
+    // keeps the records matching `f` and returns the dropped ones
+    pub fn retain<F: FnMut(&K, &V) -> bool>(&mut self, mut f: F) -> Vec<(K, V)> {
+        let mut dropped = vec![];
+        let mut pos = 0;
+        while pos < self.k.len() {
+            if f(&self.k[pos], &self.v[pos]) {
+                pos += 1;
+            } else {
+                dropped.push((self.k.remove(pos), self.v.remove(pos)));
+            }
+        }
+        dropped
+    }
 }
 
 impl<K: PartialEq + Default + Clone, V: Default + Clone> AbstractRecordHandler<K, V> for RecordHandler<K, V> {
@@ -155,8 +239,72 @@ impl<K: PartialEq + Default + Clone, V: Default + Clone> AbstractRecordHandler<K
     }
 }
 
+// little-endian
 pub type U256 = [u8; 32];
 
+pub fn u256_from_u64(value: u64) -> U256 {
+    let mut result = [0; 32];
+    result[0..8].copy_from_slice(&value.to_le_bytes());
+    result
+}
+
+// None if the value does not fit into u64
+pub fn u256_to_u64(value: &U256) -> Option<u64> {
+    if value[8..].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    Some(u64::from_le_bytes(*array_ref![value, 0, 8]))
+}
+
+fn u256_limbs(value: &U256) -> [u64; 4] {
+    let mut limbs = [0; 4];
+    for (i, limb) in limbs.iter_mut().enumerate() {
+        *limb = u64::from_le_bytes(*array_ref![value, i * 8, 8]);
+    }
+    limbs
+}
+
+fn u256_from_limbs(limbs: &[u64; 4]) -> U256 {
+    let mut result = [0; 32];
+    for (i, limb) in limbs.iter().enumerate() {
+        result[i * 8..i * 8 + 8].copy_from_slice(&limb.to_le_bytes());
+    }
+    result
+}
+
+// None on overflow
+pub fn u256_checked_mul_u64(value: &U256, factor: u64) -> Option<U256> {
+    let mut limbs = u256_limbs(value);
+    let mut carry: u128 = 0;
+    for limb in limbs.iter_mut() {
+        let product = (*limb as u128) * (factor as u128) + carry;
+        *limb = product as u64;
+        carry = product >> 64;
+    }
+
+    if carry != 0 {
+        return None;
+    }
+    Some(u256_from_limbs(&limbs))
+}
+
+// returns the quotient and the remainder, None on division by zero
+pub fn u256_div_rem_u64(value: &U256, divisor: u64) -> Option<(U256, u64)> {
+    if divisor == 0 {
+        return None;
+    }
+
+    let mut limbs = u256_limbs(value);
+    let mut remainder: u128 = 0;
+    for limb in limbs.iter_mut().rev() {
+        let dividend = (remainder << 64) | *limb as u128;
+        *limb = (dividend / divisor as u128) as u64;
+        remainder = dividend % divisor as u128;
+    }
+
+    Some((u256_from_limbs(&limbs), remainder as u64))
+}
+
 
 pub fn new_uuid(node_id: &[u8]) -> Uuid {
     let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
@@ -45,6 +45,33 @@ pub enum PortError {
 
     #[error("Invalid token mint")]
     InvalidTokenMint,
+
+    #[error("Invalid amount")]
+    InvalidAmount,
+
+    #[error("Invalid request status transition")]
+    InvalidRequestStatusTransition,
+
+    #[error("No such request")]
+    UnknownRequest,
+
+    #[error("Request can't be reclaimed before the timeout")]
+    RequestTimeoutNotReached,
+
+    #[error("Invalid fee configuration")]
+    InvalidFee,
+
+    #[error("Invalid fee vault")]
+    InvalidFeeVault,
+
+    #[error("Unsupported amount encoding")]
+    UnsupportedAmountEncoding,
+
+    #[error("Invalid lock vault")]
+    InvalidLockVault,
+
+    #[error("Fee withdrawal exceeds the accrued fees")]
+    FeeWithdrawalExceedsAccrued,
 }
 
 impl From<PortError> for ProgramError {
@@ -1,13 +1,18 @@
 use solana_program::{
+    clock::{Slot, UnixTimestamp},
     program_error::ProgramError,
     pubkey::Pubkey,
     instruction::{AccountMeta, Instruction},
 };
 use solana_program::account_info::AccountInfo;
 
+use arrayref::array_ref;
+use borsh::{BorshDeserialize, BorshSerialize};
 
-use crate::ports::error::PortError::InvalidInstructionIndex as InvalidInstruction;
+use crate::ports::error::PortError::{self, InvalidInstructionIndex as InvalidInstruction};
+use crate::ports::state::ui_amount_to_amount_checked;
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum SubscriberInstruction {
     AttachValue {
         byte_data: Vec<u8>,
@@ -16,6 +21,79 @@ pub enum SubscriberInstruction {
 
 pub const ATTACH_VALUE_INSTRUCTION_INDEX: &u8 = &2;
 
+// leading byte of the port CreateTransferUnwrapRequest data carrying a u64 base units amount,
+// the legacy encoding had an f64 amount and no version byte
+pub const AMOUNT_ENCODING_VERSION: u8 = 2;
+
+// amount of a port CreateTransferUnwrapRequest
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RequestAmount {
+    // legacy encoding, token amount as f64
+    Ui(f64),
+    // local token base units
+    BaseUnits(u64),
+}
+
+impl RequestAmount {
+    // `versioned_len` is the length of the request data in the current encoding,
+    // returns the amount and the data following it
+    pub fn unpack(data: &[u8], versioned_len: usize) -> Result<(Self, &[u8]), PortError> {
+        match data.split_first() {
+            Some((&AMOUNT_ENCODING_VERSION, rest)) if data.len() == versioned_len => {
+                Ok((RequestAmount::BaseUnits(u64::from_le_bytes(*array_ref![rest, 0, 8])), &rest[8..]))
+            }
+            _ if data.len() + 1 == versioned_len => {
+                Ok((RequestAmount::Ui(f64::from_le_bytes(*array_ref![data, 0, 8])), &data[8..]))
+            }
+            _ => Err(PortError::UnsupportedAmountEncoding),
+        }
+    }
+
+    pub fn pack_into(&self, buf: &mut Vec<u8>) {
+        match self {
+            RequestAmount::Ui(ui_amount) => buf.extend_from_slice(&ui_amount.to_le_bytes()),
+            RequestAmount::BaseUnits(amount) => {
+                buf.push(AMOUNT_ENCODING_VERSION);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+        }
+    }
+
+    // the legacy amount has to be a whole number of base units, it is not rounded
+    pub fn to_base_units(&self, decimals: u8) -> Result<u64, PortError> {
+        match *self {
+            RequestAmount::Ui(ui_amount) => ui_amount_to_amount_checked(ui_amount, decimals)
+                .filter(|amount| *amount as f64 / 10f64.powi(decimals as i32) == ui_amount)
+                .ok_or(PortError::InvalidAmount),
+            RequestAmount::BaseUnits(amount) => Ok(amount),
+        }
+    }
+}
+
+// nebula appends the pulse slot and unix timestamp (both little-endian) to the attached value
+pub const PULSE_TIME_ALLOC: usize = 16;
+
+pub fn append_pulse_time(byte_data: &Vec<u8>, slot: Slot, unix_timestamp: UnixTimestamp) -> Vec<u8> {
+    let mut result = Vec::with_capacity(byte_data.len() + PULSE_TIME_ALLOC);
+    result.extend_from_slice(byte_data);
+    result.extend_from_slice(&slot.to_le_bytes());
+    result.extend_from_slice(&unix_timestamp.to_le_bytes());
+    result
+}
+
+// returns the slot and unix timestamp of the pulse the attached value belongs to
+pub fn pulse_time_from_attached(byte_data: &[u8], value_len: usize) -> Option<(Slot, UnixTimestamp)> {
+    if byte_data.len() < value_len + PULSE_TIME_ALLOC {
+        return None;
+    }
+
+    let pos = byte_data.len() - PULSE_TIME_ALLOC;
+    Some((
+        Slot::from_le_bytes(*array_ref![byte_data, pos, 8]),
+        UnixTimestamp::from_le_bytes(*array_ref![byte_data, pos + 8, 8]),
+    ))
+}
+
 impl SubscriberInstruction {
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
         let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
@@ -47,6 +125,50 @@ impl SubscriberInstruction {
     }
 }
 
+// account required by the subscriber on AttachValue, declared at subscribe time,
+// the default pubkey stands for an account supplied by the oracle on every delivery
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct SubscriberAccount {
+    pub pubkey: Pubkey,
+    pub is_writable: bool,
+}
+
+impl SubscriberAccount {
+    pub const LEN: usize = 32 + 1;
+
+    pub fn is_supplied_on_delivery(&self) -> bool {
+        self.pubkey == Pubkey::default()
+    }
+
+    pub fn matches(&self, account: &AccountMeta) -> bool {
+        (self.is_supplied_on_delivery() || self.pubkey == account.pubkey)
+            && self.is_writable == account.is_writable
+    }
+}
+
+/// Creates an `AttachValue` instruction for an arbitrary subscriber program.
+///
+/// Accounts expected by the subscriber:
+///   0. `[signer]` Oracle delivering the value
+///   1. ..1+N Accounts declared by the subscriber, starting with its data account
+pub fn attach_value_to_subscriber(
+    subscriber_program_id: &Pubkey,
+    oracle: &Pubkey,
+    byte_data: &Vec<u8>,
+    subscriber_accounts: &[AccountMeta],
+) -> Instruction {
+    let data = SubscriberInstruction::AttachValue { byte_data: byte_data.clone() }.pack();
+
+    let mut accounts = Vec::with_capacity(1 + subscriber_accounts.len());
+    accounts.push(AccountMeta::new_readonly(*oracle, true));
+    accounts.extend_from_slice(subscriber_accounts);
+
+    Instruction {
+        program_id: *subscriber_program_id,
+        accounts,
+        data,
+    }
+}
 
 pub fn attach_value<'a>(
     byte_data: &Vec<u8>,
@@ -90,4 +212,48 @@ pub fn attach_value<'a>(
         accounts,
         data,
     })
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack_round_trip() {
+        let instruction = SubscriberInstruction::AttachValue { byte_data: vec![1, 2, 3] };
+
+        let packed = instruction.pack();
+        assert_eq!(packed[0], *ATTACH_VALUE_INSTRUCTION_INDEX);
+        assert_eq!(SubscriberInstruction::unpack(&packed).unwrap(), instruction);
+    }
+
+    #[test]
+    fn test_request_amount_to_base_units() {
+        assert_eq!(RequestAmount::BaseUnits(7).to_base_units(8).unwrap(), 7);
+        assert_eq!(RequestAmount::Ui(0.29).to_base_units(8).unwrap(), 29_000_000);
+        assert_eq!(RequestAmount::Ui(1.5).to_base_units(0).ok(), None);
+        assert_eq!(RequestAmount::Ui(0.123456789).to_base_units(8).ok(), None);
+        assert_eq!(RequestAmount::Ui(-1.0).to_base_units(8).ok(), None);
+    }
+
+    #[test]
+    fn test_pulse_time_from_attached() {
+        let byte_data = append_pulse_time(&vec![7; 64], 120, 1_620_000_000);
+
+        assert_eq!(byte_data.len(), 64 + PULSE_TIME_ALLOC);
+        assert_eq!(&byte_data[0..64], &[7; 64][..]);
+        assert_eq!(pulse_time_from_attached(&byte_data, 64), Some((120, 1_620_000_000)));
+        assert_eq!(pulse_time_from_attached(&byte_data[0..64], 64), None);
+    }
+
+    #[test]
+    fn test_subscriber_account_matches() {
+        let declared = SubscriberAccount { pubkey: Pubkey::new_unique(), is_writable: true };
+        let supplied = SubscriberAccount { pubkey: Pubkey::default(), is_writable: false };
+
+        assert!(declared.matches(&AccountMeta::new(declared.pubkey, false)));
+        assert!(!declared.matches(&AccountMeta::new_readonly(declared.pubkey, false)));
+        assert!(!declared.matches(&AccountMeta::new(Pubkey::new_unique(), false)));
+        assert!(supplied.matches(&AccountMeta::new_readonly(Pubkey::new_unique(), false)));
+    }
+}
@@ -1,5 +1,10 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use arrayref::array_ref;
+use solana_program::{clock::Slot, msg, program_error::ProgramError, pubkey::Pubkey};
+use spl_token::state::Account;
+
+use crate::model::{u256_checked_mul_u64, u256_div_rem_u64, u256_from_u64, u256_to_u64, AbstractRecordHandler, RecordHandler, U256};
+use crate::ports::error::PortError;
 
 
 #[repr(C)]
@@ -27,6 +32,17 @@ impl RequestStatus {
             _ => return None
         })
     }
+
+    // a request is created as New, then it is either confirmed by the destination chain
+    // or rejected by the oracles; finished requests are dropped from the port
+    pub fn transition(&self, next: RequestStatus) -> Result<RequestStatus, PortError> {
+        match (self, next) {
+            (RequestStatus::None, RequestStatus::New)
+            | (RequestStatus::New, RequestStatus::Success)
+            | (RequestStatus::New, RequestStatus::Rejected) => Ok(next),
+            _ => Err(PortError::InvalidRequestStatusTransition),
+        }
+    }
 }
 
 pub trait PortQueue<T> {
@@ -69,6 +85,200 @@ pub trait RequestCountConstrained {
 
 pub type ForeignAddress = [u8; 32];
 
+// bridge fee of one direction: a flat amount plus basis points of the transferred amount
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Default, Copy)]
+pub struct PortFee {
+    pub flat: u64,
+    pub bps: u16,
+}
+
+impl PortFee {
+    pub const LEN: usize = 8 + 2;
+    pub const MAX_BPS: u16 = 10_000;
+
+    pub fn is_zero(&self) -> bool {
+        self.flat == 0 && self.bps == 0
+    }
+
+    pub fn validate(&self) -> Result<(), PortError> {
+        if self.bps > Self::MAX_BPS {
+            return Err(PortError::InvalidFee);
+        }
+        Ok(())
+    }
+
+    // returns the amount left after the fee and the fee, the bps part is rounded down;
+    // the fee has to leave a non-zero amount
+    pub fn split(&self, amount: u64) -> Result<(u64, u64), PortError> {
+        let bps_fee = (amount as u128) * (self.bps as u128) / (Self::MAX_BPS as u128);
+        let fee = (self.flat as u128) + bps_fee;
+
+        if fee >= amount as u128 {
+            return Err(PortError::InvalidAmount);
+        }
+
+        Ok((amount - fee as u64, fee as u64))
+    }
+}
+
+// Written right after the legacy port fields, ahead of the fields added since.
+// Legacy accounts hold leftovers of longer request queues there, so the version byte
+// is preceded by a tag that such leftovers can't be taken for.
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy)]
+pub struct PortLayout {
+    pub tag: [u8; 7],
+    pub version: u8,
+}
+
+impl Default for PortLayout {
+    fn default() -> Self {
+        PortLayout::CURRENT
+    }
+}
+
+impl PortLayout {
+    pub const TAG: [u8; 7] = *b"gtnport";
+    pub const CURRENT: PortLayout = PortLayout { tag: Self::TAG, version: 1 };
+
+    // Decodes the legacy fields with `L`, followed by the layout tag. Accounts with the current layout
+    // are decoded as a whole with `T`, untagged ones are upgraded from the legacy fields,
+    // the rest of their data is left unread.
+    pub fn unpack<L, T, F>(src: &[u8], upgrade: F) -> Result<T, ProgramError>
+    where
+        L: BorshDeserialize,
+        T: BorshDeserialize,
+        F: FnOnce(L) -> T,
+    {
+        let failed = |err: std::io::Error| {
+            msg!("Error: failed to deserialize port contract: {}", err);
+            ProgramError::InvalidInstructionData
+        };
+
+        let mut rest: &[u8] = src;
+        let legacy = L::deserialize(&mut rest).map_err(failed)?;
+
+        match PortLayout::deserialize(&mut rest) {
+            Ok(layout) if layout == Self::CURRENT => {
+                let mut src: &[u8] = src;
+                T::deserialize(&mut src).map_err(failed)
+            }
+            Ok(layout) if layout.tag == Self::TAG => {
+                msg!("Error: unsupported port layout version: {}", layout.version);
+                Err(ProgramError::InvalidAccountData)
+            }
+            _ => Ok(upgrade(legacy)),
+        }
+    }
+
+    // clears what is left of a longer previous state, so it is never read back as the new fields
+    pub fn pack_into_slice<T: BorshSerialize>(contract: &T, dst: &mut [u8]) {
+        let data = contract.try_to_vec().unwrap();
+        dst[..data.len()].copy_from_slice(&data);
+        dst[data.len()..].fill(0);
+    }
+}
+
+// bridge fees of a port: the outbound fee is paid into the vault on request creation and refunded if the request
+// is reclaimed, the inbound fee is kept back on mint/unlock and moved into the vault on the next withdrawal
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Default, Copy)]
+pub struct PortFees {
+    pub outbound: PortFee,
+    pub inbound: PortFee,
+    pub vault: Pubkey,
+    // fees paid into the vault and fees kept back, not withdrawn yet
+    pub accrued: u64,
+    pub unsettled: u64,
+    // outbound fees of the pending requests, held in the vault until the request is confirmed or reclaimed
+    pub pending: u64,
+}
+
+// port vaults have to hold the port token and be owned by the port PDA
+pub fn is_port_vault(vault: &Account, token_mint: &Pubkey, pda: &Pubkey) -> bool {
+    vault.mint == *token_mint && vault.owner == *pda
+}
+
+impl PortFees {
+    pub fn configure(&mut self, outbound: PortFee, inbound: PortFee, vault: &Pubkey) -> Result<(), PortError> {
+        outbound.validate()?;
+        inbound.validate()?;
+
+        if *vault == Pubkey::default() && !(outbound.is_zero() && inbound.is_zero()) {
+            return Err(PortError::InvalidFeeVault);
+        }
+
+        // the fees in the current vault have to be withdrawn or refunded before it is replaced
+        if *vault != self.vault && (self.accrued != 0 || self.pending != 0) {
+            return Err(PortError::InvalidFeeVault);
+        }
+
+        self.outbound = outbound;
+        self.inbound = inbound;
+        self.vault = *vault;
+
+        Ok(())
+    }
+
+    pub fn validate_vault_account(vault: &Account, token_mint: &Pubkey, pda: &Pubkey) -> Result<(), PortError> {
+        if !is_port_vault(vault, token_mint, pda) {
+            return Err(PortError::InvalidFeeVault);
+        }
+        Ok(())
+    }
+
+    pub fn validate_vault(&self, vault: &Pubkey) -> Result<(), PortError> {
+        if self.vault == Pubkey::default() || *vault != self.vault {
+            return Err(PortError::InvalidFeeVault);
+        }
+        Ok(())
+    }
+
+    // returns the amount left after the outbound fee and the fee to pay into the vault
+    pub fn collect_outbound(&mut self, amount: u64) -> Result<(u64, u64), PortError> {
+        let (amount, fee) = self.outbound.split(amount)?;
+        self.pending = self.pending.checked_add(fee).ok_or(PortError::InvalidAmount)?;
+        Ok((amount, fee))
+    }
+
+    // the outbound fee of a confirmed request becomes withdrawable
+    pub fn confirm_outbound(&mut self, fee: u64) -> Result<(), PortError> {
+        self.pending = self.pending.checked_sub(fee).ok_or(PortError::InvalidAmount)?;
+        self.accrued = self.accrued.checked_add(fee).ok_or(PortError::InvalidAmount)?;
+        Ok(())
+    }
+
+    // the outbound fee of a reclaimed request goes back to the request origin
+    pub fn refund_outbound(&mut self, fee: u64) -> Result<(), PortError> {
+        self.pending = self.pending.checked_sub(fee).ok_or(PortError::InvalidAmount)?;
+        Ok(())
+    }
+
+    // returns the amount left after the inbound fee, the fee stays with the port until it is settled
+    pub fn collect_inbound(&mut self, amount: u64) -> Result<u64, PortError> {
+        let (amount, fee) = self.inbound.split(amount)?;
+        self.unsettled = self.unsettled.checked_add(fee).ok_or(PortError::InvalidAmount)?;
+        Ok(amount)
+    }
+
+    // returns the kept back fees to move into the vault
+    pub fn settle(&mut self) -> Result<u64, PortError> {
+        let unsettled = self.unsettled;
+        self.accrued = self.accrued.checked_add(unsettled).ok_or(PortError::InvalidAmount)?;
+        self.unsettled = 0;
+        Ok(unsettled)
+    }
+
+    pub fn withdraw(&mut self, amount: u64) -> Result<(), PortError> {
+        if amount == 0 || amount > self.accrued {
+            return Err(PortError::FeeWithdrawalExceedsAccrued);
+        }
+        self.accrued -= amount;
+        Ok(())
+    }
+}
+
 #[repr(C)]
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Default, Copy)]
 pub struct GenericRequest<O, D> {
@@ -77,27 +287,288 @@ pub struct GenericRequest<O, D> {
     pub amount: u64
 }
 
+pub type PortRequest = GenericRequest<Pubkey, ForeignAddress>;
+
+// outbound requests bookkeeping shared by the ports, borrowed from the port contract;
+// the ports only burn/mint or transfer the tokens
+pub struct PortRequests<'a> {
+    pub requests: &'a mut RecordHandler<[u8; 16], PortRequest>,
+    pub swap_status: &'a mut RecordHandler<[u8; 16], RequestStatus>,
+    pub requests_creation_slot: &'a mut RecordHandler<[u8; 16], Slot>,
+    pub requests_queue: &'a mut RequestsQueue<[u8; 16]>,
+    pub requests_fee: &'a mut RecordHandler<[u8; 16], u64>,
+    pub fees: &'a mut PortFees,
+    pub request_timeout: Slot,
+}
+
+impl<'a> PortRequests<'a> {
+    // `fee` is the outbound fee paid for the request, already collected by `PortFees::collect_outbound`
+    pub fn create(&mut self, request_id: &[u8; 16], amount: u64, fee: u64, origin: &Pubkey, destination: &ForeignAddress, creation_slot: Slot) -> Result<(), PortError> {
+        if amount == 0 {
+            return Err(PortError::InvalidAmount);
+        }
+
+        if self.requests.contains_key(request_id) {
+            return Err(PortError::RequestIDIsAlreadyBeingProcessed);
+        }
+
+        let status = self.swap_status.get(request_id).copied().unwrap_or_default();
+        self.swap_status.insert(*request_id, status.transition(RequestStatus::New)?);
+        self.requests.insert(*request_id, PortRequest {
+            destination_address: *destination,
+            origin_address: *origin,
+            amount
+        });
+        self.requests_creation_slot.insert(*request_id, creation_slot);
+        self.requests_queue.push(*request_id);
+        if fee > 0 {
+            self.requests_fee.insert(*request_id, fee);
+        }
+
+        Ok(())
+    }
+
+    // clears a request once the destination chain has processed it
+    pub fn confirm(&mut self, port_operation: &GenericPortOperation<ForeignAddress>, decimals: &PortDecimals) -> Result<(), PortError> {
+        let request_id = port_operation.swap_id;
+
+        let request = *self
+            .requests
+            .get(request_id)
+            .ok_or(PortError::RequestIDForConfirmationIsInvalid)?;
+
+        if request.destination_address != *port_operation.receiver {
+            return Err(PortError::RequestReceiverMismatch);
+        }
+
+        // only pending requests can be confirmed
+        let swap_status = self.swap_status.get(request_id).copied().unwrap_or_default();
+        swap_status.transition(RequestStatus::Success)?;
+
+        if request.amount != port_operation.amount_to_u64(decimals)? {
+            return Err(PortError::RequestAmountMismatch);
+        }
+
+        self.fees.confirm_outbound(self.request_fee(request_id))?;
+        self.drop_request(request_id);
+
+        Ok(())
+    }
+
+    pub fn reject(&mut self, request_id: &[u8; 16]) -> Result<(), PortError> {
+        if !self.requests.contains_key(request_id) {
+            return Err(PortError::UnknownRequest);
+        }
+
+        let status = self.swap_status.get(request_id).copied().unwrap_or_default();
+        self.swap_status.insert(*request_id, status.transition(RequestStatus::Rejected)?);
+
+        Ok(())
+    }
+
+    // drops a rejected or a timed out pending request, returns the amount and the outbound fee to refund to its origin;
+    // requests created before the creation slot was recorded have to be rejected first
+    pub fn reclaim(&mut self, request_id: &[u8; 16], refund_account: &Pubkey, current_slot: Slot) -> Result<(u64, u64), PortError> {
+        let request = *self.requests.get(request_id).ok_or(PortError::UnknownRequest)?;
+
+        match self.swap_status.get(request_id).copied().unwrap_or_default() {
+            RequestStatus::Rejected => {}
+            RequestStatus::New => {
+                let creation_slot = *self
+                    .requests_creation_slot
+                    .get(request_id)
+                    .ok_or(PortError::RequestTimeoutNotReached)?;
+
+                if self.request_timeout == 0 || current_slot < creation_slot.saturating_add(self.request_timeout) {
+                    return Err(PortError::RequestTimeoutNotReached);
+                }
+            }
+            _ => return Err(PortError::InvalidRequestStatusTransition),
+        }
+
+        if *refund_account != request.origin_address {
+            return Err(PortError::RequestReceiverMismatch);
+        }
+
+        let fee = self.request_fee(request_id);
+        self.fees.refund_outbound(fee)?;
+        self.drop_request(request_id);
+
+        Ok((request.amount, fee))
+    }
+
+    // requests created before the fees were introduced paid none
+    fn request_fee(&self, request_id: &[u8; 16]) -> u64 {
+        self.requests_fee.get(request_id).copied().unwrap_or_default()
+    }
+
+    fn drop_request(&mut self, request_id: &[u8; 16]) {
+        self.requests.drop(request_id);
+        self.swap_status.drop(request_id);
+        self.requests_creation_slot.drop(request_id);
+        self.requests_fee.drop(request_id);
+        self.requests_queue.drop_selected(*request_id);
+    }
+}
+
+
+// decimals of the port token on the local and the foreign chain,
+// amounts are converted exactly: an amount with dust below the destination precision is rejected
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct PortDecimals {
+    pub local: u8,
+    pub foreign: u8,
+}
+
+impl PortDecimals {
+    // u64 holds 10^19 at most
+    const MAX_SCALE_STEP: u8 = 19;
+
+    // used by ports initialized before the decimals were stored, amounts are not scaled
+    pub fn unscaled(decimals: u8) -> Self {
+        PortDecimals { local: decimals, foreign: decimals }
+    }
+
+    fn scale_up(amount: &U256, mut digits: u8) -> Option<U256> {
+        let mut result = *amount;
+        while digits > 0 {
+            let step = digits.min(Self::MAX_SCALE_STEP);
+            result = u256_checked_mul_u64(&result, 10u64.pow(step as u32))?;
+            digits -= step;
+        }
+        Some(result)
+    }
+
+    fn scale_down_exact(amount: &U256, mut digits: u8) -> Option<U256> {
+        let mut result = *amount;
+        while digits > 0 {
+            let step = digits.min(Self::MAX_SCALE_STEP);
+            let (quotient, dust) = u256_div_rem_u64(&result, 10u64.pow(step as u32))?;
+            if dust != 0 {
+                return None;
+            }
+            result = quotient;
+            digits -= step;
+        }
+        Some(result)
+    }
+
+    // foreign chain base units to the local mint ones
+    pub fn to_local(&self, amount: &U256) -> Option<u64> {
+        let local_amount = if self.foreign >= self.local {
+            Self::scale_down_exact(amount, self.foreign - self.local)?
+        } else {
+            Self::scale_up(amount, self.local - self.foreign)?
+        };
+        u256_to_u64(&local_amount)
+    }
+
+    // local mint base units to the foreign chain ones
+    pub fn to_foreign(&self, amount: u64) -> Option<U256> {
+        let amount = u256_from_u64(amount);
+        if self.foreign >= self.local {
+            Self::scale_up(&amount, self.foreign - self.local)
+        } else {
+            Self::scale_down_exact(&amount, self.local - self.foreign)
+        }
+    }
+}
+
+// amount carried by the attached data
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PortAmount {
+    // legacy attach format, token amount as f64
+    Ui(f64),
+    // integer amount in the foreign chain base units
+    BaseUnits(U256),
+}
+
+impl PortAmount {
+    // the legacy amount is rounded to the closest local base unit, None when it can't be represented
+    pub fn to_local(&self, decimals: &PortDecimals) -> Option<u64> {
+        match self {
+            PortAmount::Ui(ui_amount) => ui_amount_to_amount_checked(*ui_amount, decimals.local),
+            PortAmount::BaseUnits(amount) => decimals.to_local(amount),
+        }
+    }
+}
+
+pub fn ui_amount_to_amount_checked(ui_amount: f64, decimals: u8) -> Option<u64> {
+    if !ui_amount.is_finite() || ui_amount < 0.0 {
+        return None;
+    }
+
+    let amount = (ui_amount * 10f64.powi(decimals as i32)).round();
+    // u64::MAX as f64 rounds up to 2^64
+    if amount >= u64::MAX as f64 {
+        return None;
+    }
+
+    Some(amount as u64)
+}
 
 pub struct GenericPortOperation<'a, R> {
     pub action: u8,
     pub swap_id: &'a [u8; 16],
-    pub amount: &'a [u8; 8],
+    pub amount: PortAmount,
     pub receiver: &'a R,
 }
 
 impl<'a, R> GenericPortOperation<'a, R> {
-    // pub fn decimals() -> u8 {
-    //     8
-    // }
+    pub fn amount_to_u64(&self, decimals: &PortDecimals) -> Result<u64, PortError> {
+        self.amount.to_local(decimals).ok_or(PortError::InvalidAmount)
+    }
+}
+
+impl<'a> GenericPortOperation<'a, ForeignAddress> {
+    // legacy: [action][swap id][f64 amount][receiver]
+    pub const LEGACY_LEN: usize = 1 + 16 + 8 + 32;
+    // v2: [version][action][swap id][u256 amount][receiver]
+    pub const LEN: usize = 1 + 1 + 16 + 32 + 32;
+    pub const VERSION: u8 = 2;
+
+    // the legacy format starts with the action char, so it can't be confused with the version byte,
+    // the trailing bytes (e.g. the pulse time appended by nebula) are ignored
+    pub fn unpack(byte_data: &'a [u8]) -> Result<Self, PortError> {
+        if byte_data.first() == Some(&Self::VERSION) {
+            if byte_data.len() < Self::LEN {
+                return Err(PortError::ByteArrayUnpackFailed);
+            }
+
+            return Ok(GenericPortOperation {
+                action: byte_data[1],
+                swap_id: array_ref![byte_data, 2, 16],
+                amount: PortAmount::BaseUnits(*array_ref![byte_data, 18, 32]),
+                receiver: array_ref![byte_data, 50, 32],
+            });
+        }
 
-    pub fn amount_to_f64(&self) -> f64 {
-        let raw_amount = array_ref![self.amount, 0, 8];
-        f64::from_le_bytes(*raw_amount)
+        if byte_data.len() < Self::LEGACY_LEN {
+            return Err(PortError::ByteArrayUnpackFailed);
+        }
+
+        Ok(GenericPortOperation {
+            action: byte_data[0],
+            swap_id: array_ref![byte_data, 1, 16],
+            amount: PortAmount::Ui(f64::from_le_bytes(*array_ref![byte_data, 17, 8])),
+            receiver: array_ref![byte_data, 25, 32],
+        })
     }
 
-    pub fn amount_to_u64(&self, decimals: u8) -> u64 {
-        // let decimals = Self::decimals();
-        spl_token::ui_amount_to_amount(self.amount_to_f64(), decimals)
+    // always packs the v2 format
+    pub fn pack(&self) -> Result<Vec<u8>, PortError> {
+        let amount = match self.amount {
+            PortAmount::BaseUnits(amount) => amount,
+            PortAmount::Ui(_) => return Err(PortError::InvalidAmount),
+        };
+
+        let mut buf = Vec::with_capacity(Self::LEN);
+        buf.push(Self::VERSION);
+        buf.push(self.action);
+        buf.extend_from_slice(self.swap_id);
+        buf.extend_from_slice(&amount);
+        buf.extend_from_slice(self.receiver);
+        Ok(buf)
     }
 }
 
@@ -114,4 +585,158 @@ impl<'a> PortOperationIdentifier {
 // impl PortOperationIdentifier {
 //     pub const MINT: *const str = "m";
 //     pub const UNLOCK: *const str = "u";
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNSCALED: PortDecimals = PortDecimals { local: 8, foreign: 8 };
+
+    #[test]
+    fn test_ui_amount_to_amount_checked() {
+        // 0.29 * 1e8 is 28999999.999999996 as f64
+        assert_eq!(ui_amount_to_amount_checked(0.29, 8), Some(29_000_000));
+        assert_eq!(ui_amount_to_amount_checked(0.0, 8), Some(0));
+        assert_eq!(ui_amount_to_amount_checked(-1.0, 8), None);
+        assert_eq!(ui_amount_to_amount_checked(f64::NAN, 8), None);
+        assert_eq!(ui_amount_to_amount_checked(1e12, 8), None);
+    }
+
+    #[test]
+    fn test_unpack_port_operation_versions() {
+        let receiver = [3; 32];
+
+        let mut legacy = vec![b'm'];
+        legacy.extend_from_slice(&[1; 16]);
+        legacy.extend_from_slice(&12.5f64.to_le_bytes());
+        legacy.extend_from_slice(&receiver);
+
+        let operation = GenericPortOperation::unpack(&legacy).unwrap();
+        assert_eq!(operation.action, b'm');
+        assert_eq!(operation.amount_to_u64(&UNSCALED).unwrap(), 1_250_000_000);
+        assert_eq!(*operation.receiver, receiver);
+
+        let packed = GenericPortOperation {
+            action: b'm',
+            swap_id: &[1; 16],
+            amount: PortAmount::BaseUnits(u256_from_u64(1_250_000_001)),
+            receiver: &receiver,
+        }
+        .pack()
+        .unwrap();
+        assert_eq!(packed.len(), GenericPortOperation::LEN);
+
+        let operation = GenericPortOperation::unpack(&packed).unwrap();
+        assert_eq!(operation.action, b'm');
+        assert_eq!(*operation.swap_id, [1; 16]);
+        assert_eq!(operation.amount_to_u64(&UNSCALED).unwrap(), 1_250_000_001);
+        assert_eq!(*operation.receiver, receiver);
+
+        assert!(GenericPortOperation::unpack(&packed[..GenericPortOperation::LEGACY_LEN]).is_err());
+
+        let mut too_big = u256_from_u64(1);
+        too_big[8] = 1;
+        let packed = GenericPortOperation {
+            action: b'm',
+            swap_id: &[1; 16],
+            amount: PortAmount::BaseUnits(too_big),
+            receiver: &receiver,
+        }
+        .pack()
+        .unwrap();
+        assert!(GenericPortOperation::unpack(&packed).unwrap().amount_to_u64(&UNSCALED).is_err());
+    }
+
+    #[test]
+    fn test_port_decimals_scaling() {
+        let evm = PortDecimals { local: 8, foreign: 18 };
+
+        let foreign_amount = evm.to_foreign(123_456_789).unwrap();
+        assert_eq!(
+            u256_div_rem_u64(&foreign_amount, 10u64.pow(10)).unwrap(),
+            (u256_from_u64(123_456_789), 0)
+        );
+        assert_eq!(evm.to_local(&foreign_amount), Some(123_456_789));
+
+        // dust below the local precision
+        let mut dusty = foreign_amount;
+        dusty[0] += 1;
+        assert_eq!(evm.to_local(&dusty), None);
+
+        let coarse = PortDecimals { local: 9, foreign: 6 };
+        assert_eq!(coarse.to_foreign(1_500_000_000), Some(u256_from_u64(1_500_000)));
+        assert_eq!(coarse.to_foreign(1_500_000_001), None);
+        assert_eq!(coarse.to_local(&u256_from_u64(1_500_000)), Some(1_500_000_000));
+        assert_eq!(coarse.to_local(&u256_from_u64(u64::MAX)), None);
+
+        assert_eq!(PortDecimals::unscaled(8), UNSCALED);
+
+        // a token without decimals on both chains is converted one to one
+        let integral = PortDecimals { local: 0, foreign: 0 };
+        assert_eq!(integral.to_foreign(7), Some(u256_from_u64(7)));
+        assert_eq!(integral.to_local(&u256_from_u64(7)), Some(7));
+    }
+
+    #[test]
+    fn test_port_fee_split() {
+        let fee = PortFee { flat: 100, bps: 30 };
+
+        assert_eq!(fee.split(1_000_000).unwrap(), (996_900, 3_100));
+        // 0.3% of 333 is rounded down to 0
+        assert_eq!(fee.split(333).unwrap(), (233, 100));
+        assert!(fee.split(100).is_err());
+        assert_eq!(PortFee::default().split(1).unwrap(), (1, 0));
+
+        assert!(PortFee { flat: 0, bps: PortFee::MAX_BPS }.validate().is_ok());
+        assert!(PortFee { flat: 0, bps: PortFee::MAX_BPS + 1 }.validate().is_err());
+    }
+
+    #[test]
+    fn test_port_fees_accrual() {
+        let (vault, other_vault) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut fees = PortFees::default();
+
+        assert!(matches!(
+            fees.configure(PortFee { flat: 10, bps: 0 }, PortFee::default(), &Pubkey::default()),
+            Err(PortError::InvalidFeeVault)
+        ));
+        fees.configure(PortFee { flat: 10, bps: 0 }, PortFee { flat: 0, bps: 100 }, &vault).unwrap();
+
+        assert_eq!(fees.collect_outbound(1_000).unwrap(), (990, 10));
+        assert_eq!(fees.collect_outbound(1_000).unwrap(), (990, 10));
+        assert_eq!(fees.collect_inbound(1_000).unwrap(), 990);
+        assert_eq!((fees.accrued, fees.unsettled, fees.pending), (0, 10, 20));
+
+        // outbound fees are withdrawable once their request is confirmed, reclaimed requests get them back
+        assert!(matches!(fees.withdraw(1), Err(PortError::FeeWithdrawalExceedsAccrued)));
+        fees.confirm_outbound(10).unwrap();
+        fees.refund_outbound(10).unwrap();
+        assert!(matches!(fees.refund_outbound(1), Err(PortError::InvalidAmount)));
+        assert_eq!((fees.accrued, fees.unsettled, fees.pending), (10, 10, 0));
+
+        // only the fees paid into the vault can be withdrawn
+        assert!(matches!(fees.withdraw(11), Err(PortError::FeeWithdrawalExceedsAccrued)));
+        assert_eq!(fees.settle().unwrap(), 10);
+        assert_eq!((fees.accrued, fees.unsettled), (20, 0));
+        assert!(matches!(fees.withdraw(21), Err(PortError::FeeWithdrawalExceedsAccrued)));
+        fees.withdraw(15).unwrap();
+
+        // the vault is replaced once it is drained
+        assert!(matches!(
+            fees.configure(PortFee::default(), PortFee::default(), &other_vault),
+            Err(PortError::InvalidFeeVault)
+        ));
+        fees.withdraw(5).unwrap();
+        assert!(matches!(fees.withdraw(0), Err(PortError::FeeWithdrawalExceedsAccrued)));
+        fees.configure(PortFee::default(), PortFee::default(), &other_vault).unwrap();
+        fees.validate_vault(&other_vault).unwrap();
+
+        let pda = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let vault_account = Account { mint, owner: pda, ..Account::default() };
+        PortFees::validate_vault_account(&vault_account, &mint, &pda).unwrap();
+        assert!(PortFees::validate_vault_account(&vault_account, &Pubkey::new_unique(), &pda).is_err());
+        assert!(PortFees::validate_vault_account(&vault_account, &mint, &Pubkey::new_unique()).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+// Fixtures shared by the program unit tests, enabled with the `test-support` feature
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    sysvar::{self, Sysvar},
+};
+use spl_token::{
+    instruction::TokenInstruction,
+    state::{Account, AccountState, Mint},
+};
+use std::{cell::RefCell, collections::HashMap, sync::Once};
+
+use crate::validation::PDAResolver;
+
+pub type ProcessFn = fn(&Pubkey, &[AccountInfo], &[u8]) -> ProgramResult;
+
+pub struct TestAccount {
+    pub key: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub is_signer: bool,
+}
+
+impl TestAccount {
+    pub fn new(key: Pubkey, data_len: usize, owner: Pubkey, is_signer: bool) -> Self {
+        Self::with_data(key, vec![0; data_len], owner, is_signer)
+    }
+
+    pub fn with_data(key: Pubkey, data: Vec<u8>, owner: Pubkey, is_signer: bool) -> Self {
+        TestAccount {
+            key,
+            lamports: 0,
+            data,
+            owner,
+            is_signer,
+        }
+    }
+
+    pub fn signer(key: Pubkey) -> Self {
+        Self::new(key, 0, Pubkey::default(), true)
+    }
+
+    pub fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            true,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+// runs a contract instruction laid out as initializer, data account, multisig and then the signers
+pub fn process_with_signers(
+    process: ProcessFn,
+    program_id: &Pubkey,
+    contract: &mut TestAccount,
+    multisig: &mut TestAccount,
+    signers: &[Pubkey],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let mut initializer = TestAccount::signer(Pubkey::new_unique());
+    let mut signers: Vec<TestAccount> = signers.iter().map(|k| TestAccount::signer(*k)).collect();
+
+    let mut accounts = vec![initializer.info(), contract.info(), multisig.info()];
+    accounts.extend(signers.iter_mut().map(TestAccount::info));
+
+    process(program_id, &accounts, instruction_data)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TokenCpi {
+    Transfer { from: Pubkey, to: Pubkey, amount: u64 },
+    MintTo { to: Pubkey, amount: u64 },
+    Burn { from: Pubkey, amount: u64 },
+}
+
+thread_local! {
+    static TOKEN_CPIS: RefCell<Vec<TokenCpi>> = const { RefCell::new(Vec::new()) };
+}
+
+// records the token program CPIs of the processor instead of executing them
+struct TokenCpiStubs;
+
+impl SyscallStubs for TokenCpiStubs {
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        _account_infos: &[AccountInfo],
+        _signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        let cpi = match TokenInstruction::unpack(&instruction.data)? {
+            TokenInstruction::Transfer { amount } => TokenCpi::Transfer { from: keys[0], to: keys[1], amount },
+            TokenInstruction::MintTo { amount } => TokenCpi::MintTo { to: keys[1], amount },
+            TokenInstruction::Burn { amount } => TokenCpi::Burn { from: keys[0], amount },
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+        TOKEN_CPIS.with(|cpis| cpis.borrow_mut().push(cpi));
+        Ok(())
+    }
+}
+
+pub fn take_token_cpis() -> Vec<TokenCpi> {
+    TOKEN_CPIS.with(|cpis| cpis.borrow_mut().drain(..).collect())
+}
+
+// a port data account and its mint, the port is initialized by the port tests
+pub struct TestPort {
+    pub process: ProcessFn,
+    pub program_id: Pubkey,
+    pub pda: Pubkey,
+    pub admin: Pubkey,
+    pub oracle: Pubkey,
+    pub port: Pubkey,
+    pub mint: Pubkey,
+    // accounts with data, the other instruction accounts are created empty
+    pub accounts: Vec<TestAccount>,
+}
+
+impl TestPort {
+    pub fn new(process: ProcessFn, contract_len: usize, decimals: u8) -> Self {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(TokenCpiStubs));
+        });
+
+        // the port PDA has no bump seed, so it only exists for some program ids
+        let program_id = std::iter::repeat_with(Pubkey::new_unique)
+            .find(|program_id| PDAResolver::Gravity.address(program_id).is_ok())
+            .unwrap();
+
+        let mut mint_data = vec![0; Mint::LEN];
+        Mint { decimals, is_initialized: true, ..Mint::default() }.pack_into_slice(&mut mint_data);
+
+        let (port, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        TestPort {
+            process,
+            program_id,
+            pda: PDAResolver::Gravity.address(&program_id).unwrap(),
+            admin: Pubkey::new_unique(),
+            oracle: Pubkey::new_unique(),
+            port,
+            mint,
+            accounts: vec![
+                TestAccount::new(port, contract_len, program_id, false),
+                TestAccount::with_data(mint, mint_data, spl_token::id(), false),
+            ],
+        }
+    }
+
+    pub fn add_token_account(&mut self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        let mut data = vec![0; Account::LEN];
+        Account { mint: *mint, owner: *owner, state: AccountState::Initialized, ..Account::default() }
+            .pack_into_slice(&mut data);
+
+        let key = Pubkey::new_unique();
+        self.accounts.push(TestAccount::with_data(key, data, spl_token::id(), false));
+        key
+    }
+
+    pub fn process(&mut self, ix: &Instruction) -> ProgramResult {
+        let mut others: Vec<TestAccount> = vec![];
+        for meta in ix.accounts.iter() {
+            if !self.accounts.iter().chain(others.iter()).any(|account| account.key == meta.pubkey) {
+                others.push(TestAccount::new(meta.pubkey, Clock::size_of(), Pubkey::default(), meta.is_signer));
+            }
+        }
+
+        let infos: HashMap<Pubkey, AccountInfo> = self
+            .accounts
+            .iter_mut()
+            .chain(others.iter_mut())
+            .map(|account| (account.key, account.info()))
+            .collect();
+        let mut accounts: Vec<AccountInfo> = ix.accounts.iter().map(|meta| infos[&meta.pubkey].clone()).collect();
+
+        if let Some(clock_info) = accounts.iter_mut().find(|info| *info.key == sysvar::clock::id()) {
+            Clock::default().to_account_info(clock_info).unwrap();
+        }
+
+        (self.process)(&self.program_id, &accounts, &ix.data)
+    }
+
+    pub fn unpack_contract<T: Pack + IsInitialized>(&self) -> T {
+        T::unpack(&self.accounts[0].data[0..T::LEN]).unwrap()
+    }
+}
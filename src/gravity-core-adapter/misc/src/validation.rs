@@ -114,6 +114,10 @@ impl PDAResolver {
             // PDAResolver::LUPort => br"luport"
         }
     }
+
+    pub fn address(&self, program_id: &Pubkey) -> Result<Pubkey, ProgramError> {
+        Ok(Pubkey::create_program_address(&[self.bump_seeds()], program_id)?)
+    }
 }
 
 pub fn validate_pubkey_match<E: std::error::Error>(
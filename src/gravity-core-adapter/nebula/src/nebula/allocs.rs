@@ -12,31 +12,59 @@ pub fn allocation_by_instruction_index(
             NebulaContractInstruction::DATA_TYPE_ALLOC_RANGE,
             NebulaContractInstruction::PUBKEY_ALLOC,
             NebulaContractInstruction::PUBKEY_ALLOC * oracles_bft.unwrap(),
+            NebulaContractInstruction::PUBKEY_ALLOC,
         ],
-        // UpdateOracles
-        1 => vec![
+        // UpdateOracles, UpdateOraclesWithConsuls
+        1 | 11 => vec![
             NebulaContractInstruction::BFT_ALLOC,
             NebulaContractInstruction::PUBKEY_ALLOC * oracles_bft.unwrap(),
             NebulaContractInstruction::PULSE_ID_ALLOC,
         ],
         // SendHashValue
-        2 => vec![32],
+        2 => vec![32, NebulaContractInstruction::FEED_ID_ALLOC],
         // SendValueToSubs
         3 => vec![
-            NebulaContractInstruction::DATA_HASH_ALLOC,
             NebulaContractInstruction::DATA_TYPE_ALLOC_RANGE,
+            oracles_bft.unwrap(),
             NebulaContractInstruction::PULSE_ID_ALLOC,
             NebulaContractInstruction::SUB_ID_ALLOC,
+            NebulaContractInstruction::FEED_ID_ALLOC,
         ],
         // Subscribe
         4 => vec![
             NebulaContractInstruction::PUBKEY_ALLOC, 
             1,
-            8,
+            NebulaContractInstruction::REWARD_ALLOC,
             NebulaContractInstruction::SUB_ID_ALLOC,
+            1,
+            NebulaContractInstruction::SUBSCRIBER_ACCOUNT_ALLOC * oracles_bft.unwrap(),
+            NebulaContractInstruction::FEED_ID_ALLOC,
         ],
         5 => vec![
             NebulaContractInstruction::SUB_ID_ALLOC,
+            NebulaContractInstruction::FEED_ID_ALLOC,
+        ],
+        // TopUpSubscription
+        6 => vec![
+            NebulaContractInstruction::SUB_ID_ALLOC,
+            NebulaContractInstruction::AMOUNT_ALLOC,
+            NebulaContractInstruction::FEED_ID_ALLOC,
+        ],
+        // ClaimReward
+        7 => vec![],
+        // ConfigureRewards
+        8 => vec![
+            NebulaContractInstruction::PUBKEY_ALLOC,
+            1,
+        ],
+        // ConfigurePulseRetention
+        9 => vec![1],
+        // SendHashValueWithSignatures
+        10 => vec![32, NebulaContractInstruction::FEED_ID_ALLOC],
+        // CreateFeed
+        12 => vec![
+            NebulaContractInstruction::FEED_ID_ALLOC,
+            NebulaContractInstruction::DATA_TYPE_ALLOC_RANGE,
         ],
         _ => return Err(NebulaError::InvalidInstructionIndex.into()),
     })
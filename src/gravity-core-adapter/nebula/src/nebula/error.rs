@@ -40,6 +40,93 @@ pub enum NebulaError {
 
     #[error("Pulse validation order mismatch")]
     PulseValidationOrderMismatch,
+
+    #[error("Only subscription sender can unsubscribe")]
+    UnsubscribeAccessDenied,
+
+    #[error("Reward amount overflow")]
+    RewardOverflow,
+
+    #[error("Subscription balance is not enough to pay the reward")]
+    InsufficientRewardBalance,
+
+    #[error("Invalid reward escrow account")]
+    InvalidRewardEscrow,
+
+    #[error("Reward mint can not be changed while rewards are escrowed")]
+    RewardsConfigurationLocked,
+
+    #[error("Invalid reward distribution")]
+    InvalidRewardDistribution,
+
+    #[error("No reward to claim")]
+    NoRewardToClaim,
+
+    #[error("Only nebula initializer can configure rewards")]
+    RewardsConfigurationAccessDenied,
+
+    #[error("Pulse has been already confirmed by the oracle")]
+    PulseAlreadyConfirmed,
+
+    #[error("Nebula data account is full")]
+    NebulaStorageIsFull,
+
+    #[error("Invalid pulse retention depth")]
+    InvalidPulseRetention,
+
+    #[error("Only nebula initializer can configure pulse retention")]
+    PulseRetentionAccessDenied,
+
+    #[error("Pulse is out of the retention depth")]
+    PulseExpired,
+
+    #[error("Invalid pulse account")]
+    InvalidPulseAccount,
+
+    #[error("Invalid subscription account")]
+    InvalidSubscriptionAccount,
+
+    #[error("Pulse has been sent before the subscription")]
+    PulseIsOlderThanSubscription,
+
+    #[error("Subscriber accounts do not match the subscription")]
+    InvalidSubscriberAccounts,
+
+    #[error("Subscriber data account is not owned by the subscriber program")]
+    InvalidSubscriberDataAccount,
+
+    #[error("Invalid data type")]
+    InvalidDataType,
+
+    #[error("Data value does not match its data type")]
+    InvalidDataValue,
+
+    #[error("Data type does not match the nebula data type")]
+    DataTypeMismatch,
+
+    #[error("Not enough oracle signatures")]
+    NotEnoughOracleSignatures,
+
+    #[error("Invalid gravity contract account")]
+    InvalidGravityContract,
+
+    #[error("Not enough consuls signatures")]
+    NotEnoughConsulsSignatures,
+
+    #[error("Invalid feed account")]
+    InvalidFeedAccount,
+
+    #[error("Only the nebula initializer can create feeds")]
+    FeedCreationAccessDenied,
+
+    #[error("Subscription has been cancelled")]
+    SubscriptionCancelled,
+
+    #[error("Too many oracles")]
+    TooManyOracles,
+
+    #[error("Pulse account can't hold more confirmations")]
+    PulseConfirmationsFull,
 }
 
 impl From<NebulaError> for ProgramError {
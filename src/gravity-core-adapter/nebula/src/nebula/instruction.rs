@@ -1,6 +1,8 @@
 use solana_program::{
+    instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     pubkey::Pubkey,
+    system_program, sysvar,
 };
 
 
@@ -9,18 +11,23 @@ use solana_program::{
 
 use arrayref::{array_ref};
 
-use gravity_misc::model::{DataType, PulseID, SubscriptionID};
+use gravity_misc::model::{DataType, FeedID, PulseID, SubscriptionID, U256};
+use gravity_misc::ports::instruction::SubscriberAccount;
 use gravity_misc::validation::{build_range_from_alloc, extract_from_range, retrieve_oracles};
 
 use crate::nebula::allocs::allocation_by_instruction_index;
+use crate::nebula::error::NebulaError;
+use crate::nebula::state::{NebulaContract, RewardDistribution};
 use solana_gravity_contract::gravity::error::GravityError::InvalidInstruction;
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum NebulaContractInstruction {
     InitContract {
         nebula_data_type: DataType,
         gravity_contract_program_id: Pubkey,
         initial_oracles: Vec<Pubkey>,
         oracles_bft: u8,
+        gravity_program_id: Pubkey,
     },
     UpdateOracles {
         new_oracles: Vec<Pubkey>,
@@ -28,49 +35,52 @@ pub enum NebulaContractInstruction {
     },
     SendHashValue {
         data_hash: Vec<u8>,
+        feed_id: FeedID,
     },
     SendValueToSubs {
         data_value: Vec<u8>,
         data_type: DataType,
         pulse_id: PulseID,
         subscription_id: SubscriptionID,
+        feed_id: FeedID,
     },
     Subscribe {
         address: Pubkey,
         min_confirmations: u8,
-        reward: u64,
+        reward: U256,
         subscription_id: SubscriptionID,
+        subscriber_accounts: Vec<SubscriberAccount>,
+        feed_id: FeedID,
     },
     Unsubscribe {
         subscription_id: SubscriptionID,
+        feed_id: FeedID,
+    },
+    TopUpSubscription {
+        subscription_id: SubscriptionID,
+        amount: u64,
+        feed_id: FeedID,
+    },
+    ClaimReward,
+    ConfigureRewards {
+        reward_mint: Pubkey,
+        reward_distribution: RewardDistribution,
+    },
+    ConfigurePulseRetention {
+        pulse_retention: u8,
+    },
+    SendHashValueWithSignatures {
+        data_hash: Vec<u8>,
+        feed_id: FeedID,
+    },
+    UpdateOraclesWithConsuls {
+        new_oracles: Vec<Pubkey>,
+        new_round: PulseID,
+    },
+    CreateFeed {
+        feed_id: FeedID,
+        data_type: DataType,
     },
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_build_from_range_alloc() {
-        let allocs = vec![1, 1, 32 * 3, 8];
-
-        let ranges = build_range_from_alloc(&allocs);
-
-        assert_eq!(ranges.len(), allocs.len());
-        assert_eq!(ranges[0], 0..1);
-        assert_eq!(ranges[1], 1..1 + 1);
-        assert_eq!(ranges[2], 1 + 1..1 + 1 + (32 * 3));
-        assert_eq!(ranges[3], 1 + 1 + (32 * 3)..1 + 1 + (32 * 3) + 8);
-    }
-
-    #[test]
-    fn test_bft_extraction() {
-        let input: [u8; 1] = u8::to_le_bytes(3);
-
-        let _extracted = extract_from_range(&input, 0..1, |x: &[u8]| {
-            u8::from_le_bytes(*array_ref![x, 0, 1])
-        });
-    }
 }
 
 impl NebulaContractInstruction {
@@ -80,6 +90,10 @@ impl NebulaContractInstruction {
     pub const PULSE_ID_ALLOC: usize = 8;
     pub const SUB_ID_ALLOC: usize = 16;
     pub const DATA_HASH_ALLOC: usize = 64;
+    pub const REWARD_ALLOC: usize = 32;
+    pub const AMOUNT_ALLOC: usize = 8;
+    pub const SUBSCRIBER_ACCOUNT_ALLOC: usize = SubscriberAccount::LEN;
+    pub const FEED_ID_ALLOC: usize = 4;
 
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
         let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
@@ -97,21 +111,25 @@ impl NebulaContractInstruction {
                     rest,
                     ranges[1].clone(),
                     |x: &[u8]| u8::from_le_bytes(*array_ref![x, 0, 1]),
-                )?);
+                )?)
+                .ok_or(NebulaError::InvalidDataType)?;
 
                 let gravity_contract_program_id =
                     extract_from_range(rest, ranges[2].clone(), |x| Pubkey::new(x))?;
                 let initial_oracles = retrieve_oracles(rest, ranges[3].clone(), oracles_bft)?;
+                let gravity_program_id =
+                    extract_from_range(rest, ranges[4].clone(), |x| Pubkey::new(x))?;
 
                 Self::InitContract {
                     nebula_data_type,
                     gravity_contract_program_id,
                     initial_oracles,
                     oracles_bft,
+                    gravity_program_id,
                 }
             }
-            // UpdateOracles
-            1 => {
+            // UpdateOracles, UpdateOraclesWithConsuls
+            1 | 11 => {
                 let bft = extract_from_range(rest, 0..1, |x: &[u8]| {
                     u8::from_le_bytes(*array_ref![x, 0, 1])
                 })?;
@@ -123,9 +141,15 @@ impl NebulaContractInstruction {
                     PulseID::from_le_bytes(*array_ref![x, 0, 8])
                 })?;
 
-                Self::UpdateOracles {
-                    new_round,
-                    new_oracles,
+                match tag {
+                    1 => Self::UpdateOracles {
+                        new_round,
+                        new_oracles,
+                    },
+                    _ => Self::UpdateOraclesWithConsuls {
+                        new_round,
+                        new_oracles,
+                    },
                 }
             }
             // SendHashValue
@@ -137,52 +161,68 @@ impl NebulaContractInstruction {
                 let data_hash =
                     extract_from_range(rest, ranges[0].clone(), |x: &[u8]| *array_ref![x, 0, 32])?;
                 let data_hash = data_hash.to_vec();
+                let feed_id = extract_from_range(rest, ranges[1].clone(), |x: &[u8]| {
+                    FeedID::from_le_bytes(*array_ref![x, 0, 4])
+                })?;
 
-                Self::SendHashValue { data_hash }
+                Self::SendHashValue { data_hash, feed_id }
             }
             // SendValueToSubs
             3 => {
-                let allocs = allocation_by_instruction_index((*tag).into(), None)?;
+                let data_type = extract_from_range(rest, 0..Self::DATA_TYPE_ALLOC_RANGE, |x: &[u8]| {
+                    u8::from_le_bytes(*array_ref![x, 0, 1])
+                })?;
+                let data_type = DataType::cast_from(data_type).ok_or(NebulaError::InvalidDataType)?;
+
+                // the value length depends on the data type
+                let data_value_len = data_type
+                    .encoded_len(&rest[Self::DATA_TYPE_ALLOC_RANGE..])
+                    .ok_or(NebulaError::InvalidDataValue)?;
+                let allocs = allocation_by_instruction_index((*tag).into(), Some(data_value_len))?;
                 let ranges = build_range_from_alloc(&allocs);
 
-                let (data_value, data_type, new_round, subscription_id) = (
-                    ranges[0].clone(),
+                let (data_value, new_round, subscription_id) = (
                     ranges[1].clone(),
                     ranges[2].clone(),
                     ranges[3].clone(),
                 );
 
-                let data_value =
-                    extract_from_range(rest, data_value, |x: &[u8]| *array_ref![x, 0, NebulaContractInstruction::DATA_HASH_ALLOC])?;
-                let data_value = data_value.to_vec();
-
-                let data_type =
-                    DataType::cast_from(extract_from_range(rest, data_type, |x: &[u8]| {
-                        u8::from_le_bytes(*array_ref![x, 0, 1])
-                    })?);
+                let data_value = extract_from_range(rest, data_value, |x: &[u8]| data_type.decode_value(x))?
+                    .ok_or(NebulaError::InvalidDataValue)?;
                 let new_round = extract_from_range(rest, new_round, |x: &[u8]| {
                     PulseID::from_le_bytes(*array_ref![x, 0, 8])
                 })?;
                 let subscription_id =
                     extract_from_range(rest, subscription_id, |x: &[u8]| *array_ref![x, 0, 16])?;
+                let feed_id = extract_from_range(rest, ranges[4].clone(), |x: &[u8]| {
+                    FeedID::from_le_bytes(*array_ref![x, 0, 4])
+                })?;
 
                 Self::SendValueToSubs {
                     data_value,
                     data_type,
                     pulse_id: new_round,
                     subscription_id,
+                    feed_id,
                 }
             }
             // Subscribe
             4 => {
-                let allocs = allocation_by_instruction_index((*tag).into(), None)?;
+                let accounts_count_offset = Self::PUBKEY_ALLOC + 1 + Self::REWARD_ALLOC + Self::SUB_ID_ALLOC;
+                let accounts_count = extract_from_range(
+                    rest,
+                    accounts_count_offset..accounts_count_offset + 1,
+                    |x: &[u8]| u8::from_le_bytes(*array_ref![x, 0, 1]),
+                )?;
+                let allocs = allocation_by_instruction_index((*tag).into(), Some(accounts_count as usize))?;
 
                 let built_range = build_range_from_alloc(&allocs);
-                let (address, min_confirmations, reward, subscription_id) = (
+                let (address, min_confirmations, reward, subscription_id, subscriber_accounts) = (
                     built_range[0].clone(),
                     built_range[1].clone(),
                     built_range[2].clone(),
                     built_range[3].clone(),
+                    built_range[5].clone(),
                 );
 
                 let address = extract_from_range(rest, address, |x: &[u8]| {
@@ -194,16 +234,27 @@ impl NebulaContractInstruction {
                         u8::from_le_bytes(*array_ref![x, 0, 1])
                     })?;
 
-                let reward = extract_from_range(rest, reward, |x: &[u8]| {
-                    u64::from_le_bytes(*array_ref![x, 0, 8])
-                })?;
+                let reward = extract_from_range(rest, reward, |x: &[u8]| *array_ref![x, 0, 32])?;
                 let subscription_id = extract_from_range(rest, subscription_id, |x: &[u8]| *array_ref![x, 0, 16])?;
+                let subscriber_accounts = extract_from_range(rest, subscriber_accounts, |x: &[u8]| {
+                    x.chunks(Self::SUBSCRIBER_ACCOUNT_ALLOC)
+                        .map(|chunk| SubscriberAccount {
+                            pubkey: Pubkey::new_from_array(*array_ref![chunk, 0, 32]),
+                            is_writable: chunk[32] != 0,
+                        })
+                        .collect::<Vec<SubscriberAccount>>()
+                })?;
+                let feed_id = extract_from_range(rest, built_range[6].clone(), |x: &[u8]| {
+                    FeedID::from_le_bytes(*array_ref![x, 0, 4])
+                })?;
 
                 Self::Subscribe {
                     address,
                     min_confirmations,
                     reward,
                     subscription_id,
+                    subscriber_accounts,
+                    feed_id,
                 }
             },
             5 => {
@@ -212,12 +263,1016 @@ impl NebulaContractInstruction {
 
                 let subscription_id = built_range[0].clone();
                 let subscription_id = extract_from_range(rest, subscription_id, |x: &[u8]| *array_ref![x, 0, 16])?;
+                let feed_id = extract_from_range(rest, built_range[1].clone(), |x: &[u8]| {
+                    FeedID::from_le_bytes(*array_ref![x, 0, 4])
+                })?;
 
                 Self::Unsubscribe {
                     subscription_id,
+                    feed_id,
+                }
+            }
+            // TopUpSubscription
+            6 => {
+                let allocs = allocation_by_instruction_index((*tag).into(), None)?;
+                let built_range = build_range_from_alloc(&allocs);
+
+                let subscription_id = extract_from_range(rest, built_range[0].clone(), |x: &[u8]| *array_ref![x, 0, 16])?;
+                let amount = extract_from_range(rest, built_range[1].clone(), |x: &[u8]| {
+                    u64::from_le_bytes(*array_ref![x, 0, 8])
+                })?;
+                let feed_id = extract_from_range(rest, built_range[2].clone(), |x: &[u8]| {
+                    FeedID::from_le_bytes(*array_ref![x, 0, 4])
+                })?;
+
+                Self::TopUpSubscription {
+                    subscription_id,
+                    amount,
+                    feed_id,
+                }
+            }
+            // ClaimReward
+            7 => Self::ClaimReward,
+            // ConfigureRewards
+            8 => {
+                let allocs = allocation_by_instruction_index((*tag).into(), None)?;
+                let built_range = build_range_from_alloc(&allocs);
+
+                let reward_mint = extract_from_range(rest, built_range[0].clone(), |x: &[u8]| {
+                    Pubkey::new_from_array(*array_ref![x, 0, 32])
+                })?;
+                let reward_distribution = RewardDistribution::cast_from(extract_from_range(
+                    rest,
+                    built_range[1].clone(),
+                    |x: &[u8]| u8::from_le_bytes(*array_ref![x, 0, 1]),
+                )?)?;
+
+                Self::ConfigureRewards {
+                    reward_mint,
+                    reward_distribution,
                 }
             }
+            // ConfigurePulseRetention
+            9 => {
+                let allocs = allocation_by_instruction_index((*tag).into(), None)?;
+                let built_range = build_range_from_alloc(&allocs);
+
+                let pulse_retention = extract_from_range(rest, built_range[0].clone(), |x: &[u8]| {
+                    u8::from_le_bytes(*array_ref![x, 0, 1])
+                })?;
+
+                Self::ConfigurePulseRetention { pulse_retention }
+            }
+            // SendHashValueWithSignatures
+            10 => {
+                let allocs = allocation_by_instruction_index((*tag).into(), None)?;
+                let ranges = build_range_from_alloc(&allocs);
+
+                let data_hash =
+                    extract_from_range(rest, ranges[0].clone(), |x: &[u8]| *array_ref![x, 0, 32])?;
+                let feed_id = extract_from_range(rest, ranges[1].clone(), |x: &[u8]| {
+                    FeedID::from_le_bytes(*array_ref![x, 0, 4])
+                })?;
+
+                Self::SendHashValueWithSignatures {
+                    data_hash: data_hash.to_vec(),
+                    feed_id,
+                }
+            }
+            // CreateFeed
+            12 => {
+                let allocs = allocation_by_instruction_index((*tag).into(), None)?;
+                let ranges = build_range_from_alloc(&allocs);
+
+                let feed_id = extract_from_range(rest, ranges[0].clone(), |x: &[u8]| {
+                    FeedID::from_le_bytes(*array_ref![x, 0, 4])
+                })?;
+                let data_type = DataType::cast_from(extract_from_range(rest, ranges[1].clone(), |x: &[u8]| {
+                    u8::from_le_bytes(*array_ref![x, 0, 1])
+                })?)
+                .ok_or(NebulaError::InvalidDataType)?;
+
+                Self::CreateFeed { feed_id, data_type }
+            }
             _ => return Err(InvalidInstruction.into()),
         })
     }
 }
+
+
+impl NebulaContractInstruction {
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        match self {
+            &Self::InitContract {
+                ref nebula_data_type,
+                ref gravity_contract_program_id,
+                ref initial_oracles,
+                oracles_bft,
+                ref gravity_program_id,
+            } => {
+                buf.push(0);
+                buf.push(oracles_bft);
+                buf.push(nebula_data_type.to_u8());
+                buf.extend_from_slice(&gravity_contract_program_id.to_bytes());
+                for oracle in initial_oracles {
+                    buf.extend_from_slice(&oracle.to_bytes());
+                }
+                buf.extend_from_slice(&gravity_program_id.to_bytes());
+            }
+            &Self::UpdateOracles {
+                ref new_oracles,
+                new_round,
+            } => {
+                buf.push(1);
+                buf.push(new_oracles.len() as u8);
+                for oracle in new_oracles {
+                    buf.extend_from_slice(&oracle.to_bytes());
+                }
+                buf.extend_from_slice(&new_round.to_le_bytes());
+            }
+            &Self::SendHashValue { ref data_hash, feed_id } => {
+                buf.push(2);
+                buf.extend_from_slice(data_hash);
+                buf.extend_from_slice(&feed_id.to_le_bytes());
+            }
+            &Self::SendValueToSubs {
+                ref data_value,
+                ref data_type,
+                pulse_id,
+                ref subscription_id,
+                feed_id,
+            } => {
+                buf.push(3);
+                buf.push(data_type.to_u8());
+                buf.extend_from_slice(&data_type.encode_value(data_value));
+                buf.extend_from_slice(&pulse_id.to_le_bytes());
+                buf.extend_from_slice(subscription_id);
+                buf.extend_from_slice(&feed_id.to_le_bytes());
+            }
+            &Self::Subscribe {
+                ref address,
+                min_confirmations,
+                reward,
+                ref subscription_id,
+                ref subscriber_accounts,
+                feed_id,
+            } => {
+                buf.push(4);
+                buf.extend_from_slice(&address.to_bytes());
+                buf.push(min_confirmations);
+                buf.extend_from_slice(&reward);
+                buf.extend_from_slice(subscription_id);
+                buf.push(subscriber_accounts.len() as u8);
+                for subscriber_account in subscriber_accounts {
+                    buf.extend_from_slice(&subscriber_account.pubkey.to_bytes());
+                    buf.push(subscriber_account.is_writable as u8);
+                }
+                buf.extend_from_slice(&feed_id.to_le_bytes());
+            }
+            &Self::Unsubscribe {
+                ref subscription_id,
+                feed_id,
+            } => {
+                buf.push(5);
+                buf.extend_from_slice(subscription_id);
+                buf.extend_from_slice(&feed_id.to_le_bytes());
+            }
+            &Self::TopUpSubscription {
+                ref subscription_id,
+                amount,
+                feed_id,
+            } => {
+                buf.push(6);
+                buf.extend_from_slice(subscription_id);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&feed_id.to_le_bytes());
+            }
+            &Self::ClaimReward => {
+                buf.push(7);
+            }
+            &Self::ConfigureRewards {
+                ref reward_mint,
+                ref reward_distribution,
+            } => {
+                buf.push(8);
+                buf.extend_from_slice(&reward_mint.to_bytes());
+                buf.push(reward_distribution.to_u8());
+            }
+            &Self::ConfigurePulseRetention { pulse_retention } => {
+                buf.push(9);
+                buf.push(pulse_retention);
+            }
+            &Self::SendHashValueWithSignatures { ref data_hash, feed_id } => {
+                buf.push(10);
+                buf.extend_from_slice(data_hash);
+                buf.extend_from_slice(&feed_id.to_le_bytes());
+            }
+            &Self::UpdateOraclesWithConsuls {
+                ref new_oracles,
+                new_round,
+            } => {
+                buf.push(11);
+                buf.push(new_oracles.len() as u8);
+                for oracle in new_oracles {
+                    buf.extend_from_slice(&oracle.to_bytes());
+                }
+                buf.extend_from_slice(&new_round.to_le_bytes());
+            }
+            &Self::CreateFeed {
+                feed_id,
+                ref data_type,
+            } => {
+                buf.push(12);
+                buf.extend_from_slice(&feed_id.to_le_bytes());
+                buf.push(data_type.to_u8());
+            }
+        }
+        buf
+    }
+}
+
+// the nebula data account followed by the feed account for a non-default feed,
+// along with the address the feed pulses and subscriptions are derived from
+fn stream_accounts(program_id: &Pubkey, nebula_contract: &Pubkey, feed_id: FeedID) -> (Vec<AccountMeta>, Pubkey) {
+    let stream = NebulaContract::stream_address(program_id, nebula_contract, feed_id);
+
+    let mut accounts = vec![AccountMeta::new(*nebula_contract, false)];
+    if stream != *nebula_contract {
+        accounts.push(AccountMeta::new(stream, false));
+    }
+
+    (accounts, stream)
+}
+
+/// Creates an `InitContract` instruction, `oracles.len()` is used as the bft.
+/// `gravity_contract` is the Gravity data account owned by `gravity_program`.
+///
+/// Accounts expected:
+///   0. `[signer]` Initializer
+///   1. `[writable]` Nebula data account
+///   2. `[writable]` Multisig account
+pub fn init_contract(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    nebula_contract: &Pubkey,
+    multisig: &Pubkey,
+    nebula_data_type: DataType,
+    gravity_contract: &Pubkey,
+    gravity_program: &Pubkey,
+    oracles: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::InitContract {
+        nebula_data_type,
+        gravity_contract_program_id: *gravity_contract,
+        initial_oracles: oracles.to_vec(),
+        oracles_bft: oracles.len() as u8,
+        gravity_program_id: *gravity_program,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*nebula_contract, false),
+        AccountMeta::new(*multisig, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `UpdateOracles` instruction, the multisig signers are replaced with `new_oracles`.
+///
+/// Accounts expected:
+///   0. `[signer]` Initializer
+///   1. `[writable]` Nebula data account
+///   2. `[writable]` Multisig account
+///   3. ..3+bft `[signer]` Current oracles
+pub fn update_oracles(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    nebula_contract: &Pubkey,
+    multisig: &Pubkey,
+    oracle_signers: &[&Pubkey],
+    new_oracles: &[Pubkey],
+    new_round: PulseID,
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::UpdateOracles {
+        new_oracles: new_oracles.to_vec(),
+        new_round,
+    }
+    .pack();
+
+    let mut accounts = Vec::with_capacity(3 + oracle_signers.len());
+    accounts.push(AccountMeta::new_readonly(*initializer, true));
+    accounts.push(AccountMeta::new(*nebula_contract, false));
+    accounts.push(AccountMeta::new(*multisig, false));
+    for oracle_signer in oracle_signers.iter() {
+        accounts.push(AccountMeta::new_readonly(**oracle_signer, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `UpdateOraclesWithConsuls` instruction. The transaction must also carry
+/// an Ed25519 program instruction with the current Gravity consuls signatures over
+/// `NebulaContract::oracles_update_message`, the multisig signers are replaced with `new_oracles`.
+///
+/// Accounts expected:
+///   0. `[signer]` Relayer
+///   1. `[writable]` Nebula data account
+///   2. `[writable]` Multisig account
+///   3. `[]` Gravity data account
+///   4. `[]` Instructions sysvar
+pub fn update_oracles_with_consuls(
+    program_id: &Pubkey,
+    relayer: &Pubkey,
+    nebula_contract: &Pubkey,
+    multisig: &Pubkey,
+    gravity_contract: &Pubkey,
+    new_oracles: &[Pubkey],
+    new_round: PulseID,
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::UpdateOraclesWithConsuls {
+        new_oracles: new_oracles.to_vec(),
+        new_round,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*relayer, true),
+        AccountMeta::new(*nebula_contract, false),
+        AccountMeta::new(*multisig, false),
+        AccountMeta::new_readonly(*gravity_contract, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `SendHashValue` instruction, the pulse records the current slot and unix timestamp.
+/// `pulse_id` is the id of the new pulse, i.e. the feed `last_pulse_id` + 1.
+///
+/// Accounts expected:
+///   0. `[writable, signer]` Initializer, pays for the pulse account
+///   1. `[writable]` Nebula data account
+///   1+. `[writable]` Feed account, follows the nebula data account for a non-default `feed_id`
+///   2. `[]` Multisig account
+///   3. ..3+bft `[signer]` Oracles
+///   3+bft. `[]` Clock sysvar
+///   4+bft. `[writable]` Pulse account, derived from the feed address and `pulse_id`
+///   5+bft. `[]` System program
+///   6+bft. `[]` Rent sysvar
+///   7+bft. `[writable]` Optional expired pulse account, closed in favor of the initializer
+pub fn send_hash_value(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    nebula_contract: &Pubkey,
+    multisig: &Pubkey,
+    oracle_signers: &[&Pubkey],
+    data_hash: &[u8; 32],
+    feed_id: FeedID,
+    pulse_id: PulseID,
+    expired_pulse_id: Option<PulseID>,
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::SendHashValue {
+        data_hash: data_hash.to_vec(),
+        feed_id,
+    }
+    .pack();
+
+    let (stream_accounts, stream) = stream_accounts(program_id, nebula_contract, feed_id);
+    let (pulse_account, _) = NebulaContract::pulse_address(program_id, &stream, pulse_id);
+
+    let mut accounts = Vec::with_capacity(9 + oracle_signers.len());
+    accounts.push(AccountMeta::new(*initializer, true));
+    accounts.extend_from_slice(&stream_accounts);
+    accounts.push(AccountMeta::new_readonly(*multisig, false));
+    for oracle_signer in oracle_signers.iter() {
+        accounts.push(AccountMeta::new_readonly(**oracle_signer, true));
+    }
+    accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    accounts.push(AccountMeta::new(pulse_account, false));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+    if let Some(expired_pulse_id) = expired_pulse_id {
+        let (expired_pulse_account, _) = NebulaContract::pulse_address(program_id, &stream, expired_pulse_id);
+        accounts.push(AccountMeta::new(expired_pulse_account, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `SendHashValueWithSignatures` instruction. The transaction must also carry
+/// an Ed25519 program instruction with at least bft oracle signatures over
+/// `NebulaContract::pulse_message` of the feed address and `pulse_id`, i.e. the feed `last_pulse_id` + 1.
+///
+/// Accounts expected:
+///   0. `[writable, signer]` Relayer, pays for the pulse account
+///   1. `[writable]` Nebula data account
+///   1+. `[writable]` Feed account, follows the nebula data account for a non-default `feed_id`
+///   2. `[]` Instructions sysvar
+///   3. `[]` Clock sysvar
+///   4. `[writable]` Pulse account, derived from the feed address and `pulse_id`
+///   5. `[]` System program
+///   6. `[]` Rent sysvar
+///   7. `[writable]` Optional expired pulse account, closed in favor of the relayer
+pub fn send_hash_value_with_signatures(
+    program_id: &Pubkey,
+    relayer: &Pubkey,
+    nebula_contract: &Pubkey,
+    data_hash: &[u8; 32],
+    feed_id: FeedID,
+    pulse_id: PulseID,
+    expired_pulse_id: Option<PulseID>,
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::SendHashValueWithSignatures {
+        data_hash: data_hash.to_vec(),
+        feed_id,
+    }
+    .pack();
+
+    let (stream_accounts, stream) = stream_accounts(program_id, nebula_contract, feed_id);
+    let (pulse_account, _) = NebulaContract::pulse_address(program_id, &stream, pulse_id);
+
+    let mut accounts = vec![AccountMeta::new(*relayer, true)];
+    accounts.extend_from_slice(&stream_accounts);
+    accounts.extend_from_slice(&[
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(pulse_account, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ]);
+    if let Some(expired_pulse_id) = expired_pulse_id {
+        let (expired_pulse_account, _) = NebulaContract::pulse_address(program_id, &stream, expired_pulse_id);
+        accounts.push(AccountMeta::new(expired_pulse_account, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `SendValueToSubs` instruction. Every call confirms the pulse on behalf of the oracle,
+/// the value is forwarded once the subscription `min_confirmations` is reached.
+/// `data_value` is the raw value hashed by `SendHashValue`, encoded on the wire according to `data_type`,
+/// which has to be the nebula data type.
+/// The pulse slot and unix timestamp are appended to the forwarded value, see `pulse_time_from_attached`.
+/// Each subscription receives the pulse once; several `SendValueToSubs` instructions may be batched
+/// in one transaction to serve all the subscribers, the pulse account is closed after the last one.
+///
+/// Accounts expected:
+///   0. `[writable, signer]` Oracle, receives the pulse account rent once the value is delivered
+///   1. `[writable]` Nebula data account
+///   1+. `[writable]` Feed account, follows the nebula data account for a non-default `feed_id`
+///   2. `[]` Multisig account
+///   3. `[writable]` Pulse account
+///   4. `[writable]` Subscription account
+///   5. `[]` Subscriber program, the subscription `contract_address`
+///   6. ..6+N Subscriber accounts, in the order and with the writability declared on subscribe,
+///      the first one is the subscriber data account owned by the subscriber program
+pub fn send_value_to_subs(
+    program_id: &Pubkey,
+    oracle: &Pubkey,
+    nebula_contract: &Pubkey,
+    multisig: &Pubkey,
+    subscriber_program_id: &Pubkey,
+    subscriber_accounts: &[AccountMeta],
+    data_value: &[u8],
+    data_type: DataType,
+    feed_id: FeedID,
+    pulse_id: PulseID,
+    subscription_id: &SubscriptionID,
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::SendValueToSubs {
+        data_value: data_value.to_vec(),
+        data_type,
+        pulse_id,
+        subscription_id: *subscription_id,
+        feed_id,
+    }
+    .pack();
+
+    let (stream_accounts, stream) = stream_accounts(program_id, nebula_contract, feed_id);
+    let (pulse_account, _) = NebulaContract::pulse_address(program_id, &stream, pulse_id);
+    let (subscription_account, _) = NebulaContract::subscription_address(program_id, &stream, subscription_id);
+
+    let mut accounts = Vec::with_capacity(7 + subscriber_accounts.len());
+    accounts.push(AccountMeta::new(*oracle, true));
+    accounts.extend_from_slice(&stream_accounts);
+    accounts.push(AccountMeta::new_readonly(*multisig, false));
+    accounts.push(AccountMeta::new(pulse_account, false));
+    accounts.push(AccountMeta::new(subscription_account, false));
+    accounts.push(AccountMeta::new_readonly(*subscriber_program_id, false));
+    accounts.extend_from_slice(subscriber_accounts);
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Accounts moving rewards in or out of the lamports escrow.
+///
+/// Accounts:
+///   0. `[writable]` Reward escrow authority (PDA holding the lamports)
+///   1. `[]` System program
+pub fn lamports_escrow_accounts(program_id: &Pubkey, nebula_contract: &Pubkey) -> Vec<AccountMeta> {
+    let (escrow_authority, _) = NebulaContract::reward_escrow_authority(program_id, nebula_contract);
+
+    vec![
+        AccountMeta::new(escrow_authority, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]
+}
+
+/// Accounts moving rewards in or out of the reward mint escrow.
+///
+/// Accounts:
+///   0. `[]` Reward escrow authority (PDA owning the escrow token account)
+///   1. `[]` Token program
+///   2. `[writable]` Escrow token account
+///   3. `[writable]` User token account, the source on deposit and the receiver on withdrawal
+pub fn token_escrow_accounts(
+    program_id: &Pubkey,
+    nebula_contract: &Pubkey,
+    escrow_token_account: &Pubkey,
+    user_token_account: &Pubkey,
+) -> Vec<AccountMeta> {
+    let (escrow_authority, _) = NebulaContract::reward_escrow_authority(program_id, nebula_contract);
+
+    vec![
+        AccountMeta::new_readonly(escrow_authority, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*escrow_token_account, false),
+        AccountMeta::new(*user_token_account, false),
+    ]
+}
+
+/// Creates a `Subscribe` instruction, the first reward is escrowed.
+/// `subscriber_accounts` are the accounts `contract_address` expects on `AttachValue` after the oracle,
+/// a default pubkey stands for an account supplied by the oracle on every delivery.
+/// The first one is the subscriber data account, it has to be owned by `contract_address`.
+///
+/// Accounts expected:
+///   0. `[writable, signer]` Subscriber, pays for the subscription account
+///   1. `[writable]` Nebula data account
+///   1+. `[writable]` Feed account, follows the nebula data account for a non-default `feed_id`
+///   2. `[writable]` Subscription account, derived from the feed address and `subscription_id`
+///   3. `[]` System program
+///   4. `[]` Rent sysvar
+///   5. ..5+N Escrow accounts, see `lamports_escrow_accounts` and `token_escrow_accounts`,
+///      may be omitted for a zero reward
+pub fn subscribe(
+    program_id: &Pubkey,
+    subscriber: &Pubkey,
+    nebula_contract: &Pubkey,
+    contract_address: &Pubkey,
+    min_confirmations: u8,
+    reward: &U256,
+    subscription_id: &SubscriptionID,
+    subscriber_accounts: &[SubscriberAccount],
+    feed_id: FeedID,
+    escrow_accounts: &[AccountMeta],
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::Subscribe {
+        address: *contract_address,
+        min_confirmations,
+        reward: *reward,
+        subscription_id: *subscription_id,
+        subscriber_accounts: subscriber_accounts.to_vec(),
+        feed_id,
+    }
+    .pack();
+
+    let (stream_accounts, stream) = stream_accounts(program_id, nebula_contract, feed_id);
+    let (subscription_account, _) = NebulaContract::subscription_address(program_id, &stream, subscription_id);
+
+    let mut accounts = vec![AccountMeta::new(*subscriber, true)];
+    accounts.extend_from_slice(&stream_accounts);
+    accounts.extend_from_slice(&[
+        AccountMeta::new(subscription_account, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ]);
+    accounts.extend_from_slice(escrow_accounts);
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `Unsubscribe` instruction, the escrowed balance is refunded.
+/// A subscription still counted by pending pulses is kept until they are served,
+/// calling it again afterwards closes the account.
+///
+/// Accounts expected:
+///   0. `[writable, signer]` Subscriber
+///   1. `[writable]` Nebula data account
+///   1+. `[writable]` Feed account, follows the nebula data account for a non-default `feed_id`
+///   2. `[writable]` Subscription account, closed in favor of the subscriber once nothing is pending
+///   3. ..3+N Escrow accounts, may be omitted when nothing is escrowed
+pub fn unsubscribe(
+    program_id: &Pubkey,
+    subscriber: &Pubkey,
+    nebula_contract: &Pubkey,
+    subscription_id: &SubscriptionID,
+    feed_id: FeedID,
+    escrow_accounts: &[AccountMeta],
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::Unsubscribe {
+        subscription_id: *subscription_id,
+        feed_id,
+    }
+    .pack();
+
+    let (stream_accounts, stream) = stream_accounts(program_id, nebula_contract, feed_id);
+    let (subscription_account, _) = NebulaContract::subscription_address(program_id, &stream, subscription_id);
+
+    let mut accounts = vec![AccountMeta::new(*subscriber, true)];
+    accounts.extend_from_slice(&stream_accounts);
+    accounts.push(AccountMeta::new(subscription_account, false));
+    accounts.extend_from_slice(escrow_accounts);
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `TopUpSubscription` instruction.
+///
+/// Accounts expected:
+///   0. `[signer]` Payer
+///   1. `[writable]` Nebula data account
+///   1+. `[writable]` Feed account, follows the nebula data account for a non-default `feed_id`
+///   2. `[writable]` Subscription account
+///   3. ..3+N Escrow accounts
+pub fn top_up_subscription(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    nebula_contract: &Pubkey,
+    subscription_id: &SubscriptionID,
+    amount: u64,
+    feed_id: FeedID,
+    escrow_accounts: &[AccountMeta],
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::TopUpSubscription {
+        subscription_id: *subscription_id,
+        amount,
+        feed_id,
+    }
+    .pack();
+
+    let (stream_accounts, stream) = stream_accounts(program_id, nebula_contract, feed_id);
+    let (subscription_account, _) = NebulaContract::subscription_address(program_id, &stream, subscription_id);
+
+    let mut accounts = vec![AccountMeta::new(*payer, true)];
+    accounts.extend_from_slice(&stream_accounts);
+    accounts.push(AccountMeta::new(subscription_account, false));
+    accounts.extend_from_slice(escrow_accounts);
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `ClaimReward` instruction.
+///
+/// Accounts expected:
+///   0. `[signer]` Oracle
+///   1. `[writable]` Nebula data account
+///   2. ..2+N Escrow accounts
+pub fn claim_reward(
+    program_id: &Pubkey,
+    oracle: &Pubkey,
+    nebula_contract: &Pubkey,
+    escrow_accounts: &[AccountMeta],
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::ClaimReward.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*oracle, true),
+        AccountMeta::new(*nebula_contract, false),
+    ];
+    accounts.extend_from_slice(escrow_accounts);
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `ConfigureRewards` instruction, the default `reward_mint` means lamports.
+///
+/// Accounts expected:
+///   0. `[signer]` Nebula initializer
+///   1. `[writable]` Nebula data account
+pub fn configure_rewards(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    nebula_contract: &Pubkey,
+    reward_mint: &Pubkey,
+    reward_distribution: RewardDistribution,
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::ConfigureRewards {
+        reward_mint: *reward_mint,
+        reward_distribution,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*nebula_contract, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `ConfigurePulseRetention` instruction.
+///
+/// Accounts expected:
+///   0. `[signer]` Nebula initializer
+///   1. `[writable]` Nebula data account
+pub fn configure_pulse_retention(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    nebula_contract: &Pubkey,
+    pulse_retention: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::ConfigurePulseRetention { pulse_retention }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*nebula_contract, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `CreateFeed` instruction, the feed shares the nebula oracles and rewards.
+///
+/// Accounts expected:
+///   0. `[writable, signer]` Nebula initializer, pays for the feed account
+///   1. `[]` Nebula data account
+///   2. `[writable]` Feed account, derived from the nebula address and `feed_id`
+///   3. `[]` System program
+///   4. `[]` Rent sysvar
+pub fn create_feed(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    nebula_contract: &Pubkey,
+    feed_id: FeedID,
+    data_type: DataType,
+) -> Result<Instruction, ProgramError> {
+    let data = NebulaContractInstruction::CreateFeed { feed_id, data_type }.pack();
+
+    let (feed_account, _) = NebulaContract::feed_address(program_id, nebula_contract, feed_id);
+
+    let accounts = vec![
+        AccountMeta::new(*initializer, true),
+        AccountMeta::new_readonly(*nebula_contract, false),
+        AccountMeta::new(feed_account, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_from_range_alloc() {
+        let allocs = vec![1, 1, 32 * 3, 8];
+
+        let ranges = build_range_from_alloc(&allocs);
+
+        assert_eq!(ranges.len(), allocs.len());
+        assert_eq!(ranges[0], 0..1);
+        assert_eq!(ranges[1], 1..1 + 1);
+        assert_eq!(ranges[2], 1 + 1..1 + 1 + (32 * 3));
+        assert_eq!(ranges[3], 1 + 1 + (32 * 3)..1 + 1 + (32 * 3) + 8);
+    }
+
+    #[test]
+    fn test_bft_extraction() {
+        let input: [u8; 1] = u8::to_le_bytes(3);
+
+        let _extracted = extract_from_range(&input, 0..1, |x: &[u8]| {
+            u8::from_le_bytes(*array_ref![x, 0, 1])
+        });
+    }
+
+    fn pubkeys(count: usize) -> Vec<Pubkey> {
+        (0..count).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    #[test]
+    fn test_pack_unpack_round_trip() {
+        let instructions = vec![
+            NebulaContractInstruction::InitContract {
+                nebula_data_type: DataType::Bytes,
+                gravity_contract_program_id: Pubkey::new_unique(),
+                initial_oracles: pubkeys(3),
+                oracles_bft: 3,
+                gravity_program_id: Pubkey::new_unique(),
+            },
+            NebulaContractInstruction::UpdateOracles {
+                new_oracles: pubkeys(4),
+                new_round: 12,
+            },
+            NebulaContractInstruction::SendHashValue {
+                data_hash: vec![7; 32],
+                feed_id: 0,
+            },
+            NebulaContractInstruction::SendValueToSubs {
+                data_value: b"gravity".to_vec(),
+                data_type: DataType::String,
+                pulse_id: 42,
+                subscription_id: [3; 16],
+                feed_id: 0,
+            },
+            NebulaContractInstruction::SendValueToSubs {
+                data_value: 42i64.to_le_bytes().to_vec(),
+                data_type: DataType::Int64,
+                pulse_id: 43,
+                subscription_id: [3; 16],
+                feed_id: 9,
+            },
+            NebulaContractInstruction::SendValueToSubs {
+                data_value: vec![],
+                data_type: DataType::Bytes,
+                pulse_id: 44,
+                subscription_id: [3; 16],
+                feed_id: u32::MAX,
+            },
+            NebulaContractInstruction::Subscribe {
+                address: Pubkey::new_unique(),
+                min_confirmations: 2,
+                reward: [8; 32],
+                subscription_id: [5; 16],
+                subscriber_accounts: vec![
+                    SubscriberAccount { pubkey: Pubkey::new_unique(), is_writable: true },
+                    SubscriberAccount { pubkey: Pubkey::default(), is_writable: false },
+                ],
+                feed_id: 1,
+            },
+            NebulaContractInstruction::Unsubscribe {
+                subscription_id: [6; 16],
+                feed_id: 1,
+            },
+            NebulaContractInstruction::TopUpSubscription {
+                subscription_id: [7; 16],
+                amount: 500,
+                feed_id: 1,
+            },
+            NebulaContractInstruction::ClaimReward,
+            NebulaContractInstruction::ConfigureRewards {
+                reward_mint: Pubkey::new_unique(),
+                reward_distribution: RewardDistribution::MultisigSigners,
+            },
+            NebulaContractInstruction::ConfigurePulseRetention {
+                pulse_retention: 4,
+            },
+            NebulaContractInstruction::SendHashValueWithSignatures {
+                data_hash: vec![2; 32],
+                feed_id: 2,
+            },
+            NebulaContractInstruction::CreateFeed {
+                feed_id: 2,
+                data_type: DataType::Int64,
+            },
+            NebulaContractInstruction::UpdateOraclesWithConsuls {
+                new_oracles: pubkeys(2),
+                new_round: 13,
+            },
+        ];
+
+        for instruction in instructions {
+            let packed = instruction.pack();
+            assert_eq!(NebulaContractInstruction::unpack(&packed).unwrap(), instruction);
+        }
+    }
+
+    #[test]
+    fn test_unpack_invalid_value() {
+        let mut packed = NebulaContractInstruction::SendValueToSubs {
+            data_value: b"gravity".to_vec(),
+            data_type: DataType::String,
+            pulse_id: 1,
+            subscription_id: [0; 16],
+            feed_id: 0,
+        }
+        .pack();
+
+        let mut unknown_type = packed.clone();
+        unknown_type[1] = 3;
+        assert_eq!(
+            NebulaContractInstruction::unpack(&unknown_type).unwrap_err(),
+            NebulaError::InvalidDataType.into()
+        );
+
+        // not a valid UTF-8 string
+        packed[6] = 0xff;
+        assert_eq!(
+            NebulaContractInstruction::unpack(&packed).unwrap_err(),
+            NebulaError::InvalidDataValue.into()
+        );
+    }
+
+    #[test]
+    fn test_send_value_to_subs_accounts_order() {
+        let keys = pubkeys(7);
+        let subscriber_accounts = vec![AccountMeta::new(keys[5], false), AccountMeta::new_readonly(keys[6], false)];
+
+        let instruction = send_value_to_subs(
+            &keys[0],
+            &keys[1],
+            &keys[2],
+            &keys[3],
+            &keys[4],
+            &subscriber_accounts,
+            &[1; 8],
+            DataType::Int64,
+            NebulaContract::DEFAULT_FEED_ID,
+            1,
+            &[0; 16],
+        )
+        .unwrap();
+
+        let account_keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(account_keys.len(), 8);
+        assert_eq!(account_keys[0], keys[1]);
+        assert_eq!(account_keys[3], NebulaContract::pulse_address(&keys[0], &keys[2], 1).0);
+        assert_eq!(account_keys[4], NebulaContract::subscription_address(&keys[0], &keys[2], &[0; 16]).0);
+        assert_eq!(account_keys[5], keys[4]);
+        assert_eq!(&instruction.accounts[6..], &subscriber_accounts[..]);
+        assert!(instruction.accounts[0].is_signer);
+
+        // the feed account follows the nebula, pulses and subscriptions are derived from the feed
+        let instruction = send_value_to_subs(
+            &keys[0],
+            &keys[1],
+            &keys[2],
+            &keys[3],
+            &keys[4],
+            &subscriber_accounts,
+            &[1; 8],
+            DataType::Int64,
+            5,
+            1,
+            &[0; 16],
+        )
+        .unwrap();
+
+        let (feed, _) = NebulaContract::feed_address(&keys[0], &keys[2], 5);
+        let account_keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(account_keys.len(), 9);
+        assert_eq!(account_keys[1], keys[2]);
+        assert_eq!(account_keys[2], feed);
+        assert!(instruction.accounts[2].is_writable);
+        assert_eq!(account_keys[4], NebulaContract::pulse_address(&keys[0], &feed, 1).0);
+        assert_eq!(account_keys[5], NebulaContract::subscription_address(&keys[0], &feed, &[0; 16]).0);
+        assert_eq!(&instruction.accounts[7..], &subscriber_accounts[..]);
+    }
+}
@@ -1,16 +1,19 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
+    instruction::AccountMeta,
     msg,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack},
-    program::{invoke_signed},
+    program::{invoke, invoke_signed},
     pubkey::Pubkey,
+    system_instruction, system_program,
+    rent::Rent,
+    sysvar::Sysvar,
 };
 
-use spl_token::{
-    state::Multisig,
-};
+use spl_token::{error::TokenError, state::Account as TokenAccount};
 
 use gravity_misc::validation::validate_contract_emptiness;
 use solana_gravity_contract::gravity::{
@@ -18,24 +21,182 @@ use solana_gravity_contract::gravity::{
 };
 
 use crate::nebula::instruction::NebulaContractInstruction;
-use crate::nebula::state::{NebulaContract};
+use crate::nebula::state::{Feed, NebulaContract, Pulse, RewardDistribution, Subscription};
+use solana_gravity_contract::gravity::state::{AccountStorage, GravityContract};
 use crate::nebula::error::NebulaError;
 
 // use solana_port_contract::ibport::instruction::attach_value;
-use gravity_misc::ports::instruction::attach_value;
+use gravity_misc::ports::instruction::{append_pulse_time, attach_value_to_subscriber, SubscriberAccount};
 
-use gravity_misc::model::{DataType, PulseID, SubscriptionID};
+use gravity_misc::model::{DataType, FeedID, PulseID, SubscriptionID, U256};
 use gravity_misc::validation::PDAResolver;
+use gravity_misc::ed25519::verified_signers;
 
 pub struct NebulaProcessor;
 
 impl NebulaProcessor {
+    fn validate_reward_escrow(
+        nebula_contract_info: &NebulaContract,
+        nebula_contract_account: &AccountInfo,
+        escrow_authority: &AccountInfo,
+        transfer_program: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<u8, ProgramError> {
+        let (expected_escrow_authority, bump_seed) =
+            NebulaContract::reward_escrow_authority(program_id, nebula_contract_account.key);
+
+        if *escrow_authority.key != expected_escrow_authority {
+            return Err(NebulaError::InvalidRewardEscrow.into());
+        }
+
+        let expected_transfer_program = if nebula_contract_info.rewards_in_lamports() {
+            system_program::id()
+        } else {
+            spl_token::id()
+        };
+
+        if *transfer_program.key != expected_transfer_program {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        Ok(bump_seed)
+    }
+
+    fn validate_escrow_token_account(
+        nebula_contract_info: &NebulaContract,
+        escrow_authority: &AccountInfo,
+        escrow_token_account: &AccountInfo,
+    ) -> ProgramResult {
+        if *escrow_token_account.owner != spl_token::id() {
+            return Err(NebulaError::InvalidRewardEscrow.into());
+        }
+
+        let escrow_token_account_info = TokenAccount::unpack(&escrow_token_account.try_borrow_data()?)?;
+
+        if escrow_token_account_info.owner != *escrow_authority.key
+            || escrow_token_account_info.mint != nebula_contract_info.reward_mint
+        {
+            return Err(NebulaError::InvalidRewardEscrow.into());
+        }
+
+        Ok(())
+    }
+
+    // moves `amount` from the payer into the reward escrow
+    fn deposit_reward<'a, 'b>(
+        nebula_contract_info: &NebulaContract,
+        nebula_contract_account: &AccountInfo<'a>,
+        payer: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+        amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let escrow_authority = next_account_info(account_info_iter)?;
+        let transfer_program = next_account_info(account_info_iter)?;
+
+        Self::validate_reward_escrow(
+            nebula_contract_info,
+            nebula_contract_account,
+            escrow_authority,
+            transfer_program,
+            program_id,
+        )?;
+
+        if nebula_contract_info.rewards_in_lamports() {
+            return invoke(
+                &system_instruction::transfer(payer.key, escrow_authority.key, amount),
+                &[payer.clone(), escrow_authority.clone(), transfer_program.clone()],
+            );
+        }
+
+        let escrow_token_account = next_account_info(account_info_iter)?;
+        let payer_token_account = next_account_info(account_info_iter)?;
+
+        Self::validate_escrow_token_account(nebula_contract_info, escrow_authority, escrow_token_account)?;
+
+        invoke(
+            &spl_token::instruction::transfer(
+                transfer_program.key,
+                payer_token_account.key,
+                escrow_token_account.key,
+                payer.key,
+                &[],
+                amount,
+            )?,
+            &[
+                payer_token_account.clone(),
+                escrow_token_account.clone(),
+                payer.clone(),
+                transfer_program.clone(),
+            ],
+        )
+    }
+
+    // moves `amount` from the reward escrow to the receiver,
+    // the receiver token account is taken from the escrow accounts for the reward mint
+    fn withdraw_reward<'a, 'b>(
+        nebula_contract_info: &NebulaContract,
+        nebula_contract_account: &AccountInfo<'a>,
+        receiver: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+        amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let escrow_authority = next_account_info(account_info_iter)?;
+        let transfer_program = next_account_info(account_info_iter)?;
+
+        let bump_seed = Self::validate_reward_escrow(
+            nebula_contract_info,
+            nebula_contract_account,
+            escrow_authority,
+            transfer_program,
+            program_id,
+        )?;
+        let escrow_seeds: &[&[u8]] = &[
+            nebula_contract_account.key.as_ref(),
+            NebulaContract::REWARD_ESCROW_SEED,
+            &[bump_seed],
+        ];
+
+        if nebula_contract_info.rewards_in_lamports() {
+            return invoke_signed(
+                &system_instruction::transfer(escrow_authority.key, receiver.key, amount),
+                &[escrow_authority.clone(), receiver.clone(), transfer_program.clone()],
+                &[escrow_seeds],
+            );
+        }
+
+        let escrow_token_account = next_account_info(account_info_iter)?;
+        let receiver_token_account = next_account_info(account_info_iter)?;
+
+        Self::validate_escrow_token_account(nebula_contract_info, escrow_authority, escrow_token_account)?;
+
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                transfer_program.key,
+                escrow_token_account.key,
+                receiver_token_account.key,
+                escrow_authority.key,
+                &[],
+                amount,
+            )?,
+            &[
+                escrow_token_account.clone(),
+                receiver_token_account.clone(),
+                escrow_authority.clone(),
+                transfer_program.clone(),
+            ],
+            &[escrow_seeds],
+        )
+    }
+
     fn process_init_nebula_contract(
         accounts: &[AccountInfo],
         nebula_data_type: DataType,
         gravity_contract_data_account: &Pubkey,
         initial_oracles: Vec<Pubkey>,
         oracles_bft: u8,
+        gravity_program_id: &Pubkey,
         _program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -48,6 +209,7 @@ impl NebulaProcessor {
         let nebula_contract_account = next_account_info(account_info_iter)?;
 
         validate_contract_emptiness(&nebula_contract_account.try_borrow_data()?[0..NebulaContract::LEN])?;
+        NebulaContract::validate_oracles(&initial_oracles)?;
 
         let mut nebula_contract_info = NebulaContract::default();
 
@@ -59,6 +221,7 @@ impl NebulaProcessor {
 
         nebula_contract_info.oracles = initial_oracles.clone();
         nebula_contract_info.gravity_contract = *gravity_contract_data_account;
+        nebula_contract_info.gravity_program = *gravity_program_id;
 
         msg!("instantiated nebula contract");
 
@@ -99,7 +262,7 @@ impl NebulaProcessor {
 
         let nebula_contract_account = next_account_info(account_info_iter)?;
 
-        let mut nebula_contract_info =
+        let nebula_contract_info =
             NebulaContract::unpack(&nebula_contract_account.data.borrow()[0..NebulaContract::LEN])?;
 
         let nebula_contract_multisig_account = next_account_info(account_info_iter)?;
@@ -110,19 +273,125 @@ impl NebulaProcessor {
             program_id,
             &nebula_contract_multisig_account_pubkey,
             &nebula_contract_multisig_account,
-            &accounts[3..3 + nebula_contract_info.bft as usize].to_vec(),
+            &accounts
+                .get(3..3 + nebula_contract_info.bft as usize)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?
+                .to_vec(),
         ) {
             Err(err) => return Err(err),
             _ => {}
         };
 
+        Self::apply_oracles_update(
+            nebula_contract_account,
+            nebula_contract_multisig_account,
+            nebula_contract_info,
+            new_round,
+            new_oracles,
+        )
+    }
+
+    fn process_update_nebula_oracles_with_consuls(
+        accounts: &[AccountInfo],
+        new_round: PulseID,
+        new_oracles: Vec<Pubkey>,
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        // any relayer may submit the rotation, authority comes from the consuls signatures
+        let relayer = next_account_info(account_info_iter)?;
+        if !relayer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let nebula_contract_account = next_account_info(account_info_iter)?;
+
+        let nebula_contract_info =
+            NebulaContract::unpack(&nebula_contract_account.data.borrow()[0..NebulaContract::LEN])?;
+
+        let nebula_contract_multisig_account = next_account_info(account_info_iter)?;
+
+        msg!("picking gravity contract");
+        let gravity_contract_account = next_account_info(account_info_iter)?;
+
+        if nebula_contract_info.gravity_program == Pubkey::default()
+            || *gravity_contract_account.key != nebula_contract_info.gravity_contract
+            || *gravity_contract_account.owner != nebula_contract_info.gravity_program
+        {
+            return Err(NebulaError::InvalidGravityContract.into());
+        }
+
+        let gravity_contract_info =
+            GravityContract::unpack_from_account(&gravity_contract_account.try_borrow_data()?)?;
+        if !gravity_contract_info.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        let instructions_sysvar_account = next_account_info(account_info_iter)?;
+
+        msg!("verifying consuls signatures");
+        let message = NebulaContract::oracles_update_message(
+            nebula_contract_account.key,
+            &new_oracles,
+            new_round,
+        );
+        let signers = verified_signers(instructions_sysvar_account, &message)?;
+
+        let approvals = gravity_contract_info
+            .consuls
+            .iter()
+            .filter(|consul| signers.contains(consul))
+            .count();
+
+        if approvals < gravity_contract_info.bft as usize {
+            return Err(NebulaError::NotEnoughConsulsSignatures.into());
+        }
+
+        Self::apply_oracles_update(
+            nebula_contract_account,
+            nebula_contract_multisig_account,
+            nebula_contract_info,
+            new_round,
+            new_oracles,
+        )
+    }
+
+    // the multisig authorizes value delivery, so it is rotated together with the oracles
+    fn apply_oracles_update(
+        nebula_contract_account: &AccountInfo,
+        nebula_contract_multisig_account: &AccountInfo,
+        mut nebula_contract_info: NebulaContract,
+        new_round: PulseID,
+        new_oracles: Vec<Pubkey>,
+    ) -> ProgramResult {
+        if *nebula_contract_multisig_account.key != nebula_contract_info.multisig_account {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
         msg!("checking new round validness");
         if new_round <= nebula_contract_info.last_round {
             return Err(GravityError::InputRoundMismatch.into());
         }
 
+        NebulaContract::validate_oracles(&new_oracles)?;
+
+        // the bft is kept across rotations, so the new set has to be able to reach it
+        let bft = nebula_contract_info.bft as usize;
+        if bft == 0 || bft > new_oracles.len() {
+            return Err(GravityError::InvalidNewBFT.into());
+        }
+
+        msg!("updating multisig signers");
+        MiscProcessor::process_update_multisig(
+            nebula_contract_multisig_account,
+            &new_oracles,
+            nebula_contract_info.bft,
+        )?;
+
         nebula_contract_info.last_round = new_round;
         nebula_contract_info.oracles = new_oracles;
+        nebula_contract_info.prune_rewards()?;
 
         NebulaContract::pack(
             nebula_contract_info,
@@ -132,9 +401,154 @@ impl NebulaProcessor {
         Ok(())
     }
 
+    // returns the multisig signers, the data provider has to be one of them
+    fn authorize_data_provider(
+        nebula_contract_info: &NebulaContract,
+        nebula_contract_multisig_account: &AccountInfo,
+        data_provider: &Pubkey,
+    ) -> Result<Vec<Pubkey>, ProgramError> {
+        if *nebula_contract_multisig_account.key != nebula_contract_info.multisig_account {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+
+        let nebula_multisig_signers = MiscProcessor::multisig_signers(nebula_contract_multisig_account)?;
+
+        NebulaContract::validate_data_provider(&nebula_multisig_signers, data_provider)?;
+
+        Ok(nebula_multisig_signers)
+    }
+
+    fn create_pda_account<'a>(
+        payer: &AccountInfo<'a>,
+        pda_account: &AccountInfo<'a>,
+        system_program_account: &AccountInfo<'a>,
+        rent_sysvar: &AccountInfo<'a>,
+        space: usize,
+        seeds: &[&[u8]],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if *system_program_account.key != system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let rent = Rent::from_account_info(rent_sysvar)?;
+
+        invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                pda_account.key,
+                rent.minimum_balance(space),
+                space as u64,
+                program_id,
+            ),
+            &[payer.clone(), pda_account.clone(), system_program_account.clone()],
+            &[seeds],
+        )
+    }
+
+    // moves the lamports out and wipes the data, so the account is dropped after the transaction
+    fn close_account(account: &AccountInfo, receiver: &AccountInfo) -> ProgramResult {
+        let receiver_lamports = receiver
+            .lamports()
+            .checked_add(account.lamports())
+            .ok_or(ProgramError::InvalidAccountData)?;
+
+        **receiver.try_borrow_mut_lamports()? = receiver_lamports;
+        **account.try_borrow_mut_lamports()? = 0;
+        account.try_borrow_mut_data()?.iter_mut().for_each(|byte| *byte = 0);
+
+        Ok(())
+    }
+
+    // picks the feed account following the nebula data account and swaps the feed pulse stream
+    // into the contract, the feed has to be put back with `store_feed` before packing the contract
+    fn load_feed<'a, 'b>(
+        nebula_contract_info: &mut NebulaContract,
+        nebula_contract_account: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+        feed_id: FeedID,
+        program_id: &Pubkey,
+    ) -> Result<Option<(&'b AccountInfo<'a>, Feed)>, ProgramError> {
+        if feed_id == NebulaContract::DEFAULT_FEED_ID {
+            return Ok(None);
+        }
+
+        let feed_account = next_account_info(account_info_iter)?;
+
+        let (expected_feed_account, _) =
+            NebulaContract::feed_address(program_id, nebula_contract_account.key, feed_id);
+
+        if feed_account.owner != program_id || *feed_account.key != expected_feed_account {
+            return Err(NebulaError::InvalidFeedAccount.into());
+        }
+
+        let mut feed = Feed::unpack_from_account(&feed_account.try_borrow_data()?)?;
+        if !feed.is_initialized {
+            return Err(NebulaError::InvalidFeedAccount.into());
+        }
+
+        nebula_contract_info.swap_feed(&mut feed);
+
+        Ok(Some((feed_account, feed)))
+    }
+
+    fn store_feed(nebula_contract_info: &mut NebulaContract, feed: Option<(&AccountInfo, Feed)>) -> ProgramResult {
+        if let Some((feed_account, mut feed)) = feed {
+            nebula_contract_info.swap_feed(&mut feed);
+            feed.pack_into_account(&mut feed_account.try_borrow_mut_data()?)?;
+        }
+
+        Ok(())
+    }
+
+    // pulses and subscriptions are derived from the feed account, or the nebula one for the default feed
+    fn stream_key<'a>(nebula_contract_account: &AccountInfo<'a>, feed: &Option<(&AccountInfo<'a>, Feed)>) -> &'a Pubkey {
+        feed.as_ref()
+            .map_or(nebula_contract_account.key, |(feed_account, _)| feed_account.key)
+    }
+
+    fn load_pulse(
+        stream: &Pubkey,
+        pulse_account: &AccountInfo,
+        pulse_id: PulseID,
+        program_id: &Pubkey,
+    ) -> Result<Pulse, ProgramError> {
+        let (expected_pulse_account, _) = NebulaContract::pulse_address(program_id, stream, pulse_id);
+
+        if pulse_account.owner != program_id || *pulse_account.key != expected_pulse_account {
+            return Err(NebulaError::InvalidPulseAccount.into());
+        }
+
+        Pulse::unpack_from_account(&pulse_account.try_borrow_data()?)
+    }
+
+    fn load_subscription(
+        stream: &Pubkey,
+        subscription_account: &AccountInfo,
+        subscription_id: &SubscriptionID,
+        program_id: &Pubkey,
+    ) -> Result<Subscription, ProgramError> {
+        let (expected_subscription_account, _) =
+            NebulaContract::subscription_address(program_id, stream, subscription_id);
+
+        if subscription_account.owner != program_id
+            || *subscription_account.key != expected_subscription_account
+        {
+            return Err(NebulaError::InvalidSubscriptionAccount.into());
+        }
+
+        let subscription = Subscription::unpack_from_account(&subscription_account.try_borrow_data()?)?;
+        if !subscription.is_initialized {
+            return Err(NebulaError::InvalidSubscriptionID.into());
+        }
+
+        Ok(subscription)
+    }
+
     pub fn process_nebula_send_hash_value(
         accounts: &[AccountInfo],
         data_hash: Vec<u8>,
+        feed_id: FeedID,
         program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -154,12 +568,25 @@ impl NebulaProcessor {
             return Err(ProgramError::UninitializedAccount);
         }
 
+        let feed = Self::load_feed(
+            &mut nebula_contract_info,
+            nebula_contract_account,
+            account_info_iter,
+            feed_id,
+            program_id,
+        )?;
+
         let nebula_contract_multisig_account = next_account_info(account_info_iter)?;
         let nebula_contract_multisig_account_pubkey = nebula_contract_info.multisig_account;
 
         msg!("checking multisig bft count");
 
-        let multisig_owner_keys = &accounts[3..3 + nebula_contract_info.bft as usize].to_vec();
+        let bft = nebula_contract_info.bft as usize;
+        let remaining_accounts = account_info_iter.as_slice();
+        let multisig_owner_keys = &remaining_accounts
+            .get(..bft)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?
+            .to_vec();
 
         match MiscProcessor::validate_owner(
             program_id,
@@ -171,15 +598,140 @@ impl NebulaProcessor {
             _ => {}
         };
 
+        let signers: Vec<Pubkey> = multisig_owner_keys.iter().map(|account| *account.key).collect();
+        let signers_bitmap = nebula_contract_info.oracles_bitmap(&signers);
+
+        Self::persist_pulse(
+            initializer,
+            nebula_contract_account,
+            nebula_contract_info,
+            feed,
+            &remaining_accounts[bft..],
+            data_hash,
+            signers_bitmap,
+            program_id,
+        )
+    }
+
+    pub fn process_nebula_send_hash_value_with_signatures(
+        accounts: &[AccountInfo],
+        data_hash: Vec<u8>,
+        feed_id: FeedID,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        // any relayer may submit the pulse, authority comes from the signatures
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let nebula_contract_account = next_account_info(account_info_iter)?;
+
+        let mut nebula_contract_info = NebulaContract::unpack(
+            &nebula_contract_account.try_borrow_data()?[0..NebulaContract::LEN],
+        )?;
+
+        if !nebula_contract_info.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        let feed = Self::load_feed(
+            &mut nebula_contract_info,
+            nebula_contract_account,
+            account_info_iter,
+            feed_id,
+            program_id,
+        )?;
+        let stream = Self::stream_key(nebula_contract_account, &feed);
+
+        let instructions_sysvar_account = next_account_info(account_info_iter)?;
+
+        msg!("verifying oracles signatures");
+        let pulse_id = nebula_contract_info
+            .last_pulse_id
+            .checked_add(1)
+            .ok_or(NebulaError::PulseValidationOrderMismatch)?;
+        let message = NebulaContract::pulse_message(stream, pulse_id, &data_hash);
+        let signers = verified_signers(instructions_sysvar_account, &message)?;
+
+        let signers_bitmap = nebula_contract_info.validate_oracle_signatures(&signers)?;
+
+        Self::persist_pulse(
+            initializer,
+            nebula_contract_account,
+            nebula_contract_info,
+            feed,
+            account_info_iter.as_slice(),
+            data_hash,
+            signers_bitmap,
+            program_id,
+        )
+    }
+
+    // creates the pulse account from the remaining accounts:
+    // clock sysvar, pulse account, system program, rent sysvar and an optional expired pulse
+    fn persist_pulse<'a>(
+        initializer: &AccountInfo<'a>,
+        nebula_contract_account: &AccountInfo<'a>,
+        mut nebula_contract_info: NebulaContract,
+        feed: Option<(&AccountInfo<'a>, Feed)>,
+        accounts: &[AccountInfo<'a>],
+        data_hash: Vec<u8>,
+        signers_bitmap: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stream = Self::stream_key(nebula_contract_account, &feed);
+
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(clock_info)?;
+
+        let pulse_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let rent_sysvar = next_account_info(account_info_iter)?;
+
         msg!("incrementing pulse id");
 
-        // TODO: find out where to catch timestamp
-        // let current_block = 1;
+        let mut pulse = nebula_contract_info.add_pulse(data_hash, clock.slot, clock.unix_timestamp)?;
+        pulse.signers_bitmap = signers_bitmap;
+
+        let (expected_pulse_account, bump_seed) = NebulaContract::pulse_address(program_id, stream, pulse.pulse_id);
+        if *pulse_account.key != expected_pulse_account {
+            return Err(NebulaError::InvalidPulseAccount.into());
+        }
+
+        Self::create_pda_account(
+            initializer,
+            pulse_account,
+            system_program_account,
+            rent_sysvar,
+            // oracles may rotate while the pulse is delivered, so it is sized for any oracle set
+            Pulse::packed_len(NebulaContract::MAX_ORACLES),
+            &[
+                stream.as_ref(),
+                &pulse.pulse_id.to_le_bytes(),
+                &[bump_seed],
+            ],
+            program_id,
+        )?;
+        pulse.pack_into_account(&mut pulse_account.try_borrow_mut_data()?)?;
 
-        msg!("data_hash(len): {:} \n", &data_hash.len());
-        msg!("data_hash: {:?} \n", &data_hash);
+        // the rent of an expired pulse goes back to the oracle
+        if let Ok(expired_pulse_account) = next_account_info(account_info_iter) {
+            let expired_pulse = Pulse::unpack_from_account(&expired_pulse_account.try_borrow_data()?)?;
+            Self::load_pulse(stream, expired_pulse_account, expired_pulse.pulse_id, program_id)?;
 
-        nebula_contract_info.add_pulse(data_hash, nebula_contract_info.last_pulse_id)?;
+            if !nebula_contract_info.is_pulse_expired(expired_pulse.pulse_id) {
+                return Err(NebulaError::InvalidPulseAccount.into());
+            }
+
+            msg!("closing expired pulse");
+            Self::close_account(expired_pulse_account, initializer)?;
+        }
+
+        Self::store_feed(&mut nebula_contract_info, feed)?;
 
         NebulaContract::pack(
             nebula_contract_info,
@@ -192,10 +744,11 @@ impl NebulaProcessor {
     pub fn process_nebula_send_value_to_subs(
         accounts: &[AccountInfo],
         data_value: &Vec<u8>,
-        _data_type: &DataType,
+        data_type: &DataType,
         pulse_id: &PulseID,
         subscription_id: &SubscriptionID,
-        _program_id: &Pubkey,
+        feed_id: FeedID,
+        program_id: &Pubkey,
     ) -> ProgramResult {
         // let _accounts_copy = accounts.clone();
         let account_info_iter = &mut accounts.iter();
@@ -211,103 +764,135 @@ impl NebulaProcessor {
             &nebula_contract_account.try_borrow_data()?[0..NebulaContract::LEN],
         )?;
 
+        let feed = Self::load_feed(
+            &mut nebula_contract_info,
+            nebula_contract_account,
+            account_info_iter,
+            feed_id,
+            program_id,
+        )?;
+        let stream = Self::stream_key(nebula_contract_account, &feed);
+
+        if *data_type != nebula_contract_info.data_type {
+            return Err(NebulaError::DataTypeMismatch.into());
+        }
+
         let nebula_contract_multisig_account = next_account_info(account_info_iter)?;
-        // let _nebula_contract_multisig_account_pubkey = nebula_contract_info.multisig_account;
 
         msg!("checking multisig bft count");
 
-        let nebula_multisig_info =
-            Multisig::unpack(&nebula_contract_multisig_account.try_borrow_data()?)?;
-
-        NebulaContract::validate_data_provider(
-            &nebula_multisig_info.signers.to_vec(),
+        let nebula_multisig_signers = Self::authorize_data_provider(
+            &nebula_contract_info,
+            nebula_contract_multisig_account,
             initializer.key,
         )?;
 
-        match nebula_contract_info.send_value_to_subs(pulse_id, subscription_id) {
-            Ok(subscription) => {
+        let pulse_account = next_account_info(account_info_iter)?;
+        let mut pulse = Self::load_pulse(stream, pulse_account, *pulse_id, program_id)?;
+        nebula_contract_info.validate_pulse(&pulse, data_value)?;
 
-                let destination_program_id = subscription.contract_address;
+        let subscription_account = next_account_info(account_info_iter)?;
+        let mut subscription = Self::load_subscription(stream, subscription_account, subscription_id, program_id)?;
 
-                // TOKEN - spl_token::id()
-                let target_program_id = next_account_info(account_info_iter)?;
+        nebula_contract_info.validate_delivery(&pulse, &subscription)?;
 
-                // IB Port Binary
-                let subscriber_contract_program_id = next_account_info(account_info_iter)?;
+        if subscription.is_active {
+            let confirmations = pulse.confirm(initializer.key, Pulse::capacity(pulse_account.data_len()))?;
 
-                // IB Port Data Account
-                let ibport_data_account = next_account_info(account_info_iter)?;
+            if confirmations < subscription.min_confirmations as usize {
+                msg!(
+                    "pulse confirmed {:} of {:} times, waiting for other oracles",
+                    confirmations,
+                    subscription.min_confirmations
+                );
 
-                let mint = next_account_info(account_info_iter)?;
-                let recipient_account = next_account_info(account_info_iter)?;
-                let pda_account = next_account_info(account_info_iter)?;
+                pulse.pack_into_account(&mut pulse_account.try_borrow_mut_data()?)?;
 
-                let additional_data_accounts = &accounts[9..].to_vec().clone();
-                // let mut additional_data_account_pubkeys = vec![];
+                return Ok(());
+            }
 
-                // for additional_data_account in additional_data_accounts {
-                //     additional_data_account_pubkeys.push(additional_data_account.key);
-                // }
+            let subscriber_program = next_account_info(account_info_iter)?;
+
+            // the rest are forwarded to the subscriber as declared on subscribe
+            let subscriber_accounts = account_info_iter.as_slice();
+            subscription.validate_subscriber(
+                subscriber_program.key,
+                subscriber_program.executable,
+                subscriber_accounts.first().map(|data_account| data_account.owner),
+            )?;
+
+            let subscriber_account_metas: Vec<AccountMeta> = subscriber_accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: *account.key,
+                    is_signer: false,
+                    is_writable: account.is_writable,
+                })
+                .collect();
+            subscription.validate_accounts(&subscriber_account_metas)?;
+
+            nebula_contract_info.charge_reward(
+                &mut subscription,
+                initializer.key,
+                &nebula_multisig_signers,
+            )?;
+
+            let attached_data = append_pulse_time(data_value, pulse.slot, pulse.unix_timestamp);
+
+            let instruction = attach_value_to_subscriber(
+                subscriber_program.key,
+                initializer.key,
+                &attached_data,
+                &subscriber_account_metas,
+            );
+
+            let mut cross_program_accounts = Vec::with_capacity(2 + subscriber_accounts.len());
+            cross_program_accounts.push(initializer.clone());
+            cross_program_accounts.extend_from_slice(subscriber_accounts);
+            cross_program_accounts.push(subscriber_program.clone());
+
+            invoke_signed(
+                &instruction,
+                cross_program_accounts.as_slice(),
+                &[&[
+                    PDAResolver::Gravity.bump_seeds(),
+                ]]
+            )?;
+        } else {
+            // the cancelled subscription still counts in the pulse, so it is skipped as served
+            msg!("skipping cancelled subscription");
+        }
 
-                if *pda_account.key != destination_program_id {
-                    return Err(NebulaError::InvalidSubscriptionProgramID.into());
-                }
+        subscription.last_delivered_pulse_id = pulse.pulse_id;
 
-                let instruction = attach_value(
-                    &data_value,
-                    &initializer.key,
-                    &ibport_data_account.key,
-                    &subscriber_contract_program_id.key,
-                    target_program_id.key, // &spl_token::id(),
-                    &mint.key,
-                    &recipient_account.key,
-                    &pda_account.key,
-                    &[],
-                    &additional_data_accounts,
-                )?;
-
-                let mut cross_program_accounts = vec![
-                    initializer.clone(),
-                    ibport_data_account.clone(),
-                    subscriber_contract_program_id.clone(),
-                    mint.clone(),
-                    recipient_account.clone(),
-                    pda_account.clone(),
-                ];
-
-                for additional_account_info in additional_data_accounts {
-                    msg!("left acc: {:?} \n", additional_account_info.key);
-                    cross_program_accounts.push(additional_account_info.clone());
-                }
-
-                invoke_signed(
-                    &instruction,
-                    cross_program_accounts.as_slice(),
-                    &[&[
-                        PDAResolver::Gravity.bump_seeds(),
-                    ]]
-                )?;
-
-                nebula_contract_info.drop_processed_pulse(data_value)?;
-
-                NebulaContract::pack(
-                    nebula_contract_info,
-                    &mut nebula_contract_account.try_borrow_mut_data()?[0..NebulaContract::LEN],
-                )?;
-
-                Ok(())
-            },
-            Err(err) => Err(err.into())
+        // the pulse is dropped once served to every subscription, its rent goes to the delivering oracle
+        if pulse.mark_served() {
+            Self::close_account(pulse_account, initializer)?;
+        } else {
+            pulse.pack_into_account(&mut pulse_account.try_borrow_mut_data()?)?;
         }
+
+        subscription.pack_into_account(&mut subscription_account.try_borrow_mut_data()?)?;
+
+        Self::store_feed(&mut nebula_contract_info, feed)?;
+
+        NebulaContract::pack(
+            nebula_contract_info,
+            &mut nebula_contract_account.try_borrow_mut_data()?[0..NebulaContract::LEN],
+        )?;
+
+        Ok(())
     }
 
     pub fn process_nebula_subscription(
         accounts: &[AccountInfo],
         subscriber_address: Pubkey,
         min_confirmations: u8,
-        reward: u64,
+        reward: U256,
         subscription_id: SubscriptionID,
-        _program_id: &Pubkey,
+        subscriber_accounts: Vec<SubscriberAccount>,
+        feed_id: FeedID,
+        program_id: &Pubkey,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
 
@@ -322,19 +907,195 @@ impl NebulaProcessor {
             &nebula_contract_account.try_borrow_data()?[0..NebulaContract::LEN],
         )?;
 
-        msg!("generating subscription id");
+        let feed = Self::load_feed(
+            &mut nebula_contract_info,
+            nebula_contract_account,
+            account_info_iter,
+            feed_id,
+            program_id,
+        )?;
+        let stream = Self::stream_key(nebula_contract_account, &feed);
+
+        let subscription_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let rent_sysvar = next_account_info(account_info_iter)?;
+
+        let (expected_subscription_account, bump_seed) =
+            NebulaContract::subscription_address(program_id, stream, &subscription_id);
+        if *subscription_account.key != expected_subscription_account {
+            return Err(NebulaError::InvalidSubscriptionAccount.into());
+        }
+
+        // an approach to avoid collision
+        if subscription_account.lamports() > 0 {
+            return Err(NebulaError::SubscribeFailed.into());
+        }
+
         msg!("subscribing");
 
-        nebula_contract_info.subscribe(
+        let subscription = nebula_contract_info.subscribe(
             *initializer.key,
             subscriber_address,
             min_confirmations,
             reward,
-            &subscription_id,
+            subscriber_accounts,
         )?;
 
+        Self::create_pda_account(
+            initializer,
+            subscription_account,
+            system_program_account,
+            rent_sysvar,
+            Subscription::packed_len(subscription.accounts.len()),
+            &[
+                stream.as_ref(),
+                &subscription_id,
+                &[bump_seed],
+            ],
+            program_id,
+        )?;
+        subscription.pack_into_account(&mut subscription_account.try_borrow_mut_data()?)?;
+
+        if subscription.balance > 0 {
+            msg!("escrowing reward");
+            Self::deposit_reward(
+                &nebula_contract_info,
+                nebula_contract_account,
+                initializer,
+                account_info_iter,
+                subscription.balance,
+                program_id,
+            )?;
+        }
+
         msg!("successfully subscribed!");
 
+        Self::store_feed(&mut nebula_contract_info, feed)?;
+
+        NebulaContract::pack(
+            nebula_contract_info,
+            &mut nebula_contract_account.try_borrow_mut_data()?[0..NebulaContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn process_nebula_unsubscription(
+        accounts: &[AccountInfo],
+        subscription_id: SubscriptionID,
+        feed_id: FeedID,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let nebula_contract_account = next_account_info(account_info_iter)?;
+
+        let mut nebula_contract_info = NebulaContract::unpack(
+            &nebula_contract_account.try_borrow_data()?[0..NebulaContract::LEN],
+        )?;
+
+        let feed = Self::load_feed(
+            &mut nebula_contract_info,
+            nebula_contract_account,
+            account_info_iter,
+            feed_id,
+            program_id,
+        )?;
+        let stream = Self::stream_key(nebula_contract_account, &feed);
+
+        let subscription_account = next_account_info(account_info_iter)?;
+        let mut subscription = Self::load_subscription(stream, subscription_account, &subscription_id, program_id)?;
+
+        msg!("unsubscribing");
+
+        // the balance is refunded once, when the subscription is cancelled
+        let refund = if subscription.is_active { subscription.balance } else { 0 };
+        let can_close = nebula_contract_info.unsubscribe(initializer.key, &mut subscription)?;
+
+        if refund > 0 {
+            msg!("refunding escrowed reward");
+            Self::withdraw_reward(
+                &nebula_contract_info,
+                nebula_contract_account,
+                initializer,
+                account_info_iter,
+                refund,
+                program_id,
+            )?;
+            subscription.balance = 0;
+        }
+
+        if can_close {
+            Self::close_account(subscription_account, initializer)?;
+        } else {
+            msg!("keeping the cancelled subscription until its pending pulses are served");
+            subscription.pack_into_account(&mut subscription_account.try_borrow_mut_data()?)?;
+        }
+
+        msg!("successfully unsubscribed!");
+
+        Self::store_feed(&mut nebula_contract_info, feed)?;
+
+        NebulaContract::pack(
+            nebula_contract_info,
+            &mut nebula_contract_account.try_borrow_mut_data()?[0..NebulaContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn process_nebula_top_up_subscription(
+        accounts: &[AccountInfo],
+        subscription_id: SubscriptionID,
+        amount: u64,
+        feed_id: FeedID,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let nebula_contract_account = next_account_info(account_info_iter)?;
+
+        let mut nebula_contract_info = NebulaContract::unpack(
+            &nebula_contract_account.try_borrow_data()?[0..NebulaContract::LEN],
+        )?;
+
+        let feed = Self::load_feed(
+            &mut nebula_contract_info,
+            nebula_contract_account,
+            account_info_iter,
+            feed_id,
+            program_id,
+        )?;
+        let stream = Self::stream_key(nebula_contract_account, &feed);
+
+        let subscription_account = next_account_info(account_info_iter)?;
+        let mut subscription = Self::load_subscription(stream, subscription_account, &subscription_id, program_id)?;
+
+        nebula_contract_info.top_up(&mut subscription, amount)?;
+
+        Self::deposit_reward(
+            &nebula_contract_info,
+            nebula_contract_account,
+            initializer,
+            account_info_iter,
+            amount,
+            program_id,
+        )?;
+
+        subscription.pack_into_account(&mut subscription_account.try_borrow_mut_data()?)?;
+
+        Self::store_feed(&mut nebula_contract_info, feed)?;
+
         NebulaContract::pack(
             nebula_contract_info,
             &mut nebula_contract_account.try_borrow_mut_data()?[0..NebulaContract::LEN],
@@ -343,6 +1104,167 @@ impl NebulaProcessor {
         Ok(())
     }
 
+    pub fn process_nebula_claim_reward(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let nebula_contract_account = next_account_info(account_info_iter)?;
+
+        let mut nebula_contract_info = NebulaContract::unpack(
+            &nebula_contract_account.try_borrow_data()?[0..NebulaContract::LEN],
+        )?;
+
+        let amount = nebula_contract_info.take_reward(initializer.key)?;
+
+        Self::withdraw_reward(
+            &nebula_contract_info,
+            nebula_contract_account,
+            initializer,
+            account_info_iter,
+            amount,
+            program_id,
+        )?;
+
+        NebulaContract::pack(
+            nebula_contract_info,
+            &mut nebula_contract_account.try_borrow_mut_data()?[0..NebulaContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn process_nebula_configure_rewards(
+        accounts: &[AccountInfo],
+        reward_mint: Pubkey,
+        reward_distribution: RewardDistribution,
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let nebula_contract_account = next_account_info(account_info_iter)?;
+
+        let mut nebula_contract_info = NebulaContract::unpack(
+            &nebula_contract_account.try_borrow_data()?[0..NebulaContract::LEN],
+        )?;
+
+        if nebula_contract_info.initializer_pubkey != *initializer.key {
+            return Err(NebulaError::RewardsConfigurationAccessDenied.into());
+        }
+
+        nebula_contract_info.configure_rewards(reward_mint, reward_distribution)?;
+
+        NebulaContract::pack(
+            nebula_contract_info,
+            &mut nebula_contract_account.try_borrow_mut_data()?[0..NebulaContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn process_nebula_configure_pulse_retention(
+        accounts: &[AccountInfo],
+        pulse_retention: u8,
+        _program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let nebula_contract_account = next_account_info(account_info_iter)?;
+
+        let mut nebula_contract_info = NebulaContract::unpack(
+            &nebula_contract_account.try_borrow_data()?[0..NebulaContract::LEN],
+        )?;
+
+        if nebula_contract_info.initializer_pubkey != *initializer.key {
+            return Err(NebulaError::PulseRetentionAccessDenied.into());
+        }
+
+        nebula_contract_info.set_pulse_retention(pulse_retention)?;
+
+        NebulaContract::pack(
+            nebula_contract_info,
+            &mut nebula_contract_account.try_borrow_mut_data()?[0..NebulaContract::LEN],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn process_nebula_create_feed(
+        accounts: &[AccountInfo],
+        feed_id: FeedID,
+        data_type: DataType,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let nebula_contract_account = next_account_info(account_info_iter)?;
+
+        let nebula_contract_info = NebulaContract::unpack(
+            &nebula_contract_account.try_borrow_data()?[0..NebulaContract::LEN],
+        )?;
+
+        if nebula_contract_account.owner != program_id || !nebula_contract_info.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        if nebula_contract_info.initializer_pubkey != *initializer.key {
+            return Err(NebulaError::FeedCreationAccessDenied.into());
+        }
+
+        let feed_account = next_account_info(account_info_iter)?;
+        let system_program_account = next_account_info(account_info_iter)?;
+        let rent_sysvar = next_account_info(account_info_iter)?;
+
+        let (expected_feed_account, bump_seed) =
+            NebulaContract::feed_address(program_id, nebula_contract_account.key, feed_id);
+
+        // an approach to avoid collision
+        if *feed_account.key != expected_feed_account || feed_account.lamports() > 0 {
+            return Err(NebulaError::InvalidFeedAccount.into());
+        }
+
+        let feed = nebula_contract_info.create_feed(feed_id, data_type)?;
+
+        Self::create_pda_account(
+            initializer,
+            feed_account,
+            system_program_account,
+            rent_sysvar,
+            Feed::LEN,
+            &[
+                nebula_contract_account.key.as_ref(),
+                NebulaContract::FEED_SEED,
+                &feed_id.to_le_bytes(),
+                &[bump_seed],
+            ],
+            program_id,
+        )?;
+        feed.pack_into_account(&mut feed_account.try_borrow_mut_data()?)?;
+
+        Ok(())
+    }
+
     pub fn process(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -356,6 +1278,7 @@ impl NebulaProcessor {
                 gravity_contract_program_id,
                 initial_oracles,
                 oracles_bft,
+                gravity_program_id,
             } => {
                 msg!("Instruction: Init Nebula Contract");
 
@@ -365,6 +1288,7 @@ impl NebulaProcessor {
                     &gravity_contract_program_id,
                     initial_oracles,
                     oracles_bft,
+                    &gravity_program_id,
                     program_id,
                 )
             }
@@ -381,16 +1305,35 @@ impl NebulaProcessor {
                     program_id,
                 )
             }
-            NebulaContractInstruction::SendHashValue { data_hash } => {
+            NebulaContractInstruction::SendHashValue { data_hash, feed_id } => {
                 msg!("Instruction: Send Hash Value");
 
-                Self::process_nebula_send_hash_value(accounts, data_hash, program_id)
+                Self::process_nebula_send_hash_value(accounts, data_hash, feed_id, program_id)
+            }
+            NebulaContractInstruction::UpdateOraclesWithConsuls {
+                new_round,
+                new_oracles,
+            } => {
+                msg!("Instruction: Update Nebula Oracles With Consuls");
+
+                Self::process_update_nebula_oracles_with_consuls(
+                    accounts,
+                    new_round,
+                    new_oracles,
+                    program_id,
+                )
+            }
+            NebulaContractInstruction::SendHashValueWithSignatures { data_hash, feed_id } => {
+                msg!("Instruction: Send Hash Value With Signatures");
+
+                Self::process_nebula_send_hash_value_with_signatures(accounts, data_hash, feed_id, program_id)
             }
             NebulaContractInstruction::SendValueToSubs {
                 data_value,
                 data_type,
                 pulse_id,
                 subscription_id,
+                feed_id,
             } => {
                 msg!("Instruction: Send Value To Subs");
 
@@ -400,6 +1343,7 @@ impl NebulaProcessor {
                     &data_type,
                     &pulse_id,
                     &subscription_id,
+                    feed_id,
                     program_id,
                 )
             }
@@ -408,6 +1352,8 @@ impl NebulaProcessor {
                 min_confirmations,
                 reward,
                 subscription_id,
+                subscriber_accounts,
+                feed_id,
             } => {
                 msg!("Instruction: Subscribe To Nebula");
 
@@ -417,10 +1363,307 @@ impl NebulaProcessor {
                     min_confirmations,
                     reward,
                     subscription_id,
+                    subscriber_accounts,
+                    feed_id,
+                    program_id,
+                )
+            }
+            NebulaContractInstruction::Unsubscribe { subscription_id, feed_id } => {
+                msg!("Instruction: Unsubscribe From Nebula");
+
+                Self::process_nebula_unsubscription(accounts, subscription_id, feed_id, program_id)
+            }
+            NebulaContractInstruction::TopUpSubscription {
+                subscription_id,
+                amount,
+                feed_id,
+            } => {
+                msg!("Instruction: Top Up Nebula Subscription");
+
+                Self::process_nebula_top_up_subscription(
+                    accounts,
+                    subscription_id,
+                    amount,
+                    feed_id,
+                    program_id,
+                )
+            }
+            NebulaContractInstruction::ClaimReward => {
+                msg!("Instruction: Claim Nebula Reward");
+
+                Self::process_nebula_claim_reward(accounts, program_id)
+            }
+            NebulaContractInstruction::ConfigureRewards {
+                reward_mint,
+                reward_distribution,
+            } => {
+                msg!("Instruction: Configure Nebula Rewards");
+
+                Self::process_nebula_configure_rewards(
+                    accounts,
+                    reward_mint,
+                    reward_distribution,
                     program_id,
                 )
             }
-            _ => Err(GravityError::InvalidInstruction.into()),
+            NebulaContractInstruction::ConfigurePulseRetention { pulse_retention } => {
+                msg!("Instruction: Configure Nebula Pulse Retention");
+
+                Self::process_nebula_configure_pulse_retention(accounts, pulse_retention, program_id)
+            }
+            NebulaContractInstruction::CreateFeed { feed_id, data_type } => {
+                msg!("Instruction: Create Nebula Feed");
+
+                Self::process_nebula_create_feed(accounts, feed_id, data_type, program_id)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gravity_misc::test_support::{process_with_signers, TestAccount};
+    use spl_token::state::Multisig;
+
+    fn new_oracles(count: usize) -> Vec<Pubkey> {
+        (0..count).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    #[test]
+    fn test_multisig_follows_oracles_rotation() {
+        let program_id = Pubkey::new_unique();
+        let mut nebula = TestAccount::new(Pubkey::new_unique(), NebulaContract::LEN, program_id, false);
+        let mut multisig = TestAccount::new(Pubkey::new_unique(), Multisig::LEN, program_id, false);
+
+        let initial_oracles = new_oracles(3);
+        let init_data = NebulaContractInstruction::InitContract {
+            nebula_data_type: DataType::Int64,
+            gravity_contract_program_id: Pubkey::new_unique(),
+            initial_oracles: initial_oracles.clone(),
+            oracles_bft: 3,
+            gravity_program_id: Pubkey::new_unique(),
+        }
+        .pack();
+        process_with_signers(NebulaProcessor::process, &program_id, &mut nebula, &mut multisig, &[], &init_data).unwrap();
+
+        // the first oracle is rotated out
+        let mut next_oracles = initial_oracles[1..].to_vec();
+        next_oracles.push(Pubkey::new_unique());
+        let update_data = NebulaContractInstruction::UpdateOracles {
+            new_oracles: next_oracles.clone(),
+            new_round: 1,
+        }
+        .pack();
+        process_with_signers(NebulaProcessor::process, &program_id, &mut nebula, &mut multisig, &initial_oracles, &update_data)
+            .unwrap();
+
+        let nebula_contract_info = NebulaContract::unpack(&nebula.data).unwrap();
+        assert_eq!(nebula_contract_info.oracles, next_oracles);
+
+        let removed_oracle = initial_oracles[0];
+        let send_value_data = NebulaContractInstruction::SendValueToSubs {
+            data_value: 1i64.to_le_bytes().to_vec(),
+            data_type: DataType::Int64,
+            pulse_id: 1,
+            subscription_id: [0; 16],
+            feed_id: NebulaContract::DEFAULT_FEED_ID,
+        }
+        .pack();
+        let mut oracle = TestAccount::signer(removed_oracle);
+        let accounts = vec![oracle.info(), nebula.info(), multisig.info()];
+        assert_eq!(
+            NebulaProcessor::process(&program_id, &accounts, &send_value_data).unwrap_err(),
+            NebulaError::DataProviderForSendValueToSubsIsInvalid.into()
+        );
+
+        assert!(NebulaProcessor::authorize_data_provider(
+            &nebula_contract_info,
+            &multisig.info(),
+            &removed_oracle
+        )
+        .is_err());
+        let signers =
+            NebulaProcessor::authorize_data_provider(&nebula_contract_info, &multisig.info(), &next_oracles[2])
+                .unwrap();
+        assert_eq!(signers, next_oracles);
+
+        // the previous oracles can't rotate the set anymore
+        let update_data = NebulaContractInstruction::UpdateOracles {
+            new_oracles: new_oracles(3),
+            new_round: 2,
+        }
+        .pack();
+        assert!(process_with_signers(NebulaProcessor::process, &program_id, &mut nebula, &mut multisig, &initial_oracles[0..1], &update_data)
+            .is_err());
+    }
+
+    #[test]
+    fn test_cancelled_subscription_is_skipped() {
+        let program_id = Pubkey::new_unique();
+        let mut nebula = TestAccount::new(Pubkey::new_unique(), NebulaContract::LEN, program_id, false);
+        let mut multisig = TestAccount::new(Pubkey::new_unique(), Multisig::LEN, program_id, false);
+
+        let oracles = new_oracles(3);
+        let init_data = NebulaContractInstruction::InitContract {
+            nebula_data_type: DataType::Int64,
+            gravity_contract_program_id: Pubkey::new_unique(),
+            initial_oracles: oracles.clone(),
+            oracles_bft: 3,
+            gravity_program_id: Pubkey::new_unique(),
+        }
+        .pack();
+        process_with_signers(NebulaProcessor::process, &program_id, &mut nebula, &mut multisig, &[], &init_data).unwrap();
+
+        // the subscription is cancelled after the pulse has been sent
+        let sender = Pubkey::new_unique();
+        let data_value = 1i64.to_le_bytes().to_vec();
+        let mut nebula_contract_info = NebulaContract::unpack(&nebula.data).unwrap();
+        let mut subscription = nebula_contract_info
+            .subscribe(sender, Pubkey::new_unique(), 1, [0; 32], vec![])
+            .unwrap();
+        let pulse = nebula_contract_info
+            .add_pulse(solana_program::hash::hash(&data_value).to_bytes().to_vec(), 0, 0)
+            .unwrap();
+        assert!(!nebula_contract_info.unsubscribe(&sender, &mut subscription).unwrap());
+        NebulaContract::pack(nebula_contract_info, &mut nebula.data[0..NebulaContract::LEN]).unwrap();
+
+        let subscription_id = [7; 16];
+        let (pulse_key, _) = NebulaContract::pulse_address(&program_id, &nebula.key, pulse.pulse_id);
+        let (subscription_key, _) =
+            NebulaContract::subscription_address(&program_id, &nebula.key, &subscription_id);
+
+        let mut pulse_account = TestAccount::new(pulse_key, Pulse::packed_len(3), program_id, false);
+        pulse_account.lamports = 1;
+        pulse.pack_into_account(&mut pulse_account.data).unwrap();
+        let mut subscription_account =
+            TestAccount::new(subscription_key, Subscription::packed_len(0), program_id, false);
+        subscription.pack_into_account(&mut subscription_account.data).unwrap();
+
+        let send_value_data = NebulaContractInstruction::SendValueToSubs {
+            data_value,
+            data_type: DataType::Int64,
+            pulse_id: pulse.pulse_id,
+            subscription_id,
+            feed_id: NebulaContract::DEFAULT_FEED_ID,
+        }
+        .pack();
+        let mut oracle = TestAccount::signer(oracles[0]);
+        let accounts = vec![
+            oracle.info(),
+            nebula.info(),
+            multisig.info(),
+            pulse_account.info(),
+            subscription_account.info(),
+        ];
+        NebulaProcessor::process(&program_id, &accounts, &send_value_data).unwrap();
+        drop(accounts);
+
+        // the pulse counted only the cancelled subscription, so it is released
+        assert_eq!(pulse_account.lamports, 0);
+        assert_eq!(oracle.lamports, 1);
+
+        let subscription = Subscription::unpack_from_account(&subscription_account.data).unwrap();
+        assert_eq!(subscription.last_delivered_pulse_id, pulse.pulse_id);
+        assert!(!NebulaContract::unpack(&nebula.data).unwrap().has_pending_pulses(&subscription));
+    }
+
+    #[test]
+    fn test_rotation_rejects_foreign_multisig() {
+        let program_id = Pubkey::new_unique();
+        let mut nebula = TestAccount::new(Pubkey::new_unique(), NebulaContract::LEN, program_id, false);
+        let mut multisig = TestAccount::new(Pubkey::new_unique(), Multisig::LEN, program_id, false);
+
+        let mut nebula_contract_info = NebulaContract::default();
+        nebula_contract_info.is_state_initialized = true;
+        nebula_contract_info.multisig_account = Pubkey::new_unique();
+
+        let err = NebulaProcessor::apply_oracles_update(
+            &nebula.info(),
+            &multisig.info(),
+            nebula_contract_info,
+            1,
+            new_oracles(3),
+        )
+        .unwrap_err();
+        assert_eq!(err, TokenError::OwnerMismatch.into());
+    }
+
+    #[test]
+    fn test_oracles_limit() {
+        let program_id = Pubkey::new_unique();
+        let mut nebula = TestAccount::new(Pubkey::new_unique(), NebulaContract::LEN, program_id, false);
+        let mut multisig = TestAccount::new(Pubkey::new_unique(), Multisig::LEN, program_id, false);
+
+        // the init instruction carries as many oracles as the bft
+        let init_data = |oracles: Vec<Pubkey>| {
+            NebulaContractInstruction::InitContract {
+                nebula_data_type: DataType::Int64,
+                gravity_contract_program_id: Pubkey::new_unique(),
+                oracles_bft: oracles.len() as u8,
+                initial_oracles: oracles,
+                gravity_program_id: Pubkey::new_unique(),
+            }
+            .pack()
+        };
+
+        assert_eq!(
+            process_with_signers(NebulaProcessor::process, &program_id, &mut nebula, &mut multisig, &[], &init_data(new_oracles(65)))
+                .unwrap_err(),
+            NebulaError::TooManyOracles.into()
+        );
+
+        let oracles = new_oracles(3);
+        process_with_signers(NebulaProcessor::process, &program_id, &mut nebula, &mut multisig, &[], &init_data(oracles.clone())).unwrap();
+
+        let nebula_contract_info = NebulaContract::unpack(&nebula.data).unwrap();
+        let err = NebulaProcessor::apply_oracles_update(
+            &nebula.info(),
+            &multisig.info(),
+            nebula_contract_info,
+            1,
+            new_oracles(NebulaContract::MAX_ORACLES + 1),
+        )
+        .unwrap_err();
+        assert_eq!(err, NebulaError::TooManyOracles.into());
+    }
+
+    #[test]
+    fn test_rotation_below_bft() {
+        let program_id = Pubkey::new_unique();
+        let mut nebula = TestAccount::new(Pubkey::new_unique(), NebulaContract::LEN, program_id, false);
+        let mut multisig = TestAccount::new(Pubkey::new_unique(), Multisig::LEN, program_id, false);
+
+        let oracles = new_oracles(3);
+        let init_data = NebulaContractInstruction::InitContract {
+            nebula_data_type: DataType::Int64,
+            gravity_contract_program_id: Pubkey::new_unique(),
+            initial_oracles: oracles.clone(),
+            oracles_bft: 3,
+            gravity_program_id: Pubkey::new_unique(),
+        }
+        .pack();
+        process_with_signers(NebulaProcessor::process, &program_id, &mut nebula, &mut multisig, &[], &init_data).unwrap();
+
+        // two oracles can't reach the bft of three
+        let update_data = NebulaContractInstruction::UpdateOracles {
+            new_oracles: new_oracles(2),
+            new_round: 1,
         }
+        .pack();
+        assert_eq!(
+            process_with_signers(NebulaProcessor::process, &program_id, &mut nebula, &mut multisig, &oracles, &update_data).unwrap_err(),
+            GravityError::InvalidNewBFT.into()
+        );
+
+        let nebula_contract_info = NebulaContract::unpack(&nebula.data).unwrap();
+        assert_eq!(nebula_contract_info.oracles, oracles);
+        assert_eq!(MiscProcessor::unpack_multisig(&multisig.info()).unwrap(), (oracles, 3));
+
+        // the spl-token multisig rejects a threshold above its signers on its own
+        assert_eq!(
+            MiscProcessor::process_update_multisig(&multisig.info(), &new_oracles(2), 3).unwrap_err(),
+            TokenError::InvalidNumberOfRequiredSigners.into()
+        );
     }
 }
@@ -1,7 +1,6 @@
-
-
-
 use solana_program::{
+    clock::{Slot, UnixTimestamp},
+    instruction::AccountMeta,
     msg,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
@@ -9,32 +8,189 @@ use solana_program::{
 };
 
 use gravity_misc::model::{
-    AbstractRecordHandler, RecordHandler, DataType, PulseID, SubscriptionID
+    u256_to_u64, AbstractRecordHandler, RecordHandler, DataType, FeedID, PulseID, SubscriptionID, U256,
 };
+use gravity_misc::ports::instruction::SubscriberAccount;
 use gravity_misc::validation::validate_pubkey_match;
 
-use solana_gravity_contract::gravity::state::PartialStorage;
+use solana_gravity_contract::gravity::state::{AccountStorage, PartialStorage};
 
 use crate::nebula::error::NebulaError;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 
-#[derive(BorshSerialize, BorshDeserialize, PartialEq, Default, Debug, Clone, Copy)]
+// stored in the PDA derived from the nebula address and the subscription id
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Default, Debug, Clone)]
 pub struct Subscription {
+    pub is_initialized: bool,
     pub sender: Pubkey,
     pub contract_address: Pubkey,
     pub min_confirmations: u8,
-    pub reward: u64, // should be 2^256
+    // paid per delivered value
+    pub reward: U256,
+    // escrowed amount left, in lamports or reward mint units
+    pub balance: u64,
+    // only pulses sent after the subscription are delivered, each of them once
+    pub subscribed_at_pulse_id: PulseID,
+    pub last_delivered_pulse_id: PulseID,
+    // accounts passed to the subscriber program on AttachValue, after the oracle
+    pub accounts: Vec<SubscriberAccount>,
+    // a cancelled subscription is kept until the pulses counting it have been served or expired
+    pub is_active: bool,
+    pub unsubscribed_at_pulse_id: PulseID,
 }
 
+impl Subscription {
+    pub fn packed_len(accounts_count: usize) -> usize {
+        1 + 32 + 32 + 1 + 32 + 8 + 8 + 8 + 4 + SubscriberAccount::LEN * accounts_count + 1 + 8
+    }
+
+    // the invoked program has to be the subscribed one, the first subscriber account is its data account
+    pub fn validate_subscriber(
+        &self,
+        program_id: &Pubkey,
+        program_executable: bool,
+        data_account_owner: Option<&Pubkey>,
+    ) -> Result<(), NebulaError> {
+        if *program_id != self.contract_address || !program_executable {
+            return Err(NebulaError::InvalidSubscriptionProgramID);
+        }
+
+        match data_account_owner {
+            Some(owner) if *owner != self.contract_address => {
+                Err(NebulaError::InvalidSubscriberDataAccount)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // delivery accounts have to follow the declared ones in order, with the same writability
+    pub fn validate_accounts(&self, accounts: &[AccountMeta]) -> Result<(), NebulaError> {
+        if accounts.len() != self.accounts.len() {
+            return Err(NebulaError::InvalidSubscriberAccounts);
+        }
+
+        for (declared, account) in self.accounts.iter().zip(accounts.iter()) {
+            if !declared.matches(account) {
+                return Err(NebulaError::InvalidSubscriberAccounts);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl AccountStorage for Subscription {}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy)]
+pub enum RewardDistribution {
+    // the whole reward goes to the oracle sending the value
+    DeliveringOracle,
+    // the reward is split equally across the multisig signers,
+    // the remainder goes to the delivering oracle
+    MultisigSigners,
+}
+
+impl Default for RewardDistribution {
+    fn default() -> Self {
+        RewardDistribution::DeliveringOracle
+    }
+}
+
+impl RewardDistribution {
+    pub fn cast_from(i: u8) -> Result<RewardDistribution, NebulaError> {
+        match i {
+            0 => Ok(RewardDistribution::DeliveringOracle),
+            1 => Ok(RewardDistribution::MultisigSigners),
+            _ => Err(NebulaError::InvalidRewardDistribution),
+        }
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            RewardDistribution::DeliveringOracle => 0,
+            RewardDistribution::MultisigSigners => 1,
+        }
+    }
+}
+
+// stored in the PDA derived from the nebula address and the pulse id
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Default, Debug, Clone)]
 pub struct Pulse {
+    pub is_initialized: bool,
+    pub pulse_id: PulseID,
     pub data_hash: Vec<u8>,
-    // pub height: u64,
+    // clock of the block the hash has been sent in
+    pub slot: Slot,
+    pub unix_timestamp: UnixTimestamp,
+    // subscriptions active when the pulse has been sent and the ones served so far
+    pub subscriptions_count: u64,
+    pub served_count: u64,
+    // bit i is set when `NebulaContract::oracles[i]` has approved the pulse hash
+    pub signers_bitmap: u64,
+    // distinct oracles that delivered the pulse value
+    pub confirmations: Vec<Pubkey>,
 }
 
+impl AccountStorage for Pulse {}
+
+impl Pulse {
+    pub const DATA_HASH_LEN: usize = 32;
+
+    // account size able to hold `max_confirmations` oracle confirmations
+    pub fn packed_len(max_confirmations: usize) -> usize {
+        1 + 8 + 4 + Self::DATA_HASH_LEN + 8 + 8 + 8 + 8 + 8 + 4 + 32 * max_confirmations
+    }
+
+    pub fn matches_value(&self, raw_data_value: &[u8]) -> bool {
+        self.data_hash == solana_program::hash::hash(raw_data_value).to_bytes()
+    }
+
+    // confirmations a pulse account of `account_len` bytes is able to hold
+    pub fn capacity(account_len: usize) -> usize {
+        account_len.saturating_sub(Self::packed_len(0)) / 32
+    }
+
+    // records the oracle confirmation of the pulse value and returns the confirmations count,
+    // an oracle delivering the pulse to several subscribers is counted once
+    pub fn confirm(&mut self, oracle: &Pubkey, capacity: usize) -> Result<usize, NebulaError> {
+        if !self.confirmations.contains(oracle) {
+            if self.confirmations.len() >= capacity {
+                return Err(NebulaError::PulseConfirmationsFull);
+            }
+            self.confirmations.push(*oracle);
+        }
+
+        Ok(self.confirmations.len())
+    }
+
+    // returns true once every subscription active at the pulse time has been served
+    pub fn mark_served(&mut self) -> bool {
+        self.served_count += 1;
+        self.served_count >= self.subscriptions_count
+    }
+}
+
+// pulse stream of a feed created by CreateFeed, stored in the PDA derived from the nebula address
+// and the feed id, the default feed stream lives in the nebula contract itself
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Default, Debug, Clone)]
+pub struct Feed {
+    pub is_initialized: bool,
+    pub feed_id: FeedID,
+    pub data_type: DataType,
+    pub last_pulse_id: PulseID,
+    pub subscriptions_count: u64,
+}
+
+impl Feed {
+    pub const LEN: usize = 1 + 4 + 1 + 8 + 8;
+}
+
+impl AccountStorage for Feed {}
+
 pub type NebulaQueue<T> = Vec<T>;
 
+// pulses and subscriptions live in their own PDAs, the contract keeps only the header
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Default, Debug, Clone)]
 pub struct NebulaContract {
     pub oracles: Vec<Pubkey>,
@@ -46,13 +202,24 @@ pub struct NebulaContract {
     pub last_round: PulseID,
 
     pub last_pulse_id: PulseID,
-
-    subscriptions_map: RecordHandler<SubscriptionID, Subscription>,
-
-    pulses_map: RecordHandler<Pulse, PulseID>,
+    pub subscriptions_count: u64,
 
     pub is_state_initialized: bool,
     pub initializer_pubkey: Pubkey,
+
+    // default pubkey means rewards are escrowed in lamports
+    pub reward_mint: Pubkey,
+    pub reward_distribution: RewardDistribution,
+    // rewards credited to oracles and not claimed yet
+    rewards: RecordHandler<Pubkey, u64>,
+    // subscription balances and credited rewards held by the escrow
+    pub escrowed_amount: u64,
+
+    // count of the latest pulses deliverable, zero means DEFAULT_PULSE_RETENTION
+    pub pulse_retention: u8,
+
+    // owner of the `gravity_contract` data account, the consuls of which may rotate the oracles
+    pub gravity_program: Pubkey,
 }
 
 impl PartialStorage for NebulaContract {
@@ -85,59 +252,419 @@ impl Pack for NebulaContract {
         let data = self.try_to_vec().unwrap();
         dst[..data.len()].copy_from_slice(&data);
     }
+
+    // the serialized contract is variable sized, so it is checked before being packed
+    fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if dst.len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let packed_len = src.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?.len();
+        if packed_len > dst.len() {
+            msg!("nebula contract takes {:} bytes out of {:}", packed_len, dst.len());
+            return Err(NebulaError::NebulaStorageIsFull.into());
+        }
+
+        src.pack_into_slice(dst);
+        Ok(())
+    }
 }
 
 impl NebulaContract {
-    pub fn add_pulse(
+    pub const DEFAULT_PULSE_RETENTION: u8 = 8;
+    pub const MAX_PULSE_RETENTION: u8 = 16;
+    // pulse approvals are tracked in a u64 bitmap, one bit per oracle
+    pub const MAX_ORACLES: usize = 64;
+
+    pub const REWARD_ESCROW_SEED: &'static [u8] = br"reward";
+    pub const FEED_SEED: &'static [u8] = br"feed";
+    pub const DEFAULT_FEED_ID: FeedID = 0;
+
+    // PDA holding escrowed lamports, or owning the escrow token account
+    pub fn reward_escrow_authority(program_id: &Pubkey, nebula_contract: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[nebula_contract.as_ref(), Self::REWARD_ESCROW_SEED],
+            program_id,
+        )
+    }
+
+    // pulses and subscriptions of a feed are derived from the feed address instead of the nebula one
+    pub fn feed_address(program_id: &Pubkey, nebula_contract: &Pubkey, feed_id: FeedID) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[nebula_contract.as_ref(), Self::FEED_SEED, &feed_id.to_le_bytes()],
+            program_id,
+        )
+    }
+
+    pub fn stream_address(program_id: &Pubkey, nebula_contract: &Pubkey, feed_id: FeedID) -> Pubkey {
+        match feed_id {
+            Self::DEFAULT_FEED_ID => *nebula_contract,
+            feed_id => Self::feed_address(program_id, nebula_contract, feed_id).0,
+        }
+    }
+
+    pub fn pulse_address(program_id: &Pubkey, nebula_contract: &Pubkey, pulse_id: PulseID) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[nebula_contract.as_ref(), &pulse_id.to_le_bytes()],
+            program_id,
+        )
+    }
+
+    pub fn subscription_address(
+        program_id: &Pubkey,
+        nebula_contract: &Pubkey,
+        subscription_id: &SubscriptionID,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[nebula_contract.as_ref(), subscription_id], program_id)
+    }
+
+    pub fn rewards_in_lamports(&self) -> bool {
+        self.reward_mint == Pubkey::default()
+    }
+
+    // the escrow asset can only be switched while nothing is escrowed
+    pub fn configure_rewards(
         &mut self,
-        data_hash: Vec<u8>,
-        last_pulse_id: u64,
+        reward_mint: Pubkey,
+        reward_distribution: RewardDistribution,
+    ) -> Result<(), NebulaError> {
+        if reward_mint != self.reward_mint && self.escrowed_amount > 0 {
+            return Err(NebulaError::RewardsConfigurationLocked);
+        }
+
+        self.reward_mint = reward_mint;
+        self.reward_distribution = reward_distribution;
+
+        Ok(())
+    }
+
+    pub fn top_up(
+        &mut self,
+        subscription: &mut Subscription,
+        amount: u64,
+    ) -> Result<(), NebulaError> {
+        if !subscription.is_active {
+            return Err(NebulaError::SubscriptionCancelled);
+        }
+
+        subscription.balance = subscription
+            .balance
+            .checked_add(amount)
+            .ok_or(NebulaError::RewardOverflow)?;
+        self.escrowed_amount = self
+            .escrowed_amount
+            .checked_add(amount)
+            .ok_or(NebulaError::RewardOverflow)?;
+
+        Ok(())
+    }
+
+    // takes the subscription reward out of its balance and credits it to the oracles
+    pub fn charge_reward(
+        &mut self,
+        subscription: &mut Subscription,
+        data_provider: &Pubkey,
+        multisig_signers: &[Pubkey],
     ) -> Result<(), NebulaError> {
-        let new_pulse_id = last_pulse_id + 1;
+        let reward = u256_to_u64(&subscription.reward).ok_or(NebulaError::RewardOverflow)?;
+        if reward == 0 {
+            return Ok(());
+        }
+
+        subscription.balance = subscription
+            .balance
+            .checked_sub(reward)
+            .ok_or(NebulaError::InsufficientRewardBalance)?;
+
+        match self.reward_distribution {
+            RewardDistribution::DeliveringOracle => self.credit_reward(data_provider, reward),
+            RewardDistribution::MultisigSigners => {
+                if multisig_signers.is_empty() {
+                    return self.credit_reward(data_provider, reward);
+                }
+
+                let share = reward / multisig_signers.len() as u64;
+                for signer in multisig_signers {
+                    self.credit_reward(signer, share)?;
+                }
+                self.credit_reward(data_provider, reward - share * multisig_signers.len() as u64)
+            }
+        }
+    }
 
-        self.pulses_map.insert(
-            Pulse {
-                data_hash,
-            },
-            new_pulse_id,
+    fn credit_reward(&mut self, oracle: &Pubkey, amount: u64) -> Result<(), NebulaError> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        // only the oracles and the initializer are credited, see prune_rewards
+        if !self.rewards.contains_key(oracle) && self.rewards.len() > Self::MAX_ORACLES {
+            return Err(NebulaError::NebulaStorageIsFull);
+        }
+
+        let credited = self.rewards.get(oracle).copied().unwrap_or(0);
+        self.rewards.insert(
+            *oracle,
+            credited.checked_add(amount).ok_or(NebulaError::RewardOverflow)?,
         );
 
+        Ok(())
+    }
+
+    pub fn reward_of(&self, oracle: &Pubkey) -> u64 {
+        self.rewards.get(oracle).copied().unwrap_or(0)
+    }
+
+    // Called once the oracles are rotated: drops empty records and hands the unclaimed rewards
+    // of rotated-out oracles over to the initializer, so the records stay bound by MAX_ORACLES
+    pub fn prune_rewards(&mut self) -> Result<(), NebulaError> {
+        let (oracles, initializer) = (&self.oracles, self.initializer_pubkey);
+        let dropped = self
+            .rewards
+            .retain(|oracle, amount| *amount > 0 && (oracles.contains(oracle) || *oracle == initializer));
+
+        let mut forfeited: u64 = 0;
+        for (_, amount) in dropped {
+            forfeited = forfeited.checked_add(amount).ok_or(NebulaError::RewardOverflow)?;
+        }
+        self.credit_reward(&initializer, forfeited)
+    }
+
+    // drops the credited reward and returns it
+    pub fn take_reward(&mut self, oracle: &Pubkey) -> Result<u64, NebulaError> {
+        match self.rewards.drop(oracle) {
+            Some(amount) if amount > 0 => {
+                self.escrowed_amount = self.escrowed_amount.saturating_sub(amount);
+                Ok(amount)
+            }
+            _ => Err(NebulaError::NoRewardToClaim),
+        }
+    }
+
+    // returns the next pulse, it is persisted by the caller
+    pub fn add_pulse(
+        &mut self,
+        data_hash: Vec<u8>,
+        slot: Slot,
+        unix_timestamp: UnixTimestamp,
+    ) -> Result<Pulse, NebulaError> {
+        let new_pulse_id = self
+            .last_pulse_id
+            .checked_add(1)
+            .ok_or(NebulaError::PulseValidationOrderMismatch)?;
+
         self.last_pulse_id = new_pulse_id;
 
+        Ok(Pulse {
+            is_initialized: true,
+            pulse_id: new_pulse_id,
+            data_hash,
+            slot,
+            unix_timestamp,
+            subscriptions_count: self.subscriptions_count,
+            served_count: 0,
+            signers_bitmap: 0,
+            confirmations: vec![],
+        })
+    }
+
+    // Payload that the oracles sign off-chain to approve the hash of the next pulse.
+    // Bound to the pulse stream (the nebula data account or the feed) and the pulse id, so it can't be replayed.
+    pub fn pulse_message(stream: &Pubkey, pulse_id: PulseID, data_hash: &[u8]) -> Vec<u8> {
+        let mut payload = stream.to_bytes().to_vec();
+        payload.extend_from_slice(&pulse_id.to_le_bytes());
+        payload.extend_from_slice(data_hash);
+
+        solana_program::hash::hash(&payload).to_bytes().to_vec()
+    }
+
+    // returns the new feed, it is persisted by the caller
+    pub fn create_feed(&self, feed_id: FeedID, data_type: DataType) -> Result<Feed, NebulaError> {
+        if feed_id == Self::DEFAULT_FEED_ID {
+            return Err(NebulaError::InvalidFeedAccount);
+        }
+
+        Ok(Feed {
+            is_initialized: true,
+            feed_id,
+            data_type,
+            last_pulse_id: 0,
+            subscriptions_count: 0,
+        })
+    }
+
+    // exchanges the pulse stream of the contract with the feed one, so pulses and subscriptions
+    // are processed against the feed; swapping again restores the contract stream
+    pub fn swap_feed(&mut self, feed: &mut Feed) {
+        std::mem::swap(&mut self.data_type, &mut feed.data_type);
+        std::mem::swap(&mut self.last_pulse_id, &mut feed.last_pulse_id);
+        std::mem::swap(&mut self.subscriptions_count, &mut feed.subscriptions_count);
+    }
+
+    // Payload that the Gravity consuls sign off-chain to authorize the oracles of the round.
+    // Bound to the nebula data account, so it can't be replayed against another nebula.
+    pub fn oracles_update_message(
+        nebula_contract: &Pubkey,
+        new_oracles: &[Pubkey],
+        new_round: PulseID,
+    ) -> Vec<u8> {
+        let mut payload = nebula_contract.to_bytes().to_vec();
+        payload.extend_from_slice(&new_round.to_le_bytes());
+        for oracle in new_oracles {
+            payload.extend_from_slice(&oracle.to_bytes());
+        }
+
+        solana_program::hash::hash(&payload).to_bytes().to_vec()
+    }
+
+    pub fn validate_oracles(oracles: &[Pubkey]) -> Result<(), NebulaError> {
+        if oracles.len() > Self::MAX_ORACLES {
+            return Err(NebulaError::TooManyOracles);
+        }
         Ok(())
     }
 
+    // bit i is set when `oracles[i]` is among the signers
+    pub fn oracles_bitmap(&self, signers: &[Pubkey]) -> u64 {
+        self.oracles
+            .iter()
+            .take(Self::MAX_ORACLES)
+            .enumerate()
+            .filter(|(_, oracle)| signers.contains(oracle))
+            .fold(0, |bitmap, (i, _)| bitmap | 1 << i)
+    }
+
+    // returns the signers bitmap once at least bft oracles have signed
+    pub fn validate_oracle_signatures(&self, signers: &[Pubkey]) -> Result<u64, NebulaError> {
+        let bitmap = self.oracles_bitmap(signers);
+
+        if bitmap.count_ones() < self.bft as u32 {
+            return Err(NebulaError::NotEnoughOracleSignatures);
+        }
+
+        Ok(bitmap)
+    }
+
+    pub fn pulse_retention_depth(&self) -> u64 {
+        match self.pulse_retention {
+            0 => Self::DEFAULT_PULSE_RETENTION as u64,
+            depth => depth as u64,
+        }
+    }
+
+    pub fn set_pulse_retention(&mut self, pulse_retention: u8) -> Result<(), NebulaError> {
+        if pulse_retention == 0 || pulse_retention > Self::MAX_PULSE_RETENTION {
+            return Err(NebulaError::InvalidPulseRetention);
+        }
+
+        self.pulse_retention = pulse_retention;
+
+        Ok(())
+    }
+
+    // pulses older than the retention depth can no longer be delivered and may be closed
+    pub fn is_pulse_expired(&self, pulse_id: PulseID) -> bool {
+        pulse_id.saturating_add(self.pulse_retention_depth()) <= self.last_pulse_id
+    }
+
+    pub fn validate_pulse(&self, pulse: &Pulse, raw_data_value: &[u8]) -> Result<(), NebulaError> {
+        if !pulse.is_initialized || !pulse.matches_value(raw_data_value) {
+            return Err(NebulaError::PulseIDHasNotBeenPersisted);
+        }
+
+        if self.is_pulse_expired(pulse.pulse_id) {
+            return Err(NebulaError::PulseExpired);
+        }
+
+        Ok(())
+    }
+
+    pub fn validate_delivery(&self, pulse: &Pulse, subscription: &Subscription) -> Result<(), NebulaError> {
+        if pulse.pulse_id <= subscription.subscribed_at_pulse_id {
+            return Err(NebulaError::PulseIsOlderThanSubscription);
+        }
+
+        if pulse.pulse_id <= subscription.last_delivered_pulse_id {
+            return Err(NebulaError::SubscriberValueBeenSent);
+        }
+
+        // pulses sent after the cancellation don't count the subscription
+        if !subscription.is_active && pulse.pulse_id > subscription.unsubscribed_at_pulse_id {
+            return Err(NebulaError::SubscriptionCancelled);
+        }
+
+        Ok(())
+    }
+
+    // pulses sent while the cancelled subscription was active, that are not yet served nor expired
+    pub fn has_pending_pulses(&self, subscription: &Subscription) -> bool {
+        let served_up_to = subscription
+            .subscribed_at_pulse_id
+            .max(subscription.last_delivered_pulse_id);
+
+        served_up_to < subscription.unsubscribed_at_pulse_id
+            && !self.is_pulse_expired(subscription.unsubscribed_at_pulse_id)
+    }
+
+    // only the original sender is allowed to unsubscribe, the escrowed balance is left for the refund;
+    // returns true once the subscription account can be closed
     pub fn unsubscribe(
         &mut self,
-        _subscription_id: &SubscriptionID,
-    ) -> Result<(), NebulaError> {
-        // Ok(())
-        Err(NebulaError::UnsubscribeIsNotAvailable)
+        sender: &Pubkey,
+        subscription: &mut Subscription,
+    ) -> Result<bool, NebulaError> {
+        if !subscription.is_initialized {
+            return Err(NebulaError::InvalidSubscriptionID);
+        }
+
+        if subscription.sender != *sender {
+            return Err(NebulaError::UnsubscribeAccessDenied);
+        }
+
+        if subscription.is_active {
+            self.subscriptions_count = self.subscriptions_count.saturating_sub(1);
+            self.escrowed_amount = self.escrowed_amount.saturating_sub(subscription.balance);
+
+            subscription.is_active = false;
+            subscription.unsubscribed_at_pulse_id = self.last_pulse_id;
+        }
+
+        Ok(!self.has_pending_pulses(subscription))
     }
 
+    // returns the new subscription, it is persisted by the caller
     pub fn subscribe(
         &mut self,
         sender: Pubkey,
         contract_address: Pubkey,
         min_confirmations: u8,
-        reward: u64,
-        subscription_id: &SubscriptionID,
-    ) -> Result<(), NebulaError> {
+        reward: U256,
+        accounts: Vec<SubscriberAccount>,
+    ) -> Result<Subscription, NebulaError> {
+        // the first reward is escrowed on subscribe
+        let balance = u256_to_u64(&reward).ok_or(NebulaError::RewardOverflow)?;
+
         let subscription = Subscription {
+            is_initialized: true,
             sender,
             contract_address,
             min_confirmations,
             reward,
+            balance,
+            subscribed_at_pulse_id: self.last_pulse_id,
+            last_delivered_pulse_id: 0,
+            accounts,
+            is_active: true,
+            unsubscribed_at_pulse_id: 0,
         };
 
-        // an approach to avoid collision
-        if self.subscriptions_map.contains_key(subscription_id) {
-            return Err(NebulaError::SubscribeFailed);
-        }
-
-        self.subscriptions_map.insert(*subscription_id, subscription);
+        self.escrowed_amount = self
+            .escrowed_amount
+            .checked_add(balance)
+            .ok_or(NebulaError::RewardOverflow)?;
+        self.subscriptions_count += 1;
 
-        Ok(())
+        Ok(subscription)
     }
 
     pub fn validate_data_provider(
@@ -156,41 +683,377 @@ impl NebulaContract {
             NebulaError::DataProviderForSendValueToSubsIsInvalid
         )
     }
+}
 
-    pub fn drop_processed_pulse(&mut self, raw_data_value: &Vec<u8>) -> Result<(), NebulaError> {
-        let sha256_hashed = solana_program::hash::hash(raw_data_value.clone().as_slice());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gravity_misc::model::u256_from_u64;
 
-        msg!("sha256_hashed: {:?} \n", &sha256_hashed);
-        msg!("sha256_hashed(bytes): {:?} \n", & sha256_hashed.to_bytes());
-        msg!("sha256_hashed(bytes.to_vec()): {:?} \n", & sha256_hashed.to_bytes().to_vec());
-        msg!("raw_data_value: {:?} \n", raw_data_value);
+    #[test]
+    fn test_unsubscribe() {
+        let mut nebula_contract = NebulaContract::default();
+        let (sender, contract_address) = (Pubkey::new_unique(), Pubkey::new_unique());
 
-        let pulse = &Pulse {
-            data_hash: sha256_hashed.to_bytes().to_vec(),
-        };
+        let mut subscription = nebula_contract
+            .subscribe(sender, contract_address, 1, u256_from_u64(10), vec![])
+            .unwrap();
+        assert_eq!(nebula_contract.subscriptions_count, 1);
+        assert_eq!(nebula_contract.escrowed_amount, 10);
+
+        assert!(matches!(
+            nebula_contract.unsubscribe(&Pubkey::new_unique(), &mut subscription),
+            Err(NebulaError::UnsubscribeAccessDenied)
+        ));
+        assert!(matches!(
+            nebula_contract.unsubscribe(&sender, &mut Subscription::default()),
+            Err(NebulaError::InvalidSubscriptionID)
+        ));
+
+        assert!(nebula_contract.unsubscribe(&sender, &mut subscription).unwrap());
+        assert_eq!(nebula_contract.subscriptions_count, 0);
+        assert_eq!(nebula_contract.escrowed_amount, 0);
+    }
 
-        match self.pulses_map.drop(pulse) {
-            Some(_) => Ok(()),
-            None => Err(NebulaError::PulseIDHasNotBeenPersisted),
+    #[test]
+    fn test_unsubscribe_with_pending_pulses() {
+        let mut nebula_contract = NebulaContract::default();
+        let sender = Pubkey::new_unique();
+
+        let mut subscription = nebula_contract
+            .subscribe(sender, Pubkey::new_unique(), 1, [0; 32], vec![])
+            .unwrap();
+        let mut pulse = nebula_contract.add_pulse(vec![0; 32], 0, 0).unwrap();
+
+        // the pulse still counts the cancelled subscription, so it is kept until skipped
+        assert!(!nebula_contract.unsubscribe(&sender, &mut subscription).unwrap());
+        assert!(!subscription.is_active);
+        assert_eq!(nebula_contract.subscriptions_count, 0);
+        assert!(matches!(
+            nebula_contract.top_up(&mut subscription, 1),
+            Err(NebulaError::SubscriptionCancelled)
+        ));
+
+        let next_pulse = nebula_contract.add_pulse(vec![0; 32], 0, 0).unwrap();
+        assert_eq!(next_pulse.subscriptions_count, 0);
+        assert!(matches!(
+            nebula_contract.validate_delivery(&next_pulse, &subscription),
+            Err(NebulaError::SubscriptionCancelled)
+        ));
+
+        nebula_contract.validate_delivery(&pulse, &subscription).unwrap();
+        subscription.last_delivered_pulse_id = pulse.pulse_id;
+        assert!(pulse.mark_served());
+
+        assert!(nebula_contract.unsubscribe(&sender, &mut subscription).unwrap());
+    }
+
+    #[test]
+    fn test_reward_escrow() {
+        let mut nebula_contract = NebulaContract::default();
+        let sender = Pubkey::new_unique();
+        let oracles = vec![Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let mut too_big = u256_from_u64(1);
+        too_big[8] = 1;
+        assert!(matches!(
+            nebula_contract.subscribe(sender, Pubkey::new_unique(), 1, too_big, vec![]),
+            Err(NebulaError::RewardOverflow)
+        ));
+
+        let mut subscription = nebula_contract
+            .subscribe(sender, Pubkey::new_unique(), 1, u256_from_u64(100), vec![])
+            .unwrap();
+
+        nebula_contract.charge_reward(&mut subscription, &oracles[0], &oracles).unwrap();
+        assert_eq!(nebula_contract.reward_of(&oracles[0]), 100);
+        assert!(matches!(
+            nebula_contract.charge_reward(&mut subscription, &oracles[0], &oracles),
+            Err(NebulaError::InsufficientRewardBalance)
+        ));
+
+        assert!(matches!(
+            nebula_contract.configure_rewards(Pubkey::new_unique(), RewardDistribution::MultisigSigners),
+            Err(NebulaError::RewardsConfigurationLocked)
+        ));
+        nebula_contract
+            .configure_rewards(Pubkey::default(), RewardDistribution::MultisigSigners)
+            .unwrap();
+
+        nebula_contract.top_up(&mut subscription, 100).unwrap();
+        nebula_contract.charge_reward(&mut subscription, &oracles[1], &oracles).unwrap();
+        assert_eq!(nebula_contract.reward_of(&oracles[0]), 133);
+        assert_eq!(nebula_contract.reward_of(&oracles[1]), 34);
+        assert_eq!(nebula_contract.reward_of(&oracles[2]), 33);
+
+        assert_eq!(nebula_contract.take_reward(&oracles[0]).unwrap(), 133);
+        assert_eq!(nebula_contract.escrowed_amount, 200 - 133);
+        assert!(matches!(
+            nebula_contract.take_reward(&oracles[0]),
+            Err(NebulaError::NoRewardToClaim)
+        ));
+    }
+
+    #[test]
+    fn test_rewards_pruned_on_rotation() {
+        let mut nebula_contract = NebulaContract::default();
+        nebula_contract.initializer_pubkey = Pubkey::new_unique();
+        nebula_contract.oracles = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let oracles = nebula_contract.oracles.clone();
+
+        let mut subscription = nebula_contract
+            .subscribe(Pubkey::new_unique(), Pubkey::new_unique(), 1, u256_from_u64(100), vec![])
+            .unwrap();
+        nebula_contract.top_up(&mut subscription, 100).unwrap();
+        nebula_contract.charge_reward(&mut subscription, &oracles[0], &oracles).unwrap();
+        nebula_contract.charge_reward(&mut subscription, &oracles[1], &oracles).unwrap();
+
+        // the first oracle is rotated out before claiming
+        nebula_contract.oracles = vec![oracles[1], Pubkey::new_unique()];
+        nebula_contract.prune_rewards().unwrap();
+
+        assert_eq!(nebula_contract.reward_of(&oracles[0]), 0);
+        assert_eq!(nebula_contract.reward_of(&oracles[1]), 100);
+        assert_eq!(nebula_contract.reward_of(&nebula_contract.initializer_pubkey), 100);
+        assert_eq!(nebula_contract.rewards.len(), 2);
+        assert_eq!(nebula_contract.escrowed_amount, 200);
+
+        // a record can't be added past the oracles and the initializer
+        for _ in nebula_contract.rewards.len()..=NebulaContract::MAX_ORACLES {
+            nebula_contract.credit_reward(&Pubkey::new_unique(), 1).unwrap();
         }
+        assert!(matches!(
+            nebula_contract.credit_reward(&Pubkey::new_unique(), 1),
+            Err(NebulaError::NebulaStorageIsFull)
+        ));
+        nebula_contract.credit_reward(&oracles[1], 1).unwrap();
     }
 
-    pub fn send_value_to_subs(
-        &mut self,
-        pulse_id: &PulseID,
-        subscription_id: &SubscriptionID,
-    ) -> Result<Subscription, ProgramError> {
-        let prev_pulse_id = self.last_pulse_id - 1;
-        msg!("prev_pulse_id: {:} \n", prev_pulse_id);
-        msg!("pulse_id: {:} \n", pulse_id);
-        
-        if *pulse_id != prev_pulse_id {
-            return Err(NebulaError::PulseValidationOrderMismatch.into());
+    #[test]
+    fn test_pulse_confirmations() {
+        let mut nebula_contract = NebulaContract::default();
+        let oracles = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let data_value = vec![7; 64];
+
+        let mut pulse = nebula_contract
+            .add_pulse(solana_program::hash::hash(&data_value).to_bytes().to_vec(), 10, 1000)
+            .unwrap();
+        assert_eq!((pulse.pulse_id, pulse.slot, pulse.unix_timestamp), (1, 10, 1000));
+
+        assert!(matches!(
+            nebula_contract.validate_pulse(&pulse, &[8; 64]),
+            Err(NebulaError::PulseIDHasNotBeenPersisted)
+        ));
+        nebula_contract.validate_pulse(&pulse, &data_value).unwrap();
+
+        let capacity = Pulse::capacity(Pulse::packed_len(oracles.len()));
+        assert_eq!(capacity, oracles.len());
+        assert_eq!(pulse.confirm(&oracles[0], capacity).unwrap(), 1);
+        assert_eq!(pulse.confirm(&oracles[0], capacity).unwrap(), 1);
+        assert_eq!(pulse.confirm(&oracles[1], capacity).unwrap(), 2);
+        assert_eq!(pulse.confirm(&oracles[1], capacity).unwrap(), 2);
+
+        // e.g. an oracle added by a rotation after the pulse account has been sized
+        assert!(matches!(
+            pulse.confirm(&Pubkey::new_unique(), capacity),
+            Err(NebulaError::PulseConfirmationsFull)
+        ));
+
+        let mut dst = vec![0; Pulse::packed_len(oracles.len())];
+        pulse.pack_into_account(&mut dst).unwrap();
+        assert_eq!(Pulse::unpack_from_account(&dst).unwrap(), pulse);
+        assert!(pulse.pack_into_account(&mut dst[1..]).is_err());
+    }
+
+    #[test]
+    fn test_pulse_retention() {
+        let mut nebula_contract = NebulaContract::default();
+        let depth = NebulaContract::DEFAULT_PULSE_RETENTION as u64;
+        let data_value = vec![7; 64];
+
+        let first_pulse = nebula_contract
+            .add_pulse(solana_program::hash::hash(&data_value).to_bytes().to_vec(), 0, 0)
+            .unwrap();
+        for _ in 1..depth {
+            nebula_contract.add_pulse(vec![1; 32], 0, 0).unwrap();
         }
+        nebula_contract.validate_pulse(&first_pulse, &data_value).unwrap();
+
+        nebula_contract.add_pulse(vec![2; 32], 0, 0).unwrap();
+        assert!(nebula_contract.is_pulse_expired(first_pulse.pulse_id));
+        assert!(matches!(
+            nebula_contract.validate_pulse(&first_pulse, &data_value),
+            Err(NebulaError::PulseExpired)
+        ));
+
+        assert!(matches!(
+            nebula_contract.set_pulse_retention(NebulaContract::MAX_PULSE_RETENTION + 1),
+            Err(NebulaError::InvalidPulseRetention)
+        ));
+        nebula_contract.set_pulse_retention(NebulaContract::MAX_PULSE_RETENTION).unwrap();
+        assert!(!nebula_contract.is_pulse_expired(first_pulse.pulse_id));
+    }
+
+    #[test]
+    fn test_pack_full_storage() {
+        let mut nebula_contract = NebulaContract::default();
+        nebula_contract.oracles = (0..50).map(|_| Pubkey::new_unique()).collect();
+
+        let mut dst = vec![0; NebulaContract::LEN];
+        let err = NebulaContract::pack(nebula_contract, &mut dst).unwrap_err();
+        assert_eq!(err, NebulaError::NebulaStorageIsFull.into());
+    }
+
+    #[test]
+    fn test_fan_out_delivery() {
+        let mut nebula_contract = NebulaContract::default();
+        let sender = Pubkey::new_unique();
+
+        let mut subscriptions = vec![
+            nebula_contract.subscribe(sender, Pubkey::new_unique(), 1, [0; 32], vec![]).unwrap(),
+            nebula_contract.subscribe(sender, Pubkey::new_unique(), 1, [0; 32], vec![]).unwrap(),
+        ];
+        let mut pulse = nebula_contract.add_pulse(vec![0; 32], 0, 0).unwrap();
+        let late_subscription = nebula_contract.subscribe(sender, Pubkey::new_unique(), 1, [0; 32], vec![]).unwrap();
+
+        assert!(matches!(
+            nebula_contract.validate_delivery(&pulse, &late_subscription),
+            Err(NebulaError::PulseIsOlderThanSubscription)
+        ));
+
+        nebula_contract.validate_delivery(&pulse, &subscriptions[0]).unwrap();
+        subscriptions[0].last_delivered_pulse_id = pulse.pulse_id;
+        assert!(!pulse.mark_served());
+        assert!(matches!(
+            nebula_contract.validate_delivery(&pulse, &subscriptions[0]),
+            Err(NebulaError::SubscriberValueBeenSent)
+        ));
 
-        match self.subscriptions_map.get(&subscription_id) {
-            Some(v) => Ok(v.clone()),
-            None => return Err(NebulaError::InvalidSubscriptionID.into()),
+        nebula_contract.validate_delivery(&pulse, &subscriptions[1]).unwrap();
+        subscriptions[1].last_delivered_pulse_id = pulse.pulse_id;
+        assert!(pulse.mark_served());
+    }
+
+    #[test]
+    fn test_subscriber_accounts() {
+        let data_account = Pubkey::new_unique();
+        let subscription = Subscription {
+            accounts: vec![
+                SubscriberAccount { pubkey: data_account, is_writable: true },
+                SubscriberAccount { pubkey: Pubkey::default(), is_writable: true },
+            ],
+            ..Subscription::default()
+        };
+
+        let recipient = AccountMeta::new(Pubkey::new_unique(), false);
+        subscription
+            .validate_accounts(&[AccountMeta::new(data_account, false), recipient.clone()])
+            .unwrap();
+
+        for accounts in [
+            vec![AccountMeta::new(data_account, false)],
+            vec![AccountMeta::new(Pubkey::new_unique(), false), recipient.clone()],
+            vec![AccountMeta::new_readonly(data_account, false), recipient.clone()],
+        ]
+        .iter()
+        {
+            assert!(matches!(
+                subscription.validate_accounts(accounts),
+                Err(NebulaError::InvalidSubscriberAccounts)
+            ));
         }
     }
+
+    #[test]
+    fn test_spoofed_subscriber() {
+        let contract_address = Pubkey::new_unique();
+        let subscription = Subscription {
+            contract_address,
+            ..Subscription::default()
+        };
+
+        subscription.validate_subscriber(&contract_address, true, Some(&contract_address)).unwrap();
+        subscription.validate_subscriber(&contract_address, true, None).unwrap();
+
+        let spoofed = Pubkey::new_unique();
+        assert!(matches!(
+            subscription.validate_subscriber(&spoofed, true, Some(&contract_address)),
+            Err(NebulaError::InvalidSubscriptionProgramID)
+        ));
+        assert!(matches!(
+            subscription.validate_subscriber(&contract_address, false, Some(&contract_address)),
+            Err(NebulaError::InvalidSubscriptionProgramID)
+        ));
+        assert!(matches!(
+            subscription.validate_subscriber(&contract_address, true, Some(&spoofed)),
+            Err(NebulaError::InvalidSubscriberDataAccount)
+        ));
+    }
+
+    #[test]
+    fn test_oracle_signatures() {
+        let mut nebula_contract = NebulaContract::default();
+        nebula_contract.oracles = vec![Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        nebula_contract.bft = 2;
+
+        let outsider = Pubkey::new_unique();
+        assert!(matches!(
+            nebula_contract.validate_oracle_signatures(&[nebula_contract.oracles[0], outsider]),
+            Err(NebulaError::NotEnoughOracleSignatures)
+        ));
+
+        let bitmap = nebula_contract
+            .validate_oracle_signatures(&[nebula_contract.oracles[2], outsider, nebula_contract.oracles[0]])
+            .unwrap();
+        assert_eq!(bitmap, 0b101);
+
+        let nebula = Pubkey::new_unique();
+        assert_ne!(
+            NebulaContract::pulse_message(&nebula, 1, &[0; 32]),
+            NebulaContract::pulse_message(&nebula, 2, &[0; 32])
+        );
+    }
+
+    #[test]
+    fn test_oracles_limit() {
+        let mut nebula_contract = NebulaContract::default();
+        nebula_contract.oracles = (0..NebulaContract::MAX_ORACLES).map(|_| Pubkey::new_unique()).collect();
+        NebulaContract::validate_oracles(&nebula_contract.oracles).unwrap();
+
+        let last_oracle = nebula_contract.oracles[NebulaContract::MAX_ORACLES - 1];
+        assert_eq!(nebula_contract.oracles_bitmap(&[last_oracle]), 1 << 63);
+
+        nebula_contract.oracles.push(Pubkey::new_unique());
+        assert!(matches!(
+            NebulaContract::validate_oracles(&nebula_contract.oracles),
+            Err(NebulaError::TooManyOracles)
+        ));
+    }
+
+    #[test]
+    fn test_feed_streams() {
+        let mut nebula_contract = NebulaContract::default();
+        nebula_contract.data_type = DataType::Int64;
+        let sender = Pubkey::new_unique();
+
+        assert!(matches!(
+            nebula_contract.create_feed(NebulaContract::DEFAULT_FEED_ID, DataType::String),
+            Err(NebulaError::InvalidFeedAccount)
+        ));
+
+        nebula_contract.subscribe(sender, Pubkey::new_unique(), 1, [0; 32], vec![]).unwrap();
+        nebula_contract.add_pulse(vec![0; 32], 0, 0).unwrap();
+
+        let mut feed = nebula_contract.create_feed(1, DataType::String).unwrap();
+        nebula_contract.swap_feed(&mut feed);
+        assert_eq!(nebula_contract.data_type, DataType::String);
+        let pulse = nebula_contract.add_pulse(vec![0; 32], 0, 0).unwrap();
+        assert_eq!(pulse.pulse_id, 1);
+        assert_eq!(pulse.subscriptions_count, 0);
+        nebula_contract.swap_feed(&mut feed);
+
+        assert_eq!(nebula_contract.data_type, DataType::Int64);
+        assert_eq!((nebula_contract.last_pulse_id, nebula_contract.subscriptions_count), (1, 1));
+        assert_eq!((feed.last_pulse_id, feed.subscriptions_count), (1, 0));
+    }
 }